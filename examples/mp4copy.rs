@@ -5,7 +5,7 @@ use std::io::{self, BufReader, BufWriter};
 use std::path::Path;
 
 use mp4::{
-    AacConfig, AvcConfig, HevcConfig, MediaConfig, MediaType, Mp4Config, Result, TrackConfig,
+    AacConfig, AvcConfig, MediaConfig, MediaType, Mp4Config, Result, SampleOrder, TrackConfig,
     TtxtConfig, Vp9Config,
 };
 
@@ -38,11 +38,16 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
             minor_version: mp4_reader.minor_version(),
             compatible_brands: mp4_reader.compatible_brands().to_vec(),
             timescale: mp4_reader.timescale(),
+            reserved_moov_size: None,
+            interleave_duration: None,
         },
     )?;
 
-    // TODO interleaving
-    for track in mp4_reader.tracks().values() {
+    // Tracks are numbered from 1 in the order they are added.
+    let mut track_ids: Vec<u32> = mp4_reader.tracks().keys().copied().collect();
+    track_ids.sort_unstable();
+    for track_id in track_ids.iter() {
+        let track = mp4_reader.tracks().get(track_id).unwrap();
         let media_conf = match track.media_type()? {
             MediaType::H264 => MediaConfig::AvcConfig(AvcConfig {
                 width: track.width(),
@@ -50,20 +55,19 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
                 seq_param_set: track.sequence_parameter_set()?.to_vec(),
                 pic_param_set: track.picture_parameter_set()?.to_vec(),
             }),
-            MediaType::H265 => MediaConfig::HevcConfig(HevcConfig {
-                width: track.width(),
-                height: track.height(),
-            }),
+            MediaType::H265 => MediaConfig::HevcConfig(track.hevc_config()?),
             MediaType::VP9 => MediaConfig::Vp9Config(Vp9Config {
                 width: track.width(),
                 height: track.height(),
             }),
+            MediaType::AV1 => MediaConfig::Av1Config(track.av1_config()?),
             MediaType::AAC => MediaConfig::AacConfig(AacConfig {
                 bitrate: track.bitrate(),
                 profile: track.audio_profile()?,
                 freq_index: track.sample_freq_index()?,
                 chan_conf: track.channel_config()?,
             }),
+            MediaType::OPUS => MediaConfig::OpusConfig(track.opus_config()?),
             MediaType::TTXT => MediaConfig::TtxtConfig(TtxtConfig {}),
         };
 
@@ -72,19 +76,21 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
             timescale: track.timescale(),
             language: track.language().to_string(),
             media_conf,
+            edit_list: track.edit_list().to_vec(),
+            chunk_strategy: None,
+            encryption: None,
         };
 
         mp4_writer.add_track(&track_conf)?;
     }
+    for unknown in mp4_reader.unknown_boxes.iter() {
+        mp4_writer.add_unknown_box(unknown.clone());
+    }
 
-    for track_id in mp4_reader.tracks().keys().copied().collect::<Vec<u32>>() {
-        let sample_count = mp4_reader.sample_count(track_id)?;
-        for sample_idx in 0..sample_count {
-            let sample_id = sample_idx + 1;
-            let sample = mp4_reader.read_sample(track_id, sample_id)?.unwrap();
-            mp4_writer.write_sample(track_id, &sample)?;
-            // println!("copy {}:({})", sample_id, sample);
-        }
+    for item in mp4_reader.interleaved_samples(SampleOrder::FileOffset) {
+        let (track_id, sample) = item?;
+        let dst_track_id = track_ids.iter().position(|id| *id == track_id).unwrap() as u32 + 1;
+        mp4_writer.write_sample(dst_track_id, &sample)?;
     }
 
     mp4_writer.write_end()?;
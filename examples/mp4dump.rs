@@ -56,14 +56,16 @@ fn get_boxes(file: File) -> Result<Vec<Box>> {
         if let Some(mehd) = &mvex.mehd {
             boxes.push(build_box(mehd));
         }
-        boxes.push(build_box(&mvex.trex));
+        for trex in mvex.trexs.iter() {
+            boxes.push(build_box(trex));
+        }
     }
 
     // trak.
     for track in mp4.tracks().values() {
-        boxes.push(build_box(&track.trak));
-        boxes.push(build_box(&track.trak.tkhd));
-        if let Some(ref edts) = track.trak.edts {
+        boxes.push(build_box(track.trak()));
+        boxes.push(build_box(&track.trak().tkhd));
+        if let Some(ref edts) = track.trak().edts {
             boxes.push(build_box(edts));
             if let Some(ref elst) = edts.elst {
                 boxes.push(build_box(elst));
@@ -71,14 +73,14 @@ fn get_boxes(file: File) -> Result<Vec<Box>> {
         }
 
         // trak.mdia
-        let mdia = &track.trak.mdia;
+        let mdia = &track.trak().mdia;
         boxes.push(build_box(mdia));
         boxes.push(build_box(&mdia.mdhd));
         boxes.push(build_box(&mdia.hdlr));
-        boxes.push(build_box(&track.trak.mdia.minf));
+        boxes.push(build_box(&track.trak().mdia.minf));
 
         // trak.mdia.minf
-        let minf = &track.trak.mdia.minf;
+        let minf = &track.trak().mdia.minf;
         if let Some(ref vmhd) = &minf.vmhd {
             boxes.push(build_box(vmhd));
         }
@@ -87,17 +89,11 @@ fn get_boxes(file: File) -> Result<Vec<Box>> {
         }
 
         // trak.mdia.minf.stbl
-        let stbl = &track.trak.mdia.minf.stbl;
+        let stbl = &track.trak().mdia.minf.stbl;
         boxes.push(build_box(stbl));
         boxes.push(build_box(&stbl.stsd));
-        if let Some(ref avc1) = &stbl.stsd.avc1 {
-            boxes.push(build_box(avc1));
-        }
-        if let Some(ref hev1) = &stbl.stsd.hev1 {
-            boxes.push(build_box(hev1));
-        }
-        if let Some(ref mp4a) = &stbl.stsd.mp4a {
-            boxes.push(build_box(mp4a));
+        for entry in stbl.stsd.entries.iter() {
+            boxes.push(build_box(entry));
         }
         boxes.push(build_box(&stbl.stts));
         if let Some(ref ctts) = &stbl.ctts {
@@ -123,12 +119,20 @@ fn get_boxes(file: File) -> Result<Vec<Box>> {
         for traf in moof.trafs.iter() {
             boxes.push(build_box(traf));
             boxes.push(build_box(&traf.tfhd));
-            if let Some(ref trun) = &traf.trun {
+            for trun in traf.truns.iter() {
                 boxes.push(build_box(trun));
             }
         }
     }
 
+    if let Some(ref mfra) = mp4.mfra {
+        boxes.push(build_box(mfra));
+        for tfra in mfra.tfras.iter() {
+            boxes.push(build_box(tfra));
+        }
+        boxes.push(build_box(&mfra.mfro));
+    }
+
     Ok(boxes)
 }
 
@@ -0,0 +1,127 @@
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::time::Duration;
+
+use mp4::{
+    AacConfig, AvcConfig, MediaConfig, MediaType, Mp4Config, Mp4FragmentedWriter, Result,
+    TrackConfig, TtxtConfig, Vp9Config,
+};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Usage: mp4stream <source file> > <target file>");
+        std::process::exit(1);
+    }
+
+    if let Err(err) = stream(&args[1]) {
+        let _ = writeln!(io::stderr(), "{}", err);
+    }
+}
+
+fn stream<P: AsRef<Path>>(src_filename: &P) -> Result<()> {
+    let src_file = File::open(src_filename)?;
+    let size = src_file.metadata()?.len();
+    let reader = BufReader::new(src_file);
+
+    // stdout is not seekable, so the output has to be fragmented.
+    let stdout = io::stdout();
+    let writer = stdout.lock();
+
+    let mut mp4_reader = mp4::Mp4Reader::read_header(reader, size)?;
+    let mut mp4_writer = Mp4FragmentedWriter::write_start(
+        writer,
+        &Mp4Config {
+            major_brand: str::parse("iso5").unwrap(),
+            minor_version: 512,
+            compatible_brands: vec![
+                str::parse("iso5").unwrap(),
+                str::parse("iso6").unwrap(),
+                str::parse("mp41").unwrap(),
+            ],
+            timescale: mp4_reader.timescale(),
+            reserved_moov_size: None,
+            interleave_duration: None,
+        },
+    )?;
+    mp4_writer.set_fragment_duration(Some(Duration::from_secs(2)));
+
+    let mut track_ids: Vec<u32> = mp4_reader.tracks().keys().copied().collect();
+    track_ids.sort();
+
+    for track_id in track_ids.iter() {
+        let track = mp4_reader.tracks().get(track_id).unwrap();
+        let media_conf = match track.media_type()? {
+            MediaType::H264 => MediaConfig::AvcConfig(AvcConfig {
+                width: track.width(),
+                height: track.height(),
+                seq_param_set: track.sequence_parameter_set()?.to_vec(),
+                pic_param_set: track.picture_parameter_set()?.to_vec(),
+            }),
+            MediaType::H265 => MediaConfig::HevcConfig(track.hevc_config()?),
+            MediaType::VP9 => MediaConfig::Vp9Config(Vp9Config {
+                width: track.width(),
+                height: track.height(),
+            }),
+            MediaType::AV1 => MediaConfig::Av1Config(track.av1_config()?),
+            MediaType::AAC => MediaConfig::AacConfig(AacConfig {
+                bitrate: track.bitrate(),
+                profile: track.audio_profile()?,
+                freq_index: track.sample_freq_index()?,
+                chan_conf: track.channel_config()?,
+            }),
+            MediaType::OPUS => MediaConfig::OpusConfig(track.opus_config()?),
+            MediaType::TTXT => MediaConfig::TtxtConfig(TtxtConfig {}),
+        };
+
+        let track_conf = TrackConfig {
+            track_type: track.track_type()?,
+            timescale: track.timescale(),
+            language: track.language().to_string(),
+            media_conf,
+            edit_list: track.edit_list().to_vec(),
+            chunk_strategy: None,
+            encryption: None,
+        };
+
+        mp4_writer.add_track(&track_conf)?;
+    }
+
+    // Write the samples of all tracks in decode order.
+    let mut next_sample_ids = vec![1u32; track_ids.len()];
+    let mut pending: Vec<Option<mp4::Mp4Sample>> = track_ids.iter().map(|_| None).collect();
+    loop {
+        let mut next: Option<(usize, f64)> = None;
+        for (idx, track_id) in track_ids.iter().enumerate() {
+            if pending[idx].is_none()
+                && next_sample_ids[idx] <= mp4_reader.sample_count(*track_id)?
+            {
+                pending[idx] = mp4_reader.read_sample(*track_id, next_sample_ids[idx])?;
+                next_sample_ids[idx] += 1;
+            }
+            if let Some(ref sample) = pending[idx] {
+                let timescale = mp4_reader.tracks().get(track_id).unwrap().timescale();
+                let time = sample.start_time as f64 / timescale as f64;
+                if next.map(|(_, t)| time < t).unwrap_or(true) {
+                    next = Some((idx, time));
+                }
+            }
+        }
+
+        let idx = match next {
+            Some((idx, _)) => idx,
+            None => break,
+        };
+        let sample = pending[idx].take().unwrap();
+        // Writer track ids are assigned in the order the tracks were added.
+        mp4_writer.write_sample(idx as u32 + 1, &sample)?;
+    }
+
+    mp4_writer.write_end()?;
+
+    Ok(())
+}
@@ -12,6 +12,8 @@ fn main() -> mp4::Result<()> {
             str::parse("mp41").unwrap(),
         ],
         timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
     };
 
     let data = Cursor::new(Vec::<u8>::new());
@@ -0,0 +1,355 @@
+use byteorder::{BigEndian, ReadBytesExt};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::mp4box::*;
+use crate::*;
+
+/// Location of a top-level box in the file.
+#[derive(Debug, Clone, Copy)]
+struct BoxRange {
+    box_type: BoxType,
+    offset: u64,
+    header_size: u64,
+    size: u64,
+}
+
+impl BoxRange {
+    fn end(&self) -> u64 {
+        self.offset + self.size
+    }
+}
+
+/// Editor for the `moov.udta` and `moov.meta` metadata of an existing MP4
+/// file.
+///
+/// Only the `moov` box is read into memory. On [Mp4MetadataEditor::save] the
+/// new `moov` is written back over the old one when it fits in the space
+/// taken by the old `moov` and any `free` boxes next to it. Otherwise the old
+/// `moov` is turned into a `free` box and the new one is appended to the end
+/// of the file. The media data never moves, so chunk offsets stay valid and
+/// large files can be tagged without rewriting `mdat`.
+///
+/// The tag helpers such as [Mp4MetadataEditor::set_title] edit the `ilst` of
+/// `moov.udta.meta`, which is where [Mp4Reader::metadata] reads tags from.
+/// A `meta` box directly in `moov` can be edited through
+/// [Mp4MetadataEditor::meta_mut]. Boxes inside `moov` are written back byte
+/// for byte unless they were edited.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::OpenOptions;
+/// use mp4::{Metadata, Mp4MetadataEditor};
+///
+/// # fn main() -> mp4::Result<()> {
+/// let file = OpenOptions::new().read(true).write(true).open("movie.mp4")?;
+/// let size = file.metadata()?.len();
+///
+/// let mut editor = Mp4MetadataEditor::read_header(file, size)?;
+/// editor.set_title("Big Buck Bunny")?;
+/// editor.set_year(2008)?;
+/// editor.save()?;
+/// assert_eq!(editor.metadata().title(), Some("Big Buck Bunny".into()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Mp4MetadataEditor<F> {
+    file: F,
+    size: u64,
+    boxes: Vec<BoxRange>,
+    moov_index: usize,
+
+    // Children of moov, without the moov header.
+    moov_data: Vec<u8>,
+    udta: UdtaBox,
+    meta: Option<MetaBox>,
+    // The udta and meta boxes in moov_data, to tell whether they were edited.
+    saved_udta: UdtaBox,
+    saved_meta: Option<MetaBox>,
+}
+
+impl<F: Read + Write + Seek> Mp4MetadataEditor<F> {
+    pub fn read_header(mut file: F, size: u64) -> Result<Self> {
+        let boxes = read_box_ranges(&mut file, size)?;
+        let moov_index = boxes
+            .iter()
+            .position(|b| b.box_type == BoxType::MoovBox)
+            .ok_or(Error::BoxNotFound(BoxType::MoovBox))?;
+
+        let moov = boxes[moov_index];
+        let mut moov_data = vec![0u8; (moov.size - moov.header_size) as usize];
+        file.seek(SeekFrom::Start(moov.offset + moov.header_size))?;
+        file.read_exact(&mut moov_data)?;
+
+        let (udta, meta) = read_metadata_boxes(&moov_data)?;
+
+        Ok(Mp4MetadataEditor {
+            file,
+            size,
+            boxes,
+            moov_index,
+            moov_data,
+            udta: udta.clone(),
+            meta: meta.clone(),
+            saved_udta: udta,
+            saved_meta: meta,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn udta(&self) -> &UdtaBox {
+        &self.udta
+    }
+
+    pub fn udta_mut(&mut self) -> &mut UdtaBox {
+        &mut self.udta
+    }
+
+    /// Returns the `meta` box directly in `moov`, if any.
+    pub fn meta(&self) -> Option<&MetaBox> {
+        self.meta.as_ref()
+    }
+
+    /// Returns the `meta` box directly in `moov`. Setting it to `None`
+    /// removes the box.
+    pub fn meta_mut(&mut self) -> &mut Option<MetaBox> {
+        &mut self.meta
+    }
+
+    pub fn metadata(&self) -> impl Metadata<'_> {
+        self.udta.meta.as_ref().and_then(|meta| match meta {
+            MetaBox::Mdir { ilst } => ilst.as_ref(),
+            _ => None,
+        })
+    }
+
+    /// Returns the `ilst` box, creating `meta` and `ilst` if needed.
+    pub fn ilst_mut(&mut self) -> Result<&mut IlstBox> {
+        let meta = self.udta.meta.get_or_insert(MetaBox::Mdir { ilst: None });
+        match meta {
+            MetaBox::Mdir { ilst } => Ok(ilst.get_or_insert_with(IlstBox::default)),
+            _ => Err(Error::InvalidData("meta box does not use the mdir handler")),
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) -> Result<()> {
+        self.set_item(
+            MetadataKey::Title,
+            DataType::Text,
+            title.as_bytes().to_vec(),
+        )
+    }
+
+    pub fn set_year(&mut self, year: u32) -> Result<()> {
+        self.set_item(
+            MetadataKey::Year,
+            DataType::Text,
+            year.to_string().into_bytes(),
+        )
+    }
+
+    pub fn set_poster(&mut self, poster: Vec<u8>) -> Result<()> {
+        self.set_item(MetadataKey::Poster, DataType::Binary, poster)
+    }
+
+    pub fn set_summary(&mut self, summary: &str) -> Result<()> {
+        self.set_item(
+            MetadataKey::Summary,
+            DataType::Text,
+            summary.as_bytes().to_vec(),
+        )
+    }
+
+    pub fn remove(&mut self, key: MetadataKey) -> Result<()> {
+        self.ilst_mut()?.items.remove(&key);
+        Ok(())
+    }
+
+    fn set_item(&mut self, key: MetadataKey, data_type: DataType, data: Vec<u8>) -> Result<()> {
+        let item = IlstItemBox {
+            data: DataBox { data, data_type },
+        };
+        self.ilst_mut()?.items.insert(key, item);
+        Ok(())
+    }
+
+    /// Writes the edited `moov` box back to the file, in place if it fits in
+    /// the old `moov` and the `free` boxes around it. Otherwise the new `moov`
+    /// is appended to the end of the file, and only then the old one becomes
+    /// a `free` box. Chunk offsets in `stco` and `co64` are never patched, as `mdat`
+    /// does not move.
+    pub fn save(&mut self) -> Result<()> {
+        let moov = self.moov_bytes()?;
+        let moov_size = moov.len() as u64;
+
+        // Space the new moov can take without touching any other box.
+        let mut first = self.moov_index;
+        while first > 0 && self.boxes[first - 1].box_type == BoxType::FreeBox {
+            first -= 1;
+        }
+        let mut last = self.moov_index;
+        while last + 1 < self.boxes.len() && self.boxes[last + 1].box_type == BoxType::FreeBox {
+            last += 1;
+        }
+        let start = self.boxes[first].offset;
+        let end = self.boxes[last].end();
+        let at_end = end == self.size;
+
+        if moov_size == end - start || moov_size + HEADER_SIZE <= end - start || at_end {
+            self.file.seek(SeekFrom::Start(start))?;
+            self.file.write_all(&moov)?;
+            if moov_size < end - start {
+                let free_size = (end - start - moov_size).max(HEADER_SIZE);
+                write_free_box(&mut self.file, free_size)?;
+            }
+        } else {
+            // A box that extends to the end of the file must get an explicit
+            // size before anything is appended after it.
+            let tail = self.boxes[self.boxes.len() - 1];
+            if tail.header_size == HEADER_SIZE {
+                self.file.seek(SeekFrom::Start(tail.offset))?;
+                if self.file.read_u32::<BigEndian>()? == 0 {
+                    if tail.size > u32::MAX as u64 {
+                        return Err(Error::InvalidData(
+                            "last box is too large to be given an explicit size",
+                        ));
+                    }
+                    self.file.seek(SeekFrom::Start(tail.offset))?;
+                    BoxHeader::new(tail.box_type, tail.size).write(&mut self.file)?;
+                }
+            }
+
+            // Append the new moov before freeing the old one, so that an
+            // error in between leaves a file with two moov boxes rather than
+            // none.
+            self.file.seek(SeekFrom::Start(self.size))?;
+            self.file.write_all(&moov)?;
+            self.file.flush()?;
+            self.file.seek(SeekFrom::Start(start))?;
+            BoxHeader::new(BoxType::FreeBox, end - start).write(&mut self.file)?;
+        }
+        self.file.flush()?;
+
+        self.size = self.file.seek(SeekFrom::End(0))?;
+        self.boxes = read_box_ranges(&mut self.file, self.size)?;
+        self.moov_index = self
+            .boxes
+            .iter()
+            .position(|b| b.box_type == BoxType::MoovBox)
+            .ok_or(Error::BoxNotFound(BoxType::MoovBox))?;
+        let header_size = self.boxes[self.moov_index].header_size as usize;
+        self.moov_data = moov[header_size..].to_vec();
+        self.saved_udta = self.udta.clone();
+        self.saved_meta = self.meta.clone();
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
+    fn moov_bytes(&self) -> Result<Vec<u8>> {
+        let udta_edited = self.udta != self.saved_udta;
+        let meta_edited = self.meta != self.saved_meta;
+
+        // The edited boxes replace the first box of their type.
+        let mut children = Vec::with_capacity(self.moov_data.len());
+        let mut udta_written = !udta_edited;
+        let mut meta_written = !meta_edited;
+        let moov_size = self.moov_data.len() as u64;
+        for child in read_box_ranges(&mut Cursor::new(&self.moov_data), moov_size)? {
+            match child.box_type {
+                BoxType::UdtaBox if udta_edited => {
+                    if !udta_written {
+                        self.udta.write_box(&mut children)?;
+                        udta_written = true;
+                    }
+                }
+                BoxType::MetaBox if meta_edited => {
+                    if !meta_written {
+                        if let Some(ref meta) = self.meta {
+                            meta.write_box(&mut children)?;
+                        }
+                        meta_written = true;
+                    }
+                }
+                _ => children.extend_from_slice(
+                    &self.moov_data[child.offset as usize..child.end() as usize],
+                ),
+            }
+        }
+        if !udta_written {
+            self.udta.write_box(&mut children)?;
+        }
+        if let (false, Some(meta)) = (meta_written, &self.meta) {
+            meta.write_box(&mut children)?;
+        }
+
+        let mut size = HEADER_SIZE + children.len() as u64;
+        if size > u32::MAX as u64 {
+            size += 8;
+        }
+        let mut moov = Vec::with_capacity(size as usize);
+        BoxHeader::new(BoxType::MoovBox, size).write(&mut moov)?;
+        moov.extend_from_slice(&children);
+        Ok(moov)
+    }
+}
+
+/// Reads the `udta` and `meta` boxes among the children of `moov`.
+fn read_metadata_boxes(moov_data: &[u8]) -> Result<(UdtaBox, Option<MetaBox>)> {
+    let mut reader = Cursor::new(moov_data);
+    let mut udta = None;
+    let mut meta = None;
+    for child in read_box_ranges(&mut reader, moov_data.len() as u64)? {
+        reader.set_position(child.offset);
+        match child.box_type {
+            BoxType::UdtaBox if udta.is_none() => {
+                let header = BoxHeader::read(&mut reader)?;
+                udta = Some(UdtaBox::read_box(&mut reader, header.size)?);
+            }
+            BoxType::MetaBox if meta.is_none() => {
+                let header = BoxHeader::read(&mut reader)?;
+                meta = Some(MetaBox::read_box(&mut reader, header.size)?);
+            }
+            _ => {}
+        }
+    }
+    Ok((udta.unwrap_or_default(), meta))
+}
+
+fn write_free_box<W: Write>(writer: &mut W, size: u64) -> Result<()> {
+    BoxHeader::new(BoxType::FreeBox, size).write(writer)?;
+    io::copy(&mut io::repeat(0).take(size - HEADER_SIZE), writer)?;
+    Ok(())
+}
+
+fn read_box_ranges<R: Read + Seek>(reader: &mut R, size: u64) -> Result<Vec<BoxRange>> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        reader.seek(SeekFrom::Start(offset))?;
+        let box_size = reader.read_u32::<BigEndian>()?;
+        let box_type = BoxType::from(reader.read_u32::<BigEndian>()?);
+        let (header_size, box_size) = match box_size {
+            0 => (HEADER_SIZE, size - offset),
+            1 => (HEADER_SIZE + 8, reader.read_u64::<BigEndian>()?),
+            _ => (HEADER_SIZE, box_size as u64),
+        };
+        if box_size < header_size || box_size > size - offset {
+            return Err(Error::InvalidData("box size does not fit in its parent"));
+        }
+        boxes.push(BoxRange {
+            box_type,
+            offset,
+            header_size,
+            size: box_size,
+        });
+        offset += box_size;
+    }
+    Ok(boxes)
+}
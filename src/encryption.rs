@@ -0,0 +1,461 @@
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, StreamCipher};
+use aes::{Aes128, Block};
+use std::ops::Range;
+
+use crate::*;
+
+type Aes128Ctr = ctr::Ctr64BE<Aes128>;
+type Aes128CbcEnc = cbc::Encryptor<Aes128>;
+type Aes128CbcDec = cbc::Decryptor<Aes128>;
+
+const BLOCK_SIZE: usize = 16;
+
+// Size of the NAL unit length prefix written by the AVC and HEVC configs.
+const NAL_LENGTH_SIZE: usize = 4;
+
+// Pattern of cbcs video tracks: one encrypted block out of every ten.
+const CBCS_CRYPT_BYTE_BLOCK: u8 = 1;
+const CBCS_SKIP_BYTE_BLOCK: u8 = 9;
+
+/// Decrypts a sample in place with its IV and subsample map and the pattern
+/// of `tenc`.
+pub(crate) fn decrypt_sample(
+    scheme: EncryptionScheme,
+    tenc: &TencBox,
+    key: &[u8; 16],
+    encryption: &SampleEncryption,
+    data: &mut [u8],
+) -> Result<()> {
+    let pattern = (tenc.default_crypt_byte_block, tenc.default_skip_byte_block);
+    apply_cipher(scheme, pattern, key, encryption, data, false)
+}
+
+fn apply_cipher(
+    scheme: EncryptionScheme,
+    pattern: (u8, u8),
+    key: &[u8; 16],
+    encryption: &SampleEncryption,
+    data: &mut [u8],
+    encrypt: bool,
+) -> Result<()> {
+    // 8-byte IVs are padded with zeros, which leaves room for the block
+    // counter of cenc.
+    let mut iv = [0u8; 16];
+    match encryption.iv.len() {
+        8 | 16 => iv[..encryption.iv.len()].copy_from_slice(&encryption.iv),
+        _ => return Err(Error::InvalidData("IV must be 8 or 16 bytes")),
+    }
+    let ranges = protected_ranges(data.len(), &encryption.subsamples)?;
+
+    match scheme {
+        EncryptionScheme::Cenc => {
+            // The counter runs on across all the protected ranges of a sample.
+            let mut cipher = Aes128Ctr::new(key.into(), &iv.into());
+            for range in ranges {
+                cipher.apply_keystream(&mut data[range]);
+            }
+        }
+        EncryptionScheme::Cbcs => {
+            // Every protected range starts over from the IV, and a trailing
+            // partial block is left in the clear.
+            for range in ranges {
+                let blocks = pattern_blocks(&mut data[range], pattern);
+                if encrypt {
+                    let mut cipher = Aes128CbcEnc::new(key.into(), &iv.into());
+                    blocks.for_each(|block| cipher.encrypt_block_mut(block));
+                } else {
+                    let mut cipher = Aes128CbcDec::new(key.into(), &iv.into());
+                    blocks.for_each(|block| cipher.decrypt_block_mut(block));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the protected byte ranges of a sample of `size` bytes.
+fn protected_ranges(size: usize, subsamples: &[SubsampleEntry]) -> Result<Vec<Range<usize>>> {
+    if subsamples.is_empty() {
+        return Ok(vec![Range {
+            start: 0,
+            end: size,
+        }]);
+    }
+    let mut ranges = Vec::with_capacity(subsamples.len());
+    let mut offset = 0;
+    for subsample in subsamples {
+        let start = offset + subsample.bytes_of_clear_data as usize;
+        let end = start + subsample.bytes_of_protected_data as usize;
+        if end > size {
+            return Err(Error::InvalidData("subsamples exceed the sample size"));
+        }
+        ranges.push(start..end);
+        offset = end;
+    }
+    Ok(ranges)
+}
+
+/// Returns the encrypted blocks of a protected range. A `(0, 0)` pattern
+/// encrypts every block.
+fn pattern_blocks(
+    data: &mut [u8],
+    (crypt_byte_block, skip_byte_block): (u8, u8),
+) -> impl Iterator<Item = &mut Block> {
+    let crypt = crypt_byte_block as usize;
+    let period = crypt + skip_byte_block as usize;
+    data.chunks_exact_mut(BLOCK_SIZE)
+        .enumerate()
+        .filter(move |(i, _)| period == 0 || i % period < crypt)
+        .map(|(_, block)| Block::from_mut_slice(block))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NalFormat {
+    Avc,
+    Hevc,
+}
+
+/// Encrypts the samples of a track written with an [`EncryptionConfig`].
+#[derive(Debug, Clone)]
+pub(crate) struct SampleEncryptor {
+    config: EncryptionConfig,
+    nal_format: Option<NalFormat>,
+    sample_count: u64,
+}
+
+impl SampleEncryptor {
+    pub(crate) fn new(track_id: u32, config: &EncryptionConfig, media_conf: &MediaConfig) -> Self {
+        let mut config = config.clone();
+        if config.scheme == EncryptionScheme::Cenc {
+            // Tracks sharing a key must not share counter blocks, so the
+            // track ID goes into the top half of the per-sample IV.
+            for (byte, id) in config.iv[..4].iter_mut().zip(track_id.to_be_bytes()) {
+                *byte ^= id;
+            }
+        }
+        let nal_format = match media_conf {
+            MediaConfig::AvcConfig(_) => Some(NalFormat::Avc),
+            MediaConfig::HevcConfig(_) => Some(NalFormat::Hevc),
+            _ => None,
+        };
+        SampleEncryptor {
+            config,
+            nal_format,
+            sample_count: 0,
+        }
+    }
+
+    fn pattern(&self) -> (u8, u8) {
+        match (self.config.scheme, self.nal_format) {
+            (EncryptionScheme::Cbcs, Some(_)) => (CBCS_CRYPT_BYTE_BLOCK, CBCS_SKIP_BYTE_BLOCK),
+            _ => (0, 0),
+        }
+    }
+
+    /// Returns the protection scheme information of the sample entries.
+    pub(crate) fn sinf(&self) -> SinfBox {
+        let (default_crypt_byte_block, default_skip_byte_block) = self.pattern();
+        let tenc = match self.config.scheme {
+            EncryptionScheme::Cenc => TencBox {
+                default_is_protected: 1,
+                default_per_sample_iv_size: 8,
+                default_kid: self.config.key_id,
+                ..TencBox::default()
+            },
+            EncryptionScheme::Cbcs => TencBox {
+                version: 1,
+                default_crypt_byte_block,
+                default_skip_byte_block,
+                default_is_protected: 1,
+                default_per_sample_iv_size: 0,
+                default_kid: self.config.key_id,
+                default_constant_iv: self.config.iv.to_vec(),
+                ..TencBox::default()
+            },
+        };
+        SinfBox {
+            schm: Some(SchmBox {
+                scheme_type: self.config.scheme.into(),
+                scheme_version: 0x00010000,
+                ..SchmBox::default()
+            }),
+            schi: Some(SchiBox {
+                tenc: Some(tenc),
+                ..SchiBox::default()
+            }),
+            ..SinfBox::default()
+        }
+    }
+
+    /// Encrypts the next sample, returning the encrypted data and the
+    /// auxiliary information to store for it.
+    pub(crate) fn encrypt(&mut self, bytes: &[u8]) -> Result<(Vec<u8>, SampleEncryption)> {
+        let subsamples = match self.nal_format {
+            Some(format) => nal_subsamples(format, bytes)?,
+            None => Vec::new(),
+        };
+        let (iv, stored_iv) = match self.config.scheme {
+            EncryptionScheme::Cenc => {
+                let mut prefix = [0u8; 8];
+                prefix.copy_from_slice(&self.config.iv[..8]);
+                let iv = u64::from_be_bytes(prefix)
+                    .wrapping_add(self.sample_count)
+                    .to_be_bytes()
+                    .to_vec();
+                (iv.clone(), iv)
+            }
+            EncryptionScheme::Cbcs => (self.config.iv.to_vec(), Vec::new()),
+        };
+        self.sample_count += 1;
+
+        let mut data = bytes.to_vec();
+        let mut encryption = SampleEncryption { iv, subsamples };
+        apply_cipher(
+            self.config.scheme,
+            self.pattern(),
+            &self.config.key,
+            &encryption,
+            &mut data,
+            true,
+        )?;
+        encryption.iv = stored_iv;
+        Ok((data, encryption))
+    }
+}
+
+/// Splits a sample of length prefixed NAL units into subsamples that leave
+/// the NAL unit headers and non-VCL NAL units in the clear. The protected
+/// part of a NAL unit is a whole number of blocks at its end.
+fn nal_subsamples(format: NalFormat, data: &[u8]) -> Result<Vec<SubsampleEntry>> {
+    let mut subsamples = Vec::new();
+    let mut clear = 0;
+    let mut offset = 0;
+    while offset < data.len() {
+        let nal_start = offset + NAL_LENGTH_SIZE;
+        if nal_start > data.len() {
+            return Err(Error::InvalidData("truncated NAL unit length"));
+        }
+        let mut length = [0u8; NAL_LENGTH_SIZE];
+        length.copy_from_slice(&data[offset..nal_start]);
+        let nal_size = u32::from_be_bytes(length) as usize;
+        let nal_end = nal_start + nal_size;
+        if nal_end > data.len() {
+            return Err(Error::InvalidData("NAL unit exceeds the sample size"));
+        }
+
+        let (header_size, is_vcl) = match format {
+            NalFormat::Avc => (1, nal_size > 0 && matches!(data[nal_start] & 0x1f, 1..=5)),
+            NalFormat::Hevc => (2, nal_size > 0 && (data[nal_start] >> 1) & 0x3f < 32),
+        };
+        let protected = if is_vcl && nal_size > header_size {
+            (nal_size - header_size) / BLOCK_SIZE * BLOCK_SIZE
+        } else {
+            0
+        };
+        clear += nal_end - offset - protected;
+        if protected > 0 {
+            push_subsample(&mut subsamples, clear, protected);
+            clear = 0;
+        }
+        offset = nal_end;
+    }
+    if clear > 0 || subsamples.is_empty() {
+        push_subsample(&mut subsamples, clear, 0);
+    }
+    Ok(subsamples)
+}
+
+fn push_subsample(subsamples: &mut Vec<SubsampleEntry>, mut clear: usize, protected: usize) {
+    while clear > u16::MAX as usize {
+        subsamples.push(SubsampleEntry {
+            bytes_of_clear_data: u16::MAX,
+            bytes_of_protected_data: 0,
+        });
+        clear -= u16::MAX as usize;
+    }
+    subsamples.push(SubsampleEntry {
+        bytes_of_clear_data: clear as u16,
+        bytes_of_protected_data: protected as u32,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 16] = [0x11; 16];
+
+    fn avc_sample() -> Vec<u8> {
+        let mut data = Vec::new();
+        // SEI, kept in the clear.
+        data.extend_from_slice(&[0, 0, 0, 5, 0x06, 1, 2, 3, 4]);
+        // IDR slice of 40 bytes, the last 32 of which are protected.
+        data.extend_from_slice(&[0, 0, 0, 40, 0x65]);
+        data.extend((0..39).map(|i| i as u8));
+        data
+    }
+
+    #[test]
+    fn test_nal_subsamples() {
+        let subsamples = nal_subsamples(NalFormat::Avc, &avc_sample()).unwrap();
+        assert_eq!(
+            subsamples,
+            vec![SubsampleEntry {
+                bytes_of_clear_data: 9 + 4 + 8,
+                bytes_of_protected_data: 32,
+            }]
+        );
+
+        let subsamples = nal_subsamples(NalFormat::Avc, &[0, 0, 0, 1, 0x06]).unwrap();
+        assert_eq!(
+            subsamples,
+            vec![SubsampleEntry {
+                bytes_of_clear_data: 5,
+                bytes_of_protected_data: 0,
+            }]
+        );
+        assert!(nal_subsamples(NalFormat::Avc, &[0, 0, 0, 9, 0x65]).is_err());
+    }
+
+    #[test]
+    fn test_cenc_roundtrip() {
+        let config = EncryptionConfig {
+            scheme: EncryptionScheme::Cenc,
+            key_id: [0x22; 16],
+            key: KEY,
+            iv: [0x33; 16],
+        };
+        let media_conf = MediaConfig::AvcConfig(AvcConfig::default());
+        let mut encryptor = SampleEncryptor::new(1, &config, &media_conf);
+        let tenc = encryptor.sinf().tenc().cloned().unwrap();
+        assert_eq!(tenc.default_per_sample_iv_size, 8);
+
+        let sample = avc_sample();
+        let (mut data, encryption) = encryptor.encrypt(&sample).unwrap();
+        assert_eq!(
+            encryption.iv,
+            vec![0x33, 0x33, 0x33, 0x32, 0x33, 0x33, 0x33, 0x33]
+        );
+        assert_eq!(data[..21], sample[..21]);
+        assert_ne!(data[21..], sample[21..]);
+        let encrypted = data.clone();
+
+        decrypt_sample(EncryptionScheme::Cenc, &tenc, &KEY, &encryption, &mut data).unwrap();
+        assert_eq!(data, sample);
+
+        let (_, encryption) = encryptor.encrypt(&sample).unwrap();
+        assert_eq!(
+            encryption.iv,
+            vec![0x33, 0x33, 0x33, 0x32, 0x33, 0x33, 0x33, 0x34]
+        );
+
+        // Another track with the same key and IV gets its own IVs.
+        let mut encryptor = SampleEncryptor::new(2, &config, &media_conf);
+        let (other, encryption) = encryptor.encrypt(&sample).unwrap();
+        assert_eq!(
+            encryption.iv,
+            vec![0x33, 0x33, 0x33, 0x31, 0x33, 0x33, 0x33, 0x33]
+        );
+        assert_ne!(other[21..], encrypted[21..]);
+    }
+
+    #[test]
+    fn test_cbcs_roundtrip() {
+        let config = EncryptionConfig {
+            scheme: EncryptionScheme::Cbcs,
+            key_id: [0x22; 16],
+            key: KEY,
+            iv: [0x44; 16],
+        };
+        let media_conf = MediaConfig::AacConfig(AacConfig::default());
+        let mut encryptor = SampleEncryptor::new(1, &config, &media_conf);
+        let mut tenc = encryptor.sinf().tenc().cloned().unwrap();
+        assert_eq!(tenc.default_constant_iv, vec![0x44; 16]);
+
+        // The trailing partial block stays in the clear.
+        let sample: Vec<u8> = (0..40).collect();
+        let (mut data, mut encryption) = encryptor.encrypt(&sample).unwrap();
+        assert!(encryption.iv.is_empty());
+        assert_ne!(data[..32], sample[..32]);
+        assert_eq!(data[32..], sample[32..]);
+
+        encryption.iv = tenc.default_constant_iv.clone();
+        decrypt_sample(EncryptionScheme::Cbcs, &tenc, &KEY, &encryption, &mut data).unwrap();
+        assert_eq!(data, sample);
+
+        // With a 1:1 pattern only every other block is encrypted.
+        tenc.default_crypt_byte_block = 1;
+        tenc.default_skip_byte_block = 1;
+        let mut data = sample.clone();
+        apply_cipher(
+            EncryptionScheme::Cbcs,
+            (1, 1),
+            &KEY,
+            &encryption,
+            &mut data,
+            true,
+        )
+        .unwrap();
+        assert_ne!(data[..16], sample[..16]);
+        assert_eq!(data[16..32], sample[16..32]);
+        decrypt_sample(EncryptionScheme::Cbcs, &tenc, &KEY, &encryption, &mut data).unwrap();
+        assert_eq!(data, sample);
+    }
+
+    #[test]
+    fn test_known_answers() {
+        // NIST SP 800-38A, F.2.1 and F.5.1.
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let plaintext = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a,
+        ];
+
+        let encryption = SampleEncryption {
+            iv: (0..16).collect(),
+            subsamples: vec![],
+        };
+        let mut data = plaintext;
+        apply_cipher(
+            EncryptionScheme::Cbcs,
+            (0, 0),
+            &key,
+            &encryption,
+            &mut data,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            data,
+            [
+                0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46, 0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9,
+                0x19, 0x7d
+            ]
+        );
+
+        let encryption = SampleEncryption {
+            iv: (0xf0..=0xff).collect(),
+            subsamples: vec![],
+        };
+        let mut data = plaintext;
+        apply_cipher(
+            EncryptionScheme::Cenc,
+            (0, 0),
+            &key,
+            &encryption,
+            &mut data,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            data,
+            [
+                0x87, 0x4d, 0x61, 0x91, 0xb6, 0x20, 0xe3, 0x26, 0x1b, 0xef, 0x68, 0x64, 0x99, 0x0d,
+                0xb6, 0xce
+            ]
+        );
+    }
+}
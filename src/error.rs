@@ -24,6 +24,10 @@ pub enum Error {
     EntryInStblNotFound(u32, BoxType, u32),
     #[error("traf[{0}].trun.{1}.entry[{2}] not found")]
     EntryInTrunNotFound(u32, BoxType, u32),
+    #[error("key for key ID {0:02x?} not found")]
+    KeyNotFound([u8; 16]),
+    #[error("moov box of {0} bytes does not fit in the reserved space")]
+    MoovTooLarge(u64),
     #[error("{0} version {1} is not supported")]
     UnsupportedBoxVersion(BoxType, u8),
 }
@@ -80,13 +80,19 @@ mod mp4box;
 pub use mp4box::*;
 
 mod track;
-pub use track::{Mp4Track, TrackConfig};
+pub use track::{ChunkStrategy, Mp4Track, TrackConfig};
 
 mod reader;
-pub use reader::Mp4Reader;
+pub use reader::{Mp4InterleavedSamples, Mp4Reader, Mp4Samples, SampleOrder};
 
 mod writer;
-pub use writer::{Mp4Config, Mp4Writer};
+pub use writer::{Mp4Config, Mp4FragmentedWriter, Mp4Writer};
+
+mod editor;
+pub use editor::Mp4MetadataEditor;
+
+#[cfg(feature = "encryption")]
+mod encryption;
 
 pub fn read_mp4(f: File) -> Result<Mp4Reader<BufReader<File>>> {
     let size = f.metadata()?.len();
@@ -0,0 +1,206 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// AV1CodecConfigurationBox, as defined by the AV1 ISOBMFF binding.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Av1CBox {
+    pub version: u8,
+    pub seq_profile: u8,
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: bool,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: u8,
+    pub initial_presentation_delay_minus_one: Option<u8>,
+
+    /// Zero or more OBUs, typically the sequence header OBU.
+    pub config_obus: Vec<u8>,
+}
+
+impl Av1CBox {
+    pub const DEFAULT_VERSION: u8 = 1;
+
+    pub fn new(config: &Av1Config) -> Self {
+        Av1CBox {
+            version: Av1CBox::DEFAULT_VERSION,
+            seq_profile: config.seq_profile,
+            seq_level_idx_0: config.seq_level_idx_0,
+            seq_tier_0: config.seq_tier_0,
+            high_bitdepth: config.bit_depth > 8,
+            twelve_bit: config.bit_depth == 12,
+            monochrome: config.monochrome,
+            chroma_subsampling_x: config.chroma_subsampling_x,
+            chroma_subsampling_y: config.chroma_subsampling_y,
+            chroma_sample_position: config.chroma_sample_position,
+            initial_presentation_delay_minus_one: None,
+            config_obus: config.config_obus.clone(),
+        }
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        match (self.high_bitdepth, self.twelve_bit) {
+            (true, true) => 12,
+            (true, false) => 10,
+            _ => 8,
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::Av1CBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 4 + self.config_obus.len() as u64
+    }
+}
+
+impl Mp4Box for Av1CBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "seq_profile={} seq_level_idx_0={} seq_tier_0={} bit_depth={} monochrome={}",
+            self.seq_profile,
+            self.seq_level_idx_0,
+            self.seq_tier_0,
+            self.bit_depth(),
+            self.monochrome
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Av1CBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let b = reader.read_u8()?;
+        if b & 0x80 == 0 {
+            return Err(Error::InvalidData("av1c marker bit not set"));
+        }
+        let version = b & 0x7f;
+
+        let b = reader.read_u8()?;
+        let seq_profile = b >> 5;
+        let seq_level_idx_0 = b & 0x1f;
+
+        let b = reader.read_u8()?;
+        let seq_tier_0 = b & 0x80 != 0;
+        let high_bitdepth = b & 0x40 != 0;
+        let twelve_bit = b & 0x20 != 0;
+        let monochrome = b & 0x10 != 0;
+        let chroma_subsampling_x = b & 0x08 != 0;
+        let chroma_subsampling_y = b & 0x04 != 0;
+        let chroma_sample_position = b & 0x03;
+
+        let b = reader.read_u8()?;
+        let initial_presentation_delay_minus_one =
+            if b & 0x10 != 0 { Some(b & 0x0f) } else { None };
+
+        let header_size = reader.stream_position()? - start;
+        if size < header_size {
+            return Err(Error::InvalidData("av1c box is too small"));
+        }
+        let mut config_obus = vec![0; (size - header_size) as usize];
+        reader.read_exact(&mut config_obus)?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Av1CBox {
+            version,
+            seq_profile,
+            seq_level_idx_0,
+            seq_tier_0,
+            high_bitdepth,
+            twelve_bit,
+            monochrome,
+            chroma_subsampling_x,
+            chroma_subsampling_y,
+            chroma_sample_position,
+            initial_presentation_delay_minus_one,
+            config_obus,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Av1CBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u8(0x80 | (self.version & 0x7f))?;
+        writer.write_u8((self.seq_profile << 5) | (self.seq_level_idx_0 & 0x1f))?;
+        writer.write_u8(
+            (u8::from(self.seq_tier_0) << 7)
+                | (u8::from(self.high_bitdepth) << 6)
+                | (u8::from(self.twelve_bit) << 5)
+                | (u8::from(self.monochrome) << 4)
+                | (u8::from(self.chroma_subsampling_x) << 3)
+                | (u8::from(self.chroma_subsampling_y) << 2)
+                | (self.chroma_sample_position & 0x03),
+        )?;
+        match self.initial_presentation_delay_minus_one {
+            Some(delay) => writer.write_u8(0x10 | (delay & 0x0f))?,
+            None => writer.write_u8(0)?,
+        }
+        writer.write_all(&self.config_obus)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_av1c() {
+        let src_box = Av1CBox {
+            version: Av1CBox::DEFAULT_VERSION,
+            seq_profile: 1,
+            seq_level_idx_0: 13,
+            seq_tier_0: true,
+            high_bitdepth: true,
+            twelve_bit: false,
+            monochrome: false,
+            chroma_subsampling_x: true,
+            chroma_subsampling_y: true,
+            chroma_sample_position: 1,
+            initial_presentation_delay_minus_one: Some(3),
+            config_obus: vec![
+                0x0a, 0x0b, 0x00, 0x00, 0x00, 0x42, 0xab, 0xbf, 0xc3, 0x71, 0xab,
+            ],
+        };
+        assert_eq!(src_box.bit_depth(), 10);
+
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Av1CBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Av1CBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -18,6 +18,9 @@ pub struct Avc1Box {
     pub frame_count: u16,
     pub depth: u16,
     pub avcc: AvcCBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl Default for Avc1Box {
@@ -31,6 +34,7 @@ impl Default for Avc1Box {
             frame_count: 1,
             depth: 0x0018,
             avcc: AvcCBox::default(),
+            unknown_boxes: Vec::new(),
         }
     }
 }
@@ -46,6 +50,7 @@ impl Avc1Box {
             frame_count: 1,
             depth: 0x0018,
             avcc: AvcCBox::new(&config.seq_param_set, &config.pic_param_set),
+            unknown_boxes: Vec::new(),
         }
     }
 
@@ -54,7 +59,11 @@ impl Avc1Box {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.avcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.avcc.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 }
 
@@ -101,12 +110,14 @@ impl<R: Read + Seek> ReadBox<&mut R> for Avc1Box {
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
+        let mut avcc = None;
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
+
+        let mut current = reader.stream_position()?;
         let end = start + size;
-        loop {
-            let current = reader.stream_position()?;
-            if current >= end {
-                return Err(Error::InvalidData("avcc not found"));
-            }
+        while current + HEADER_SIZE <= end {
+            // Get box header.
             let header = BoxHeader::read(reader)?;
             let BoxHeader { name, size: s } = header;
             if s > size {
@@ -114,25 +125,38 @@ impl<R: Read + Seek> ReadBox<&mut R> for Avc1Box {
                     "avc1 box contains a box with a larger size than it",
                 ));
             }
-            if name == BoxType::AvcCBox {
-                let avcc = AvcCBox::read_box(reader, s)?;
-
-                skip_bytes_to(reader, start + size)?;
-
-                return Ok(Avc1Box {
-                    data_reference_index,
-                    width,
-                    height,
-                    horizresolution,
-                    vertresolution,
-                    frame_count,
-                    depth,
-                    avcc,
-                });
-            } else {
-                skip_bytes_to(reader, current + s)?;
+            // Some writers pad sample entries with zeros.
+            if s < HEADER_SIZE {
+                break;
             }
+
+            match name {
+                BoxType::AvcCBox => {
+                    avcc = Some(AvcCBox::read_box(reader, s)?);
+                }
+                _ => {
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
+                }
+            }
+            children += 1;
+
+            current = reader.stream_position()?;
         }
+        let avcc = avcc.ok_or(Error::InvalidData("avcc not found"))?;
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Avc1Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            avcc,
+            unknown_boxes,
+        })
     }
 }
 
@@ -159,7 +183,9 @@ impl<W: Write> WriteBox<&mut W> for Avc1Box {
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
-        self.avcc.write_box(writer)?;
+        let mut children = ChildWriter::new(&self.unknown_boxes);
+        children.write(writer, &self.avcc)?;
+        children.finish(writer)?;
 
         Ok(size)
     }
@@ -338,6 +364,11 @@ mod tests {
                     bytes: vec![0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0],
                 }],
             },
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0x70617370),
+                vec![0, 0, 0, 1, 0, 0, 0, 1],
+            )
+            .with_position(1)],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -1,11 +1,15 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde::Serialize;
+use std::convert::TryFrom;
 use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
 
+/// An HEVC sample entry, written as `hev1` or `hvc1` depending on
+/// `box_type`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Hev1Box {
+    pub box_type: HevcBoxType,
     pub data_reference_index: u16,
     pub width: u16,
     pub height: u16,
@@ -18,11 +22,15 @@ pub struct Hev1Box {
     pub frame_count: u16,
     pub depth: u16,
     pub hvcc: HvcCBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl Default for Hev1Box {
     fn default() -> Self {
         Hev1Box {
+            box_type: HevcBoxType::Hev1,
             data_reference_index: 0,
             width: 0,
             height: 0,
@@ -31,13 +39,17 @@ impl Default for Hev1Box {
             frame_count: 1,
             depth: 0x0018,
             hvcc: HvcCBox::default(),
+            unknown_boxes: Vec::new(),
         }
     }
 }
 
 impl Hev1Box {
-    pub fn new(config: &HevcConfig) -> Self {
-        Hev1Box {
+    /// Fails if a parameter set is larger than 65535 bytes, which `hvcC`
+    /// can't store.
+    pub fn new(config: &HevcConfig) -> Result<Self> {
+        Ok(Hev1Box {
+            box_type: config.box_type,
             data_reference_index: 1,
             width: config.width,
             height: config.height,
@@ -45,16 +57,21 @@ impl Hev1Box {
             vertresolution: FixedPointU16::new(0x48),
             frame_count: 1,
             depth: 0x0018,
-            hvcc: HvcCBox::new(),
-        }
+            hvcc: HvcCBox::with_config(config)?,
+            unknown_boxes: Vec::new(),
+        })
     }
 
     pub fn get_type(&self) -> BoxType {
-        BoxType::Hev1Box
+        self.box_type.into()
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.hvcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.hvcc.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
     }
 }
 
@@ -84,6 +101,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for Hev1Box {
     fn read_box(reader: &mut R, size: u64) -> Result<Self> {
         let start = box_start(reader)?;
 
+        // hev1 and hvc1 only differ in the box type of the header.
+        reader.seek(SeekFrom::Start(start))?;
+        let box_type = match BoxHeader::read(reader)?.name {
+            BoxType::Hvc1Box => HevcBoxType::Hvc1,
+            _ => HevcBoxType::Hev1,
+        };
+
         reader.read_u32::<BigEndian>()?; // reserved
         reader.read_u16::<BigEndian>()?; // reserved
         let data_reference_index = reader.read_u16::<BigEndian>()?;
@@ -101,31 +125,54 @@ impl<R: Read + Seek> ReadBox<&mut R> for Hev1Box {
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "hev1 box contains a box with a larger size than it",
-            ));
-        }
-        if name == BoxType::HvcCBox {
-            let hvcc = HvcCBox::read_box(reader, s)?;
-
-            skip_bytes_to(reader, start + size)?;
-
-            Ok(Hev1Box {
-                data_reference_index,
-                width,
-                height,
-                horizresolution,
-                vertresolution,
-                frame_count,
-                depth,
-                hvcc,
-            })
-        } else {
-            Err(Error::InvalidData("hvcc not found"))
+        let mut hvcc = None;
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current + HEADER_SIZE <= end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "hev1 box contains a box with a larger size than it",
+                ));
+            }
+            // Some writers pad sample entries with zeros.
+            if s < HEADER_SIZE {
+                break;
+            }
+
+            match name {
+                BoxType::HvcCBox => {
+                    hvcc = Some(HvcCBox::read_box(reader, s)?);
+                }
+                _ => {
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
+                }
+            }
+            children += 1;
+
+            current = reader.stream_position()?;
         }
+        let hvcc = hvcc.ok_or(Error::InvalidData("hvcc not found"))?;
+
+        skip_bytes_to(reader, end)?;
+
+        Ok(Hev1Box {
+            box_type,
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            hvcc,
+            unknown_boxes,
+        })
     }
 }
 
@@ -152,7 +199,9 @@ impl<W: Write> WriteBox<&mut W> for Hev1Box {
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
-        self.hvcc.write_box(writer)?;
+        let mut children = ChildWriter::new(&self.unknown_boxes);
+        children.write(writer, &self.hvcc)?;
+        children.finish(writer)?;
 
         Ok(size)
     }
@@ -180,6 +229,10 @@ pub struct HvcCBox {
     pub arrays: Vec<HvcCArray>,
 }
 
+const HEVC_NAL_VPS: u8 = 32;
+const HEVC_NAL_SPS: u8 = 33;
+const HEVC_NAL_PPS: u8 = 34;
+
 impl HvcCBox {
     pub fn new() -> Self {
         Self {
@@ -187,6 +240,196 @@ impl HvcCBox {
             ..Default::default()
         }
     }
+
+    pub fn with_config(config: &HevcConfig) -> Result<Self> {
+        let mut hvcc = Self {
+            configuration_version: 1,
+            general_profile_space: config.general_profile_space,
+            general_tier_flag: config.general_tier_flag,
+            general_profile_idc: config.general_profile_idc,
+            general_profile_compatibility_flags: config.general_profile_compatibility_flags,
+            general_constraint_indicator_flag: config.general_constraint_indicator_flag,
+            general_level_idc: config.general_level_idc,
+            chroma_format_idc: 1, // 4:2:0
+            num_temporal_layers: 1,
+            length_size_minus_one: 3, // length_size = 4
+            ..Default::default()
+        };
+        let sps = sps_rbsp(&config.seq_param_set);
+        if hvcc.general_profile_idc == 0 {
+            hvcc.set_profile_tier_level_from_sps(&sps);
+        }
+        if let Some((chroma_format_idc, bit_depth_luma_minus8, bit_depth_chroma_minus8)) =
+            sps_format(&sps)
+        {
+            hvcc.chroma_format_idc = chroma_format_idc;
+            hvcc.bit_depth_luma_minus8 = bit_depth_luma_minus8;
+            hvcc.bit_depth_chroma_minus8 = bit_depth_chroma_minus8;
+        }
+
+        for (nal_unit_type, nal) in [
+            (HEVC_NAL_VPS, &config.vid_param_set),
+            (HEVC_NAL_SPS, &config.seq_param_set),
+            (HEVC_NAL_PPS, &config.pic_param_set),
+        ] {
+            if !nal.is_empty() {
+                hvcc.arrays.push(HvcCArray {
+                    completeness: true,
+                    nal_unit_type,
+                    nalus: vec![HvcCArrayNalu {
+                        size: u16::try_from(nal.len()).map_err(|_| {
+                            Error::InvalidData("hevc parameter set is larger than 65535 bytes")
+                        })?,
+                        data: nal.clone(),
+                    }],
+                });
+            }
+        }
+        Ok(hvcc)
+    }
+
+    /// Copies general_profile_tier_level() from the start of an SPS RBSP:
+    /// 2 bytes of NAL header, 1 byte of ids/flags and 12 bytes of
+    /// profile_tier_level.
+    fn set_profile_tier_level_from_sps(&mut self, rbsp: &[u8]) {
+        if rbsp.len() < 15 {
+            return;
+        }
+
+        let ptl = &rbsp[3..];
+        self.general_profile_space = ptl[0] >> 6;
+        self.general_tier_flag = (ptl[0] >> 5) & 0b1 > 0;
+        self.general_profile_idc = ptl[0] & 0b11111;
+        self.general_profile_compatibility_flags =
+            u32::from_be_bytes([ptl[1], ptl[2], ptl[3], ptl[4]]);
+        self.general_constraint_indicator_flag = ptl[5..11]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | b as u64);
+        self.general_level_idc = ptl[11];
+        self.num_temporal_layers = ((rbsp[2] >> 1) & 0b111) + 1;
+        self.temporal_id_nested = rbsp[2] & 0b1 > 0;
+    }
+
+    fn nalu(&self, nal_unit_type: u8) -> Option<&[u8]> {
+        self.arrays
+            .iter()
+            .find(|a| a.nal_unit_type == nal_unit_type)
+            .and_then(|a| a.nalus.first())
+            .map(|n| n.data.as_slice())
+    }
+
+    pub fn video_parameter_set(&self) -> Option<&[u8]> {
+        self.nalu(HEVC_NAL_VPS)
+    }
+
+    pub fn sequence_parameter_set(&self) -> Option<&[u8]> {
+        self.nalu(HEVC_NAL_SPS)
+    }
+
+    pub fn picture_parameter_set(&self) -> Option<&[u8]> {
+        self.nalu(HEVC_NAL_PPS)
+    }
+}
+
+/// Strips the emulation prevention bytes of an SPS NAL unit.
+fn sps_rbsp(sps: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(sps.len());
+    let mut zeros = 0;
+    for &b in sps {
+        if zeros >= 2 && b == 0x03 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        rbsp.push(b);
+    }
+    rbsp
+}
+
+/// Reads `(chroma_format_idc, bit_depth_luma_minus8, bit_depth_chroma_minus8)`
+/// from an SPS RBSP.
+fn sps_format(rbsp: &[u8]) -> Option<(u8, u8, u8)> {
+    let mut bits = RbspBits { data: rbsp, pos: 0 };
+    bits.skip(16 + 4)?; // NAL header, sps_video_parameter_set_id
+    let max_sub_layers_minus1 = bits.read(3)? as usize;
+    bits.skip(1 + 96)?; // sps_temporal_id_nesting_flag, general profile_tier_level
+
+    let mut sub_layers = Vec::with_capacity(max_sub_layers_minus1);
+    for _ in 0..max_sub_layers_minus1 {
+        sub_layers.push((bits.read(1)? > 0, bits.read(1)? > 0));
+    }
+    if max_sub_layers_minus1 > 0 {
+        bits.skip(2 * (8 - max_sub_layers_minus1))?; // reserved_zero_2bits
+    }
+    for (profile_present, level_present) in sub_layers {
+        if profile_present {
+            bits.skip(88)?;
+        }
+        if level_present {
+            bits.skip(8)?;
+        }
+    }
+
+    bits.read_ue()?; // sps_seq_parameter_set_id
+    let chroma_format_idc = bits.read_ue()?;
+    if chroma_format_idc == 3 {
+        bits.skip(1)?; // separate_colour_plane_flag
+    }
+    bits.read_ue()?; // pic_width_in_luma_samples
+    bits.read_ue()?; // pic_height_in_luma_samples
+    if bits.read(1)? > 0 {
+        // conf_win_{left,right,top,bottom}_offset
+        for _ in 0..4 {
+            bits.read_ue()?;
+        }
+    }
+    let bit_depth_luma_minus8 = bits.read_ue()?;
+    let bit_depth_chroma_minus8 = bits.read_ue()?;
+    if chroma_format_idc > 3 || bit_depth_luma_minus8 > 7 || bit_depth_chroma_minus8 > 7 {
+        return None;
+    }
+    Some((
+        chroma_format_idc as u8,
+        bit_depth_luma_minus8 as u8,
+        bit_depth_chroma_minus8 as u8,
+    ))
+}
+
+struct RbspBits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl RbspBits<'_> {
+    fn skip(&mut self, n: usize) -> Option<()> {
+        if self.pos + n > self.data.len() * 8 {
+            return None;
+        }
+        self.pos += n;
+        Some(())
+    }
+
+    fn read(&mut self, n: usize) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..n {
+            let byte = self.data.get(self.pos / 8)?;
+            value = (value << 1) | ((byte >> (7 - self.pos % 8)) & 1) as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    /// Reads an unsigned Exp-Golomb code, ue(v).
+    fn read_ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while self.read(1)? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+        Some(((1u64 << zeros) - 1 + self.read(zeros)? as u64) as u32)
+    }
 }
 
 impl Mp4Box for HvcCBox {
@@ -248,8 +491,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
     fn read_box(reader: &mut R, _size: u64) -> Result<Self> {
         let configuration_version = reader.read_u8()?;
         let params = reader.read_u8()?;
-        let general_profile_space = params & 0b11000000 >> 6;
-        let general_tier_flag = (params & 0b00100000 >> 5) > 0;
+        let general_profile_space = (params & 0b11000000) >> 6;
+        let general_tier_flag = (params & 0b00100000) >> 5 > 0;
         let general_profile_idc = params & 0b00011111;
 
         let general_profile_compatibility_flags = reader.read_u32::<BigEndian>()?;
@@ -263,9 +506,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
         let avg_frame_rate = reader.read_u16::<BigEndian>()?;
 
         let params = reader.read_u8()?;
-        let constant_frame_rate = params & 0b11000000 >> 6;
-        let num_temporal_layers = params & 0b00111000 >> 3;
-        let temporal_id_nested = (params & 0b00000100 >> 2) > 0;
+        let constant_frame_rate = (params & 0b11000000) >> 6;
+        let num_temporal_layers = (params & 0b00111000) >> 3;
+        let temporal_id_nested = (params & 0b00000100) >> 2 > 0;
         let length_size_minus_one = params & 0b000011;
 
         let num_of_arrays = reader.read_u8()?;
@@ -330,11 +573,12 @@ impl<W: Write> WriteBox<&mut W> for HvcCBox {
         writer.write_u48::<BigEndian>(self.general_constraint_indicator_flag)?;
         writer.write_u8(self.general_level_idc)?;
 
-        writer.write_u16::<BigEndian>(self.min_spatial_segmentation_idc & 0x0FFF)?;
-        writer.write_u8(self.parallelism_type & 0b11)?;
-        writer.write_u8(self.chroma_format_idc & 0b11)?;
-        writer.write_u8(self.bit_depth_luma_minus8 & 0b111)?;
-        writer.write_u8(self.bit_depth_chroma_minus8 & 0b111)?;
+        // Reserved bits are set to 1.
+        writer.write_u16::<BigEndian>(0xF000 | (self.min_spatial_segmentation_idc & 0x0FFF))?;
+        writer.write_u8(0b11111100 | (self.parallelism_type & 0b11))?;
+        writer.write_u8(0b11111100 | (self.chroma_format_idc & 0b11))?;
+        writer.write_u8(0b11111000 | (self.bit_depth_luma_minus8 & 0b111))?;
+        writer.write_u8(0b11111000 | (self.bit_depth_chroma_minus8 & 0b111))?;
         writer.write_u16::<BigEndian>(self.avg_frame_rate)?;
 
         let constant_frame_rate = (self.constant_frame_rate & 0b11) << 6;
@@ -368,6 +612,7 @@ mod tests {
     #[test]
     fn test_hev1() {
         let src_box = Hev1Box {
+            box_type: HevcBoxType::Hev1,
             data_reference_index: 1,
             width: 320,
             height: 240,
@@ -379,7 +624,75 @@ mod tests {
                 configuration_version: 1,
                 ..Default::default()
             },
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0x70617370),
+                vec![0, 0, 0, 1, 0, 0, 0, 1],
+            )
+            .with_position(1)],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Hev1Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Hev1Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_hvc1() {
+        let src_box = Hev1Box {
+            box_type: HevcBoxType::Hvc1,
+            width: 320,
+            height: 240,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Hvc1Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Hev1Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert!(dst_box.to_json().unwrap().contains(r#""box_type":"hvc1""#));
+    }
+
+    #[test]
+    fn test_hev1_with_config() {
+        // SPS of a 1920x1080 Main profile, level 4.0 stream.
+        let sps = vec![
+            0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x03, 0x00, 0x78, 0xa0, 0x03, 0xc0, 0x80, 0x10, 0xe5, 0x96,
+        ];
+        let config = HevcConfig {
+            width: 1920,
+            height: 1080,
+            vid_param_set: vec![0x40, 0x01, 0x0c, 0x01],
+            seq_param_set: sps.clone(),
+            pic_param_set: vec![0x44, 0x01, 0xc1, 0x72],
+            ..Default::default()
         };
+        let src_box = Hev1Box::new(&config).unwrap();
+        assert_eq!(src_box.hvcc.general_profile_idc, 1);
+        assert_eq!(src_box.hvcc.general_profile_compatibility_flags, 0x60000000);
+        assert_eq!(
+            src_box.hvcc.general_constraint_indicator_flag,
+            0x900000000000
+        );
+        assert_eq!(src_box.hvcc.general_level_idc, 120);
+        assert_eq!(src_box.hvcc.chroma_format_idc, 1);
+        assert_eq!(src_box.hvcc.bit_depth_luma_minus8, 0);
+        assert_eq!(src_box.hvcc.bit_depth_chroma_minus8, 0);
+        assert_eq!(src_box.hvcc.sequence_parameter_set(), Some(sps.as_slice()));
+
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
         assert_eq!(buf.len(), src_box.box_size() as usize);
@@ -392,4 +705,33 @@ mod tests {
         let dst_box = Hev1Box::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_hev1_with_config_10bit_422() {
+        // SPS of a 1920x1080 4:2:2 10 bit format range extensions stream.
+        let sps = vec![
+            0x42, 0x01, 0x01, 0x04, 0x08, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x03, 0x00, 0x78, 0xb0, 0x03, 0xc0, 0x80, 0x10, 0xe4, 0xdc,
+        ];
+        let config = HevcConfig {
+            width: 1920,
+            height: 1080,
+            seq_param_set: sps,
+            ..Default::default()
+        };
+        let hvcc = HvcCBox::with_config(&config).unwrap();
+        assert_eq!(hvcc.general_profile_idc, 4);
+        assert_eq!(hvcc.chroma_format_idc, 2);
+        assert_eq!(hvcc.bit_depth_luma_minus8, 2);
+        assert_eq!(hvcc.bit_depth_chroma_minus8, 2);
+    }
+
+    #[test]
+    fn test_hev1_parameter_set_too_large() {
+        let config = HevcConfig {
+            pic_param_set: vec![0x44; 0x10000],
+            ..Default::default()
+        };
+        assert!(matches!(Hev1Box::new(&config), Err(Error::InvalidData(_))));
+    }
 }
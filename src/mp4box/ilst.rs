@@ -11,6 +11,9 @@ use crate::mp4box::*;
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct IlstBox {
     pub items: HashMap<MetadataKey, IlstItemBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl IlstBox {
@@ -23,6 +26,9 @@ impl IlstBox {
         for item in self.items.values() {
             size += item.get_size();
         }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -51,6 +57,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstBox {
         let start = box_start(reader)?;
 
         let mut items = HashMap::new();
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -78,17 +86,20 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstBox {
                     items.insert(MetadataKey::Summary, IlstItemBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
                 }
             }
+            children += 1;
 
             current = reader.stream_position()?;
         }
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(IlstBox { items })
+        Ok(IlstBox {
+            items,
+            unknown_boxes,
+        })
     }
 }
 
@@ -97,7 +108,9 @@ impl<W: Write> WriteBox<&mut W> for IlstBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
+        let mut children = ChildWriter::new(&self.unknown_boxes);
         for (key, value) in &self.items {
+            children.next(writer)?;
             let name = match key {
                 MetadataKey::Title => BoxType::NameBox,
                 MetadataKey::Year => BoxType::DayBox,
@@ -107,6 +120,7 @@ impl<W: Write> WriteBox<&mut W> for IlstBox {
             BoxHeader::new(name, value.get_size()).write(writer)?;
             value.data.write_box(writer)?;
         }
+        children.finish(writer)?;
         Ok(size)
     }
 }
@@ -166,7 +180,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstItemBox {
 }
 
 impl<'a> Metadata<'a> for IlstBox {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         self.items.get(&MetadataKey::Title).map(item_to_str)
     }
 
@@ -178,7 +192,7 @@ impl<'a> Metadata<'a> for IlstBox {
         self.items.get(&MetadataKey::Poster).map(item_to_bytes)
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn summary(&self) -> Option<Cow<'_, str>> {
         self.items.get(&MetadataKey::Summary).map(item_to_str)
     }
 }
@@ -187,7 +201,7 @@ fn item_to_bytes(item: &IlstItemBox) -> &[u8] {
     &item.data.data
 }
 
-fn item_to_str(item: &IlstItemBox) -> Cow<str> {
+fn item_to_str(item: &IlstItemBox) -> Cow<'_, str> {
     String::from_utf8_lossy(&item.data.data)
 }
 
@@ -221,6 +235,12 @@ mod tests {
                 (MetadataKey::Summary, IlstItemBox::default()),
             ]
             .into(),
+            // ©ART, which has no MetadataKey and is kept as is.
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0xa9415254),
+                b"test_artist".to_vec(),
+            )
+            .with_position(4)],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -276,13 +276,14 @@ mod tests {
         let meta_box = MetaBox::read_box(&mut reader, header.size).unwrap();
 
         // this contains \xa9too box in the ilst
-        // it designates the tool that created the file, but is not yet supported by this crate
-        assert_eq!(
-            meta_box,
-            MetaBox::Mdir {
-                ilst: Some(IlstBox::default())
-            }
-        );
+        // it designates the tool that created the file, and is kept as an unknown box
+        let MetaBox::Mdir { ilst: Some(ilst) } = meta_box else {
+            panic!("expected an mdir meta box with ilst");
+        };
+        assert!(ilst.items.is_empty());
+        assert_eq!(ilst.unknown_boxes.len(), 1);
+        assert_eq!(ilst.unknown_boxes[0].box_type, BoxType::from(0xa9746f6f));
+        assert_eq!(ilst.unknown_boxes[0].payload.len(), 0x41);
     }
 
     #[test]
@@ -0,0 +1,140 @@
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+use crate::mp4box::{mfro::MfroBox, tfra::TfraBox};
+
+/// Movie fragment random access box, usually the last box of a fragmented
+/// file.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct MfraBox {
+    pub tfras: Vec<TfraBox>,
+    pub mfro: MfroBox,
+}
+
+impl MfraBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::MfraBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + self.tfras.iter().map(|x| x.box_size()).sum::<u64>() + self.mfro.box_size()
+    }
+}
+
+impl Mp4Box for MfraBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("tfras={}", self.tfras.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for MfraBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut tfras = Vec::new();
+        let mut mfro = None;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "mfra box contains a box with a larger size than it",
+                ));
+            }
+
+            match name {
+                BoxType::TfraBox => {
+                    tfras.push(TfraBox::read_box(reader, s)?);
+                }
+                BoxType::MfroBox => {
+                    mfro = Some(MfroBox::read_box(reader, s)?);
+                }
+                _ => {
+                    // XXX warn!()
+                    skip_box(reader, s)?;
+                }
+            }
+            current = reader.stream_position()?;
+        }
+
+        if mfro.is_none() {
+            return Err(Error::BoxNotFound(BoxType::MfroBox));
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(MfraBox {
+            tfras,
+            mfro: mfro.unwrap(),
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for MfraBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        for tfra in self.tfras.iter() {
+            tfra.write_box(writer)?;
+        }
+        self.mfro.write_box(writer)?;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::tfra::TfraEntry;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_mfra() {
+        let mut src_box = MfraBox {
+            tfras: vec![TfraBox {
+                track_id: 1,
+                entries: vec![TfraEntry {
+                    time: 1000,
+                    moof_offset: 2048,
+                    traf_number: 1,
+                    trun_number: 1,
+                    sample_number: 1,
+                }],
+                ..TfraBox::default()
+            }],
+            mfro: MfroBox::default(),
+        };
+        src_box.mfro.parent_size = src_box.box_size() as u32;
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::MfraBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = MfraBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -14,6 +14,9 @@ pub struct MinfBox {
 
     pub dinf: DinfBox,
     pub stbl: StblBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl MinfBox {
@@ -31,6 +34,9 @@ impl MinfBox {
         }
         size += self.dinf.box_size();
         size += self.stbl.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -62,6 +68,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
         let mut smhd = None;
         let mut dinf = None;
         let mut stbl = None;
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -89,10 +97,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
                     stbl = Some(StblBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
                 }
             }
+            children += 1;
 
             current = reader.stream_position()?;
         }
@@ -111,6 +119,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
             smhd,
             dinf: dinf.unwrap(),
             stbl: stbl.unwrap(),
+            unknown_boxes,
         })
     }
 }
@@ -120,15 +129,16 @@ impl<W: Write> WriteBox<&mut W> for MinfBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
+        let mut children = ChildWriter::new(&self.unknown_boxes);
         if let Some(ref vmhd) = self.vmhd {
-            vmhd.write_box(writer)?;
+            children.write(writer, vmhd)?;
         }
         if let Some(ref smhd) = self.smhd {
-            smhd.write_box(writer)?;
+            children.write(writer, smhd)?;
         }
-        self.dinf.write_box(writer)?;
-        self.stbl.write_box(writer)?;
-
+        children.write(writer, &self.dinf)?;
+        children.write(writer, &self.stbl)?;
+        children.finish(writer)?;
         Ok(size)
     }
 }
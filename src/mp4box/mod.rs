@@ -13,6 +13,7 @@
 //! ftyp
 //! moov
 //!     mvhd
+//!     pssh
 //!     udta
 //!         meta
 //!             ilst
@@ -26,9 +27,18 @@
 //!                 stbl
 //!                     stsd
 //!                         avc1
+//!                         av01
 //!                         hev1
+//!                         hvc1
 //!                         mp4a
+//!                         Opus
 //!                         tx3g
+//!                         encv / enca
+//!                             sinf
+//!                                 frma
+//!                                 schm
+//!                                 schi
+//!                                     tenc
 //!                     stts
 //!                     stsc
 //!                     stsz
@@ -36,6 +46,8 @@
 //!                     stco
 //!                     co64
 //!                     ctts
+//!                     saiz
+//!                     saio
 //!                 dinf
 //!                     dref
 //!                 smhd
@@ -46,13 +58,21 @@
 //!         mehd
 //!         trex
 //! emsg
+//! sidx
 //! moof
 //!     mfhd
+//!     pssh
 //!     traf
 //!         tfhd
 //!         tfdt
 //!         trun
+//!         senc
+//!         saiz
+//!         saio
 //! mdat
+//! mfra
+//!     tfra
+//!     mfro
 //! free
 //!
 
@@ -62,6 +82,8 @@ use std::io::{Read, Seek, SeekFrom, Write};
 
 use crate::*;
 
+pub(crate) mod av01;
+pub(crate) mod av1c;
 pub(crate) mod avc1;
 pub(crate) mod co64;
 pub(crate) mod ctts;
@@ -70,6 +92,7 @@ pub(crate) mod dinf;
 pub(crate) mod edts;
 pub(crate) mod elst;
 pub(crate) mod emsg;
+pub(crate) mod frma;
 pub(crate) mod ftyp;
 pub(crate) mod hdlr;
 pub(crate) mod hev1;
@@ -79,12 +102,23 @@ pub(crate) mod mdia;
 pub(crate) mod mehd;
 pub(crate) mod meta;
 pub(crate) mod mfhd;
+pub(crate) mod mfra;
+pub(crate) mod mfro;
 pub(crate) mod minf;
 pub(crate) mod moof;
 pub(crate) mod moov;
 pub(crate) mod mp4a;
 pub(crate) mod mvex;
 pub(crate) mod mvhd;
+pub(crate) mod opus;
+pub(crate) mod pssh;
+pub(crate) mod saio;
+pub(crate) mod saiz;
+pub(crate) mod schi;
+pub(crate) mod schm;
+pub(crate) mod senc;
+pub(crate) mod sidx;
+pub(crate) mod sinf;
 pub(crate) mod smhd;
 pub(crate) mod stbl;
 pub(crate) mod stco;
@@ -93,8 +127,10 @@ pub(crate) mod stsd;
 pub(crate) mod stss;
 pub(crate) mod stsz;
 pub(crate) mod stts;
+pub(crate) mod tenc;
 pub(crate) mod tfdt;
 pub(crate) mod tfhd;
+pub(crate) mod tfra;
 pub(crate) mod tkhd;
 pub(crate) mod traf;
 pub(crate) mod trak;
@@ -102,43 +138,60 @@ pub(crate) mod trex;
 pub(crate) mod trun;
 pub(crate) mod tx3g;
 pub(crate) mod udta;
+pub(crate) mod unknown;
 pub(crate) mod vmhd;
 pub(crate) mod vp09;
 pub(crate) mod vpcc;
 
+pub use av01::Av01Box;
+pub use av1c::Av1CBox;
 pub use avc1::Avc1Box;
 pub use co64::Co64Box;
 pub use ctts::CttsBox;
 pub use data::DataBox;
 pub use dinf::DinfBox;
 pub use edts::EdtsBox;
-pub use elst::ElstBox;
+pub use elst::{ElstBox, ElstEntry};
 pub use emsg::EmsgBox;
+pub use frma::FrmaBox;
 pub use ftyp::FtypBox;
 pub use hdlr::HdlrBox;
 pub use hev1::Hev1Box;
-pub use ilst::IlstBox;
+pub use ilst::{IlstBox, IlstItemBox};
 pub use mdhd::MdhdBox;
 pub use mdia::MdiaBox;
 pub use mehd::MehdBox;
 pub use meta::MetaBox;
 pub use mfhd::MfhdBox;
+pub use mfra::MfraBox;
+pub use mfro::MfroBox;
 pub use minf::MinfBox;
 pub use moof::MoofBox;
 pub use moov::MoovBox;
 pub use mp4a::Mp4aBox;
 pub use mvex::MvexBox;
 pub use mvhd::MvhdBox;
+pub use opus::{DopsBox, OpusBox, OpusChannelMappingTable};
+pub use pssh::PsshBox;
+pub use saio::SaioBox;
+pub use saiz::SaizBox;
+pub use schi::SchiBox;
+pub use schm::SchmBox;
+pub use senc::SencBox;
+pub use sidx::{SidxBox, SidxReference};
+pub use sinf::SinfBox;
 pub use smhd::SmhdBox;
 pub use stbl::StblBox;
 pub use stco::StcoBox;
 pub use stsc::StscBox;
-pub use stsd::StsdBox;
+pub use stsd::{EncryptedSampleEntry, SampleEntry, StsdBox};
 pub use stss::StssBox;
 pub use stsz::StszBox;
 pub use stts::SttsBox;
+pub use tenc::TencBox;
 pub use tfdt::TfdtBox;
 pub use tfhd::TfhdBox;
+pub use tfra::{TfraBox, TfraEntry};
 pub use tkhd::TkhdBox;
 pub use traf::TrafBox;
 pub use trak::TrakBox;
@@ -146,6 +199,8 @@ pub use trex::TrexBox;
 pub use trun::TrunBox;
 pub use tx3g::Tx3gBox;
 pub use udta::UdtaBox;
+pub(crate) use unknown::ChildWriter;
+pub use unknown::UnknownBox;
 pub use vmhd::VmhdBox;
 pub use vp09::Vp09Box;
 pub use vpcc::VpccBox;
@@ -193,6 +248,10 @@ boxtype! {
     MehdBox => 0x6d656864,
     TrexBox => 0x74726578,
     EmsgBox => 0x656d7367,
+    SidxBox => 0x73696478,
+    MfraBox => 0x6d667261,
+    TfraBox => 0x74667261,
+    MfroBox => 0x6d66726f,
     MoofBox => 0x6d6f6f66,
     TkhdBox => 0x746b6864,
     TfhdBox => 0x74666864,
@@ -225,12 +284,17 @@ boxtype! {
     Avc1Box => 0x61766331,
     AvcCBox => 0x61766343,
     Hev1Box => 0x68657631,
+    Hvc1Box => 0x68766331,
     HvcCBox => 0x68766343,
     Mp4aBox => 0x6d703461,
     EsdsBox => 0x65736473,
+    OpusBox => 0x4f707573,
+    DopsBox => 0x644f7073,
     Tx3gBox => 0x74783367,
     VpccBox => 0x76706343,
     Vp09Box => 0x76703039,
+    Av01Box => 0x61763031,
+    Av1CBox => 0x61763143,
     DataBox => 0x64617461,
     IlstBox => 0x696c7374,
     NameBox => 0xa96e616d,
@@ -238,7 +302,18 @@ boxtype! {
     CovrBox => 0x636f7672,
     DescBox => 0x64657363,
     WideBox => 0x77696465,
-    WaveBox => 0x77617665
+    WaveBox => 0x77617665,
+    EncvBox => 0x656e6376,
+    EncaBox => 0x656e6361,
+    SinfBox => 0x73696e66,
+    FrmaBox => 0x66726d61,
+    SchmBox => 0x7363686d,
+    SchiBox => 0x73636869,
+    TencBox => 0x74656e63,
+    PsshBox => 0x70737368,
+    SencBox => 0x73656e63,
+    SaizBox => 0x7361697a,
+    SaioBox => 0x7361696f
 }
 
 pub trait Mp4Box: Sized {
@@ -306,6 +381,22 @@ impl BoxHeader {
         }
     }
 
+    /// Asynchronous version of [BoxHeader::read].
+    #[cfg(feature = "async")]
+    pub async fn read_async<R: tokio::io::AsyncRead + Unpin>(reader: &mut R) -> Result<Self> {
+        use tokio::io::AsyncReadExt;
+
+        let mut buf = [0u8; 16];
+        reader.read_exact(&mut buf[..8]).await?;
+        let len = if buf[0..4] == [0, 0, 0, 1] {
+            reader.read_exact(&mut buf[8..]).await?;
+            16
+        } else {
+            8
+        };
+        BoxHeader::read(&mut &buf[..len])
+    }
+
     pub fn write<W: Write>(&self, writer: &mut W) -> Result<u64> {
         if self.size > u32::MAX as u64 {
             writer.write_u32::<BigEndian>(1)?;
@@ -8,6 +8,9 @@ use crate::mp4box::{mfhd::MfhdBox, traf::TrafBox};
 pub struct MoofBox {
     pub mfhd: MfhdBox,
 
+    #[serde(rename = "pssh", skip_serializing_if = "Vec::is_empty")]
+    pub psshs: Vec<PsshBox>,
+
     #[serde(rename = "traf")]
     pub trafs: Vec<TrafBox>,
 }
@@ -19,6 +22,9 @@ impl MoofBox {
 
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE + self.mfhd.box_size();
+        for pssh in self.psshs.iter() {
+            size += pssh.box_size();
+        }
         for traf in self.trafs.iter() {
             size += traf.box_size();
         }
@@ -50,6 +56,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
         let start = box_start(reader)?;
 
         let mut mfhd = None;
+        let mut psshs = Vec::new();
         let mut trafs = Vec::new();
 
         let mut current = reader.stream_position()?;
@@ -68,6 +75,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
                 BoxType::MfhdBox => {
                     mfhd = Some(MfhdBox::read_box(reader, s)?);
                 }
+                BoxType::PsshBox => {
+                    psshs.push(PsshBox::read_box(reader, s)?);
+                }
                 BoxType::TrafBox => {
                     let traf = TrafBox::read_box(reader, s)?;
                     trafs.push(traf);
@@ -88,6 +98,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
 
         Ok(MoofBox {
             mfhd: mfhd.unwrap(),
+            psshs,
             trafs,
         })
     }
@@ -99,6 +110,9 @@ impl<W: Write> WriteBox<&mut W> for MoofBox {
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
         self.mfhd.write_box(writer)?;
+        for pssh in self.psshs.iter() {
+            pssh.write_box(writer)?;
+        }
         for traf in self.trafs.iter() {
             traf.write_box(writer)?;
         }
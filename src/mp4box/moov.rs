@@ -18,8 +18,14 @@ pub struct MoovBox {
     #[serde(rename = "trak")]
     pub traks: Vec<TrakBox>,
 
+    #[serde(rename = "pssh", skip_serializing_if = "Vec::is_empty")]
+    pub psshs: Vec<PsshBox>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub udta: Option<UdtaBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl MoovBox {
@@ -32,12 +38,21 @@ impl MoovBox {
         for trak in self.traks.iter() {
             size += trak.box_size();
         }
+        if let Some(mvex) = &self.mvex {
+            size += mvex.box_size();
+        }
         if let Some(meta) = &self.meta {
             size += meta.box_size();
         }
+        for pssh in self.psshs.iter() {
+            size += pssh.box_size();
+        }
         if let Some(udta) = &self.udta {
             size += udta.box_size();
         }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -70,6 +85,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
         let mut udta = None;
         let mut mvex = None;
         let mut traks = Vec::new();
+        let mut psshs = Vec::new();
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -97,14 +115,17 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
                     let trak = TrakBox::read_box(reader, s)?;
                     traks.push(trak);
                 }
+                BoxType::PsshBox => {
+                    psshs.push(PsshBox::read_box(reader, s)?);
+                }
                 BoxType::UdtaBox => {
                     udta = Some(UdtaBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
                 }
             }
+            children += 1;
 
             current = reader.stream_position()?;
         }
@@ -121,6 +142,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
             udta,
             mvex,
             traks,
+            psshs,
+            unknown_boxes,
         })
     }
 }
@@ -130,16 +153,24 @@ impl<W: Write> WriteBox<&mut W> for MoovBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.mvhd.write_box(writer)?;
+        let mut children = ChildWriter::new(&self.unknown_boxes);
+        children.write(writer, &self.mvhd)?;
         for trak in self.traks.iter() {
-            trak.write_box(writer)?;
+            children.write(writer, trak)?;
+        }
+        if let Some(mvex) = &self.mvex {
+            children.write(writer, mvex)?;
         }
         if let Some(meta) = &self.meta {
-            meta.write_box(writer)?;
+            children.write(writer, meta)?;
+        }
+        for pssh in self.psshs.iter() {
+            children.write(writer, pssh)?;
         }
         if let Some(udta) = &self.udta {
-            udta.write_box(writer)?;
+            children.write(writer, udta)?;
         }
+        children.finish(writer)?;
         Ok(0)
     }
 }
@@ -154,10 +185,27 @@ mod tests {
     fn test_moov() {
         let src_box = MoovBox {
             mvhd: MvhdBox::default(),
-            mvex: None, // XXX mvex is not written currently
+            mvex: Some(MvexBox {
+                mehd: None,
+                trexs: vec![TrexBox {
+                    track_id: 1,
+                    default_sample_description_index: 1,
+                    ..TrexBox::default()
+                }],
+            }),
             traks: vec![],
+            psshs: vec![PsshBox {
+                system_id: [0xed; 16],
+                data: vec![1, 2, 3],
+                ..PsshBox::default()
+            }],
             meta: Some(MetaBox::default()),
             udta: Some(UdtaBox::default()),
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0x75756964), // uuid
+                vec![0xab; 20],
+            )
+            .with_position(5)],
         };
 
         let mut buf = Vec::new();
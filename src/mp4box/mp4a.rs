@@ -1,6 +1,6 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde::Serialize;
-use std::io::{Read, Seek, Write};
+use std::io::{Cursor, Read, Seek, Write};
 
 use crate::mp4box::*;
 
@@ -13,6 +13,9 @@ pub struct Mp4aBox {
     #[serde(with = "value_u32")]
     pub samplerate: FixedPointU16,
     pub esds: Option<EsdsBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl Default for Mp4aBox {
@@ -23,6 +26,7 @@ impl Default for Mp4aBox {
             samplesize: 16,
             samplerate: FixedPointU16::new(48000),
             esds: Some(EsdsBox::default()),
+            unknown_boxes: Vec::new(),
         }
     }
 }
@@ -35,6 +39,7 @@ impl Mp4aBox {
             samplesize: 16,
             samplerate: FixedPointU16::new(config.freq_index.freq() as u16),
             esds: Some(EsdsBox::new(config)),
+            unknown_boxes: Vec::new(),
         }
     }
 
@@ -47,6 +52,9 @@ impl Mp4aBox {
         if let Some(ref esds) = self.esds {
             size += esds.box_size();
         }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -98,12 +106,12 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
 
         // Find esds in mp4a or wave
         let mut esds = None;
+        let mut esds_in_wave = false;
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
         let end = start + size;
-        loop {
-            let current = reader.stream_position()?;
-            if current >= end {
-                break;
-            }
+        let mut current = reader.stream_position()?;
+        while current + HEADER_SIZE <= end {
             let header = BoxHeader::read(reader)?;
             let BoxHeader { name, size: s } = header;
             if s > size {
@@ -111,16 +119,26 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
                     "mp4a box contains a box with a larger size than it",
                 ));
             }
-            if name == BoxType::EsdsBox {
-                esds = Some(EsdsBox::read_box(reader, s)?);
+            // Some writers pad sample entries with zeros.
+            if s < HEADER_SIZE {
                 break;
-            } else if name == BoxType::WaveBox {
-                // Typically contains frma, mp4a, esds, and a terminator atom
+            }
+            if name == BoxType::EsdsBox && (esds.is_none() || esds_in_wave) {
+                esds = Some(EsdsBox::read_box(reader, s)?);
+                esds_in_wave = false;
             } else {
-                // Skip boxes
-                let skip_to = current + s;
-                skip_bytes_to(reader, skip_to)?;
+                let unknown = UnknownBox::read(reader, name, s)?.with_position(children);
+                if name == BoxType::WaveBox && esds.is_none() {
+                    // Typically contains frma, mp4a, esds, and a terminator
+                    // atom. It is written back as is, so an esds directly in
+                    // mp4a takes precedence over the one in wave.
+                    esds = find_wave_esds(&unknown.payload)?;
+                    esds_in_wave = esds.is_some();
+                }
+                unknown_boxes.push(unknown);
             }
+            children += 1;
+            current = reader.stream_position()?;
         }
 
         skip_bytes_to(reader, end)?;
@@ -131,6 +149,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
             samplesize,
             samplerate,
             esds,
+            unknown_boxes,
         })
     }
 }
@@ -150,14 +169,36 @@ impl<W: Write> WriteBox<&mut W> for Mp4aBox {
         writer.write_u32::<BigEndian>(0)?; // reserved
         writer.write_u32::<BigEndian>(self.samplerate.raw_value())?;
 
+        let mut children = ChildWriter::new(&self.unknown_boxes);
         if let Some(ref esds) = self.esds {
-            esds.write_box(writer)?;
+            children.write(writer, esds)?;
         }
+        children.finish(writer)?;
 
         Ok(size)
     }
 }
 
+fn find_wave_esds(payload: &[u8]) -> Result<Option<EsdsBox>> {
+    let mut reader = Cursor::new(payload);
+    let end = payload.len() as u64;
+    let mut current = 0;
+    while current + HEADER_SIZE <= end {
+        let header = BoxHeader::read(&mut reader)?;
+        let BoxHeader { name, size: s } = header;
+        // Stop at padding or a truncated box.
+        if s < HEADER_SIZE || current + s > end {
+            break;
+        }
+        if name == BoxType::EsdsBox {
+            return Ok(Some(EsdsBox::read_box(&mut reader, s)?));
+        }
+        current += s;
+        reader.set_position(current);
+    }
+    Ok(None)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct EsdsBox {
     pub version: u8,
@@ -285,7 +326,7 @@ fn size_of_length(size: u32) -> u32 {
 fn write_desc<W: Write>(writer: &mut W, tag: u8, size: u32) -> Result<u64> {
     writer.write_u8(tag)?;
 
-    if size as u64 > std::u32::MAX as u64 {
+    if size as u64 > u32::MAX as u64 {
         return Err(Error::InvalidData("invalid descriptor length range"));
     }
 
@@ -645,6 +686,9 @@ mod tests {
                     sl_config: SLConfigDescriptor::default(),
                 },
             }),
+            unknown_boxes: vec![
+                UnknownBox::new(BoxType::from(0x62747274), vec![0; 12]).with_position(1)
+            ],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -667,6 +711,7 @@ mod tests {
             samplesize: 16,
             samplerate: FixedPointU16::new(48000),
             esds: None,
+            unknown_boxes: Vec::new(),
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -680,4 +725,44 @@ mod tests {
         let dst_box = Mp4aBox::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_mp4a_keeps_wave() {
+        let esds = EsdsBox::new(&AacConfig::default());
+        let mut wave = Vec::new();
+        FrmaBox {
+            data_format: str::parse("mp4a").unwrap(),
+        }
+        .write_box(&mut wave)
+        .unwrap();
+        esds.write_box(&mut wave).unwrap();
+        wave.extend_from_slice(&[0, 0, 0, 8, 0, 0, 0, 0]); // terminator
+        let wave = UnknownBox::new(BoxType::WaveBox, wave).with_position(0);
+
+        let src_box = Mp4aBox {
+            esds: None,
+            unknown_boxes: vec![wave.clone()],
+            ..Mp4aBox::new(&AacConfig::default())
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = Mp4aBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dst_box.esds, Some(esds));
+        assert_eq!(dst_box.unknown_boxes, vec![wave]);
+
+        // An esds directly in mp4a is preferred over the one in wave.
+        let mut edited = dst_box.clone();
+        edited.esds.as_mut().unwrap().es_desc.es_id = 7;
+        let mut buf = Vec::new();
+        edited.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), edited.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = Mp4aBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dst_box, edited);
+    }
 }
@@ -7,16 +7,24 @@ use crate::mp4box::{mehd::MehdBox, trex::TrexBox};
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct MvexBox {
     pub mehd: Option<MehdBox>,
-    pub trex: TrexBox,
+    /// One trex per track, holding the defaults of its fragments.
+    pub trexs: Vec<TrexBox>,
 }
 
 impl MvexBox {
     pub fn get_type(&self) -> BoxType {
-        BoxType::MdiaBox
+        BoxType::MvexBox
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.mehd.as_ref().map(|x| x.box_size()).unwrap_or(0) + self.trex.box_size()
+        HEADER_SIZE
+            + self.mehd.as_ref().map(|x| x.box_size()).unwrap_or(0)
+            + self.trexs.iter().map(|x| x.box_size()).sum::<u64>()
+    }
+
+    /// Returns the trex of a track.
+    pub fn trex(&self, track_id: u32) -> Option<&TrexBox> {
+        self.trexs.iter().find(|trex| trex.track_id == track_id)
     }
 }
 
@@ -44,7 +52,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
         let start = box_start(reader)?;
 
         let mut mehd = None;
-        let mut trex = None;
+        let mut trexs = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -63,7 +71,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
                     mehd = Some(MehdBox::read_box(reader, s)?);
                 }
                 BoxType::TrexBox => {
-                    trex = Some(TrexBox::read_box(reader, s)?);
+                    trexs.push(TrexBox::read_box(reader, s)?);
                 }
                 _ => {
                     // XXX warn!()
@@ -74,16 +82,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
             current = reader.stream_position()?;
         }
 
-        if trex.is_none() {
+        if trexs.is_empty() {
             return Err(Error::BoxNotFound(BoxType::TrexBox));
         }
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(MvexBox {
-            mehd,
-            trex: trex.unwrap(),
-        })
+        Ok(MvexBox { mehd, trexs })
     }
 }
 
@@ -95,8 +100,58 @@ impl<W: Write> WriteBox<&mut W> for MvexBox {
         if let Some(mehd) = &self.mehd {
             mehd.write_box(writer)?;
         }
-        self.trex.write_box(writer)?;
+        for trex in self.trexs.iter() {
+            trex.write_box(writer)?;
+        }
 
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_mvex() {
+        let src_box = MvexBox {
+            mehd: Some(MehdBox {
+                fragment_duration: 4000,
+                ..MehdBox::default()
+            }),
+            trexs: vec![
+                TrexBox {
+                    track_id: 1,
+                    default_sample_description_index: 1,
+                    default_sample_duration: 40,
+                    default_sample_size: 0,
+                    default_sample_flags: 0x01010000,
+                    ..TrexBox::default()
+                },
+                TrexBox {
+                    track_id: 2,
+                    default_sample_description_index: 1,
+                    default_sample_duration: 1024,
+                    default_sample_size: 6,
+                    default_sample_flags: 0x02000000,
+                    ..TrexBox::default()
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::MvexBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = MvexBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.trex(2).unwrap().default_sample_duration, 1024);
+        assert!(dst_box.trex(3).is_none());
+    }
+}
@@ -0,0 +1,185 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Sample auxiliary information offsets box. In a `traf` the offsets are
+/// relative to the same base as the `trun` data offsets, in a `stbl` they
+/// are absolute file offsets.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SaioBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// Only stored when [SaioBox::FLAG_AUX_INFO_TYPE] is set.
+    pub aux_info_type: FourCC,
+    pub aux_info_type_parameter: u32,
+
+    /// One offset per chunk, or a single offset for all the samples of a
+    /// track fragment.
+    pub offsets: Vec<u64>,
+}
+
+impl SaioBox {
+    pub const FLAG_AUX_INFO_TYPE: u32 = 0x01;
+
+    /// Returns the `aux_info_type` and `aux_info_type_parameter`, if stored.
+    pub fn aux_info_type(&self) -> Option<(FourCC, u32)> {
+        if self.flags & Self::FLAG_AUX_INFO_TYPE != 0 {
+            Some((self.aux_info_type, self.aux_info_type_parameter))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SaioBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 4;
+        if self.flags & Self::FLAG_AUX_INFO_TYPE != 0 {
+            size += 8;
+        }
+        let offset_size = if self.version == 0 { 4 } else { 8 };
+        size += offset_size * self.offsets.len() as u64;
+        size
+    }
+}
+
+impl Mp4Box for SaioBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("entry_count={}", self.offsets.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SaioBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let (aux_info_type, aux_info_type_parameter) = if flags & Self::FLAG_AUX_INFO_TYPE != 0 {
+            (
+                FourCC::from(reader.read_u32::<BigEndian>()?),
+                reader.read_u32::<BigEndian>()?,
+            )
+        } else {
+            (FourCC::default(), 0)
+        };
+
+        let entry_count = reader.read_u32::<BigEndian>()?;
+        let offset_size = if version == 0 { 4 } else { 8 };
+        if offset_size * entry_count as u64 > size {
+            return Err(Error::InvalidData(
+                "saio entry_count indicates more entries than could fit in the box",
+            ));
+        }
+        let mut offsets = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let offset = if version == 0 {
+                reader.read_u32::<BigEndian>()? as u64
+            } else {
+                reader.read_u64::<BigEndian>()?
+            };
+            offsets.push(offset);
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SaioBox {
+            version,
+            flags,
+            aux_info_type,
+            aux_info_type_parameter,
+            offsets,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SaioBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        if self.flags & Self::FLAG_AUX_INFO_TYPE != 0 {
+            writer.write_u32::<BigEndian>((&self.aux_info_type).into())?;
+            writer.write_u32::<BigEndian>(self.aux_info_type_parameter)?;
+        }
+        writer.write_u32::<BigEndian>(self.offsets.len() as u32)?;
+        for offset in self.offsets.iter() {
+            if self.version == 0 {
+                writer.write_u32::<BigEndian>(*offset as u32)?;
+            } else {
+                writer.write_u64::<BigEndian>(*offset)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_saio() {
+        let src_box = SaioBox {
+            version: 0,
+            flags: 0,
+            aux_info_type: FourCC::default(),
+            aux_info_type_parameter: 0,
+            offsets: vec![1234],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SaioBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SaioBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_saio64() {
+        let src_box = SaioBox {
+            version: 1,
+            flags: SaioBox::FLAG_AUX_INFO_TYPE,
+            aux_info_type: str::parse("cbcs").unwrap(),
+            aux_info_type_parameter: 0,
+            offsets: vec![u32::MAX as u64 + 1, u32::MAX as u64 + 200],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SaioBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SaioBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
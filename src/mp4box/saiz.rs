@@ -0,0 +1,231 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Sample auxiliary information sizes box. For common encryption, the
+/// auxiliary information of a sample is its IV and subsample map.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SaizBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// Only stored when [SaizBox::FLAG_AUX_INFO_TYPE] is set.
+    pub aux_info_type: FourCC,
+    pub aux_info_type_parameter: u32,
+
+    /// Size of every sample's information, or 0 if the sizes vary.
+    pub default_sample_info_size: u8,
+    pub sample_count: u32,
+
+    /// Per-sample sizes, only stored when `default_sample_info_size` is 0.
+    #[serde(skip_serializing)]
+    pub sample_info_sizes: Vec<u8>,
+}
+
+impl SaizBox {
+    pub const FLAG_AUX_INFO_TYPE: u32 = 0x01;
+
+    /// Creates a box for the given per-sample sizes, using
+    /// `default_sample_info_size` when they are all the same.
+    pub fn new(sample_info_sizes: Vec<u8>) -> Self {
+        let sample_count = sample_info_sizes.len() as u32;
+        match sample_info_sizes.split_first() {
+            Some((first, rest)) if *first != 0 && rest.iter().all(|size| size == first) => {
+                SaizBox {
+                    default_sample_info_size: *first,
+                    sample_count,
+                    ..SaizBox::default()
+                }
+            }
+            _ => SaizBox {
+                sample_count,
+                sample_info_sizes,
+                ..SaizBox::default()
+            },
+        }
+    }
+
+    /// Returns the `aux_info_type` and `aux_info_type_parameter`, if stored.
+    pub fn aux_info_type(&self) -> Option<(FourCC, u32)> {
+        if self.flags & Self::FLAG_AUX_INFO_TYPE != 0 {
+            Some((self.aux_info_type, self.aux_info_type_parameter))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SaizBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 5;
+        if self.flags & Self::FLAG_AUX_INFO_TYPE != 0 {
+            size += 8;
+        }
+        if self.default_sample_info_size == 0 {
+            size += self.sample_info_sizes.len() as u64;
+        }
+        size
+    }
+
+    /// Returns the size of the information of a 0-based sample index.
+    pub fn sample_info_size(&self, index: usize) -> Option<u8> {
+        if index >= self.sample_count as usize {
+            None
+        } else if self.default_sample_info_size != 0 {
+            Some(self.default_sample_info_size)
+        } else {
+            self.sample_info_sizes.get(index).copied()
+        }
+    }
+}
+
+impl Mp4Box for SaizBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "default_sample_info_size={} sample_count={}",
+            self.default_sample_info_size, self.sample_count
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SaizBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let (aux_info_type, aux_info_type_parameter) = if flags & Self::FLAG_AUX_INFO_TYPE != 0 {
+            (
+                FourCC::from(reader.read_u32::<BigEndian>()?),
+                reader.read_u32::<BigEndian>()?,
+            )
+        } else {
+            (FourCC::default(), 0)
+        };
+        let default_sample_info_size = reader.read_u8()?;
+        let sample_count = reader.read_u32::<BigEndian>()?;
+
+        let mut sample_info_sizes = Vec::new();
+        if default_sample_info_size == 0 {
+            if sample_count as u64 > size {
+                return Err(Error::InvalidData(
+                    "saiz sample_count indicates more values than could fit in the box",
+                ));
+            }
+            sample_info_sizes = vec![0u8; sample_count as usize];
+            reader.read_exact(&mut sample_info_sizes)?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SaizBox {
+            version,
+            flags,
+            aux_info_type,
+            aux_info_type_parameter,
+            default_sample_info_size,
+            sample_count,
+            sample_info_sizes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SaizBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        if self.flags & Self::FLAG_AUX_INFO_TYPE != 0 {
+            writer.write_u32::<BigEndian>((&self.aux_info_type).into())?;
+            writer.write_u32::<BigEndian>(self.aux_info_type_parameter)?;
+        }
+        writer.write_u8(self.default_sample_info_size)?;
+        writer.write_u32::<BigEndian>(self.sample_count)?;
+        if self.default_sample_info_size == 0 {
+            writer.write_all(&self.sample_info_sizes)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_saiz() {
+        let src_box = SaizBox {
+            version: 0,
+            flags: 0,
+            aux_info_type: FourCC::default(),
+            aux_info_type_parameter: 0,
+            default_sample_info_size: 0,
+            sample_count: 3,
+            sample_info_sizes: vec![16, 22, 28],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SaizBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SaizBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.sample_info_size(1), Some(22));
+        assert_eq!(dst_box.sample_info_size(3), None);
+        assert_eq!(SaizBox::new(vec![16, 22, 28]), src_box);
+    }
+
+    #[test]
+    fn test_saiz_default_size() {
+        let src_box = SaizBox {
+            version: 0,
+            flags: SaizBox::FLAG_AUX_INFO_TYPE,
+            aux_info_type: str::parse("cenc").unwrap(),
+            aux_info_type_parameter: 0,
+            default_sample_info_size: 8,
+            sample_count: 100,
+            sample_info_sizes: vec![],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SaizBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SaizBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.sample_info_size(99), Some(8));
+
+        let new_box = SaizBox::new(vec![8; 100]);
+        assert_eq!(new_box.default_sample_info_size, 8);
+        assert_eq!(new_box.sample_count, 100);
+        assert!(new_box.sample_info_sizes.is_empty());
+    }
+}
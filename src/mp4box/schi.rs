@@ -0,0 +1,136 @@
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Scheme information box. Holds `tenc` for the common encryption schemes.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SchiBox {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenc: Option<TencBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+}
+
+impl SchiBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SchiBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE;
+        if let Some(ref tenc) = self.tenc {
+            size += tenc.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+}
+
+impl Mp4Box for SchiBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        Ok(String::new())
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SchiBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut tenc = None;
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "schi box contains a box with a larger size than it",
+                ));
+            }
+
+            match name {
+                BoxType::TencBox => {
+                    tenc = Some(TencBox::read_box(reader, s)?);
+                }
+                _ => {
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
+                }
+            }
+            children += 1;
+
+            current = reader.stream_position()?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SchiBox {
+            tenc,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SchiBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let mut children = ChildWriter::new(&self.unknown_boxes);
+        if let Some(ref tenc) = self.tenc {
+            children.write(writer, tenc)?;
+        }
+        children.finish(writer)?;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_schi() {
+        let src_box = SchiBox {
+            tenc: Some(TencBox {
+                default_is_protected: 1,
+                default_per_sample_iv_size: 16,
+                default_kid: [0x44; 16],
+                ..TencBox::default()
+            }),
+            unknown_boxes: vec![],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SchiBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SchiBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
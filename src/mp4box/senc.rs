@@ -0,0 +1,277 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Cursor, Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Sample encryption box, holding the IV and subsample map of every sample
+/// in a track fragment.
+///
+/// The IV size is not stored in the box itself but in the track's `tenc`,
+/// so the entries are kept serialized and decoded with
+/// [SencBox::entries].
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SencBox {
+    pub version: u8,
+    pub flags: u32,
+    pub sample_count: u32,
+
+    #[serde(skip_serializing)]
+    pub data: Vec<u8>,
+}
+
+impl SencBox {
+    pub const FLAG_USE_SUBSAMPLE_ENCRYPTION: u32 = 0x02;
+
+    pub fn new(entries: &[SampleEncryption]) -> Self {
+        let use_subsamples = entries.iter().any(|entry| !entry.subsamples.is_empty());
+        let mut data = Vec::new();
+        for entry in entries {
+            // Writing to a Vec cannot fail.
+            write_sample_encryption(&mut data, entry, use_subsamples).unwrap();
+        }
+        SencBox {
+            version: 0,
+            flags: if use_subsamples {
+                Self::FLAG_USE_SUBSAMPLE_ENCRYPTION
+            } else {
+                0
+            },
+            sample_count: entries.len() as u32,
+            data,
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SencBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 4 + self.data.len() as u64
+    }
+
+    /// Decodes the entries, given the per-sample IV size from `tenc`.
+    pub fn entries(&self, per_sample_iv_size: u8) -> Result<Vec<SampleEncryption>> {
+        let use_subsamples = self.flags & Self::FLAG_USE_SUBSAMPLE_ENCRYPTION != 0;
+        let mut reader = Cursor::new(&self.data);
+        let mut entries = Vec::new();
+        for _ in 0..self.sample_count {
+            entries.push(read_sample_encryption(
+                &mut reader,
+                per_sample_iv_size,
+                use_subsamples,
+            )?);
+        }
+        Ok(entries)
+    }
+}
+
+impl Mp4Box for SencBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("sample_count={}", self.sample_count);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SencBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let sample_count = reader.read_u32::<BigEndian>()?;
+
+        let header_size = HEADER_SIZE + HEADER_EXT_SIZE + 4;
+        if size < header_size {
+            return Err(Error::InvalidData("senc box is too small"));
+        }
+        let mut data = vec![0u8; (size - header_size) as usize];
+        reader.read_exact(&mut data)?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SencBox {
+            version,
+            flags,
+            sample_count,
+            data,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SencBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        writer.write_u32::<BigEndian>(self.sample_count)?;
+        writer.write_all(&self.data)?;
+
+        Ok(size)
+    }
+}
+
+/// Reads one entry in the `CencSampleAuxiliaryDataFormat` layout shared by
+/// `senc` and the auxiliary information referenced by `saio`.
+pub(crate) fn read_sample_encryption<R: Read>(
+    reader: &mut R,
+    iv_size: u8,
+    use_subsamples: bool,
+) -> Result<SampleEncryption> {
+    let mut iv = vec![0u8; iv_size as usize];
+    reader.read_exact(&mut iv)?;
+
+    let mut subsamples = Vec::new();
+    if use_subsamples {
+        let subsample_count = reader.read_u16::<BigEndian>()?;
+        for _ in 0..subsample_count {
+            subsamples.push(SubsampleEntry {
+                bytes_of_clear_data: reader.read_u16::<BigEndian>()?,
+                bytes_of_protected_data: reader.read_u32::<BigEndian>()?,
+            });
+        }
+    }
+    Ok(SampleEncryption { iv, subsamples })
+}
+
+pub(crate) fn write_sample_encryption<W: Write>(
+    writer: &mut W,
+    entry: &SampleEncryption,
+    use_subsamples: bool,
+) -> Result<u64> {
+    writer.write_all(&entry.iv)?;
+    let mut size = entry.iv.len() as u64;
+    if use_subsamples {
+        writer.write_u16::<BigEndian>(entry.subsamples.len() as u16)?;
+        for subsample in entry.subsamples.iter() {
+            writer.write_u16::<BigEndian>(subsample.bytes_of_clear_data)?;
+            writer.write_u32::<BigEndian>(subsample.bytes_of_protected_data)?;
+        }
+        size += 2 + 6 * entry.subsamples.len() as u64;
+    }
+    Ok(size)
+}
+
+/// Finds the `saiz` and `saio` boxes locating the auxiliary information of a
+/// protection scheme, pairing them by `aux_info_type` and
+/// `aux_info_type_parameter`. Boxes without a type are of the scheme.
+pub(crate) fn scheme_aux_info<'a>(
+    saizs: &'a [SaizBox],
+    saios: &'a [SaioBox],
+    scheme_type: Option<FourCC>,
+) -> Option<(&'a SaizBox, &'a SaioBox)> {
+    let key = |aux_info_type: Option<(FourCC, u32)>| {
+        aux_info_type
+            .map(|(aux_info_type, parameter)| (Some(aux_info_type), parameter))
+            .unwrap_or((scheme_type, 0))
+    };
+    saizs
+        .iter()
+        .filter(|saiz| key(saiz.aux_info_type()).0 == scheme_type)
+        .find_map(|saiz| {
+            let saiz_key = key(saiz.aux_info_type());
+            saios
+                .iter()
+                .find(|saio| key(saio.aux_info_type()) == saiz_key)
+                .map(|saio| (saiz, saio))
+        })
+}
+
+/// Serializes the auxiliary information of samples in the `senc` layout,
+/// returning the data and the size of each entry.
+pub(crate) fn sample_aux_info(entries: &[SampleEncryption]) -> (Vec<u8>, Vec<u8>) {
+    let use_subsamples = entries.iter().any(|entry| !entry.subsamples.is_empty());
+    let mut data = Vec::new();
+    let mut sizes = Vec::with_capacity(entries.len());
+    for entry in entries {
+        // Writing to a Vec cannot fail.
+        let size = write_sample_encryption(&mut data, entry, use_subsamples).unwrap();
+        sizes.push(size as u8);
+    }
+    (data, sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_senc() {
+        let entries = vec![
+            SampleEncryption {
+                iv: vec![1; 8],
+                subsamples: vec![],
+            },
+            SampleEncryption {
+                iv: vec![2; 8],
+                subsamples: vec![],
+            },
+        ];
+        let src_box = SencBox::new(&entries);
+        assert_eq!(src_box.flags, 0);
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.entries(8).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_senc_subsamples() {
+        let entries = vec![
+            SampleEncryption {
+                iv: vec![3; 16],
+                subsamples: vec![
+                    SubsampleEntry {
+                        bytes_of_clear_data: 5,
+                        bytes_of_protected_data: 1024,
+                    },
+                    SubsampleEntry {
+                        bytes_of_clear_data: 100,
+                        bytes_of_protected_data: 0,
+                    },
+                ],
+            },
+            SampleEncryption {
+                iv: vec![4; 16],
+                subsamples: vec![],
+            },
+        ];
+        let src_box = SencBox::new(&entries);
+        assert_eq!(src_box.flags, SencBox::FLAG_USE_SUBSAMPLE_ENCRYPTION);
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.entries(16).unwrap(), entries);
+        assert!(dst_box.entries(8).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Protection scheme information box of a protected sample entry.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SinfBox {
+    pub frma: FrmaBox,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schm: Option<SchmBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schi: Option<SchiBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
+}
+
+impl SinfBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SinfBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + self.frma.box_size();
+        if let Some(ref schm) = self.schm {
+            size += schm.box_size();
+        }
+        if let Some(ref schi) = self.schi {
+            size += schi.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
+        size
+    }
+
+    /// Returns the protection scheme, e.g. `cenc` or `cbcs`.
+    pub fn scheme_type(&self) -> Option<FourCC> {
+        self.schm.as_ref().map(|schm| schm.scheme_type)
+    }
+
+    pub fn tenc(&self) -> Option<&TencBox> {
+        self.schi.as_ref().and_then(|schi| schi.tenc.as_ref())
+    }
+}
+
+impl Mp4Box for SinfBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = match self.scheme_type() {
+            Some(scheme_type) => format!(
+                "data_format={} scheme_type={}",
+                self.frma.data_format, scheme_type
+            ),
+            None => format!("data_format={}", self.frma.data_format),
+        };
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SinfBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut frma = None;
+        let mut schm = None;
+        let mut schi = None;
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "sinf box contains a box with a larger size than it",
+                ));
+            }
+
+            match name {
+                BoxType::FrmaBox => {
+                    frma = Some(FrmaBox::read_box(reader, s)?);
+                }
+                BoxType::SchmBox => {
+                    schm = Some(SchmBox::read_box(reader, s)?);
+                }
+                BoxType::SchiBox => {
+                    schi = Some(SchiBox::read_box(reader, s)?);
+                }
+                _ => {
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
+                }
+            }
+            children += 1;
+
+            current = reader.stream_position()?;
+        }
+
+        if frma.is_none() {
+            return Err(Error::BoxNotFound(BoxType::FrmaBox));
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SinfBox {
+            frma: frma.unwrap(),
+            schm,
+            schi,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SinfBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let mut children = ChildWriter::new(&self.unknown_boxes);
+        children.write(writer, &self.frma)?;
+        if let Some(ref schm) = self.schm {
+            children.write(writer, schm)?;
+        }
+        if let Some(ref schi) = self.schi {
+            children.write(writer, schi)?;
+        }
+        children.finish(writer)?;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sinf() {
+        let src_box = SinfBox {
+            frma: FrmaBox {
+                data_format: str::parse("mp4a").unwrap(),
+            },
+            schm: Some(SchmBox {
+                scheme_type: str::parse("cenc").unwrap(),
+                scheme_version: 0x00010000,
+                ..SchmBox::default()
+            }),
+            schi: Some(SchiBox {
+                tenc: Some(TencBox {
+                    default_is_protected: 1,
+                    default_per_sample_iv_size: 8,
+                    default_kid: [0x55; 16],
+                    ..TencBox::default()
+                }),
+                unknown_boxes: vec![],
+            }),
+            unknown_boxes: vec![
+                UnknownBox::new(BoxType::from(0x74657374), vec![1, 2, 3]).with_position(3)
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SinfBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SinfBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.scheme_type(), Some(str::parse("cenc").unwrap()));
+        assert_eq!(dst_box.tenc().unwrap().default_kid, [0x55; 16]);
+    }
+}
@@ -3,8 +3,8 @@ use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
 use crate::mp4box::{
-    co64::Co64Box, ctts::CttsBox, stco::StcoBox, stsc::StscBox, stsd::StsdBox, stss::StssBox,
-    stsz::StszBox, stts::SttsBox,
+    co64::Co64Box, ctts::CttsBox, saio::SaioBox, saiz::SaizBox, stco::StcoBox, stsc::StscBox,
+    stsd::StsdBox, stss::StssBox, stsz::StszBox, stts::SttsBox,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
@@ -25,6 +25,15 @@ pub struct StblBox {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub co64: Option<Co64Box>,
+
+    #[serde(rename = "saiz", skip_serializing_if = "Vec::is_empty")]
+    pub saizs: Vec<SaizBox>,
+
+    #[serde(rename = "saio", skip_serializing_if = "Vec::is_empty")]
+    pub saios: Vec<SaioBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl StblBox {
@@ -50,6 +59,15 @@ impl StblBox {
         if let Some(ref co64) = self.co64 {
             size += co64.box_size();
         }
+        for saiz in self.saizs.iter() {
+            size += saiz.box_size();
+        }
+        for saio in self.saios.iter() {
+            size += saio.box_size();
+        }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -85,6 +103,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
         let mut stsz = None;
         let mut stco = None;
         let mut co64 = None;
+        let mut saizs = Vec::new();
+        let mut saios = Vec::new();
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -123,11 +145,18 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
                 BoxType::Co64Box => {
                     co64 = Some(Co64Box::read_box(reader, s)?);
                 }
+                BoxType::SaizBox => {
+                    saizs.push(SaizBox::read_box(reader, s)?);
+                }
+                BoxType::SaioBox => {
+                    saios.push(SaioBox::read_box(reader, s)?);
+                }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
                 }
             }
+            children += 1;
+
             current = reader.stream_position()?;
         }
 
@@ -158,6 +187,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
             stsz: stsz.unwrap(),
             stco,
             co64,
+            saizs,
+            saios,
+            unknown_boxes,
         })
     }
 }
@@ -167,23 +199,30 @@ impl<W: Write> WriteBox<&mut W> for StblBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.stsd.write_box(writer)?;
-        self.stts.write_box(writer)?;
+        let mut children = ChildWriter::new(&self.unknown_boxes);
+        children.write(writer, &self.stsd)?;
+        children.write(writer, &self.stts)?;
         if let Some(ref ctts) = self.ctts {
-            ctts.write_box(writer)?;
+            children.write(writer, ctts)?;
         }
         if let Some(ref stss) = self.stss {
-            stss.write_box(writer)?;
+            children.write(writer, stss)?;
         }
-        self.stsc.write_box(writer)?;
-        self.stsz.write_box(writer)?;
+        children.write(writer, &self.stsc)?;
+        children.write(writer, &self.stsz)?;
         if let Some(ref stco) = self.stco {
-            stco.write_box(writer)?;
+            children.write(writer, stco)?;
         }
         if let Some(ref co64) = self.co64 {
-            co64.write_box(writer)?;
+            children.write(writer, co64)?;
         }
-
+        for saiz in self.saizs.iter() {
+            children.write(writer, saiz)?;
+        }
+        for saio in self.saios.iter() {
+            children.write(writer, saio)?;
+        }
+        children.finish(writer)?;
         Ok(size)
     }
 }
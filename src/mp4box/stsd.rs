@@ -1,30 +1,309 @@
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
 use serde::Serialize;
-use std::io::{Read, Seek, Write};
+use std::cmp;
+use std::io::{Cursor, Read, Seek, Write};
 
 use crate::mp4box::vp09::Vp09Box;
 use crate::mp4box::*;
-use crate::mp4box::{avc1::Avc1Box, hev1::Hev1Box, mp4a::Mp4aBox, tx3g::Tx3gBox};
+use crate::mp4box::{
+    av01::Av01Box, avc1::Avc1Box, hev1::Hev1Box, mp4a::Mp4aBox, opus::OpusBox, tx3g::Tx3gBox,
+};
+
+/// A single sample description in an [`StsdBox`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleEntry {
+    Avc1(Avc1Box),
+    /// `hev1` or `hvc1`.
+    Hev1(Hev1Box),
+    Vp09(Vp09Box),
+    Av01(Av01Box),
+    Mp4a(Mp4aBox),
+    Opus(OpusBox),
+    Tx3g(Tx3gBox),
+    Encrypted(EncryptedSampleEntry),
+    Unknown(UnknownBox),
+}
+
+impl SampleEntry {
+    fn read<R: Read + Seek>(reader: &mut R, name: BoxType, size: u64) -> Result<Self> {
+        let entry = match name {
+            BoxType::Avc1Box => SampleEntry::Avc1(Avc1Box::read_box(reader, size)?),
+            BoxType::Hev1Box | BoxType::Hvc1Box => {
+                SampleEntry::Hev1(Hev1Box::read_box(reader, size)?)
+            }
+            BoxType::Vp09Box => SampleEntry::Vp09(Vp09Box::read_box(reader, size)?),
+            BoxType::Av01Box => SampleEntry::Av01(Av01Box::read_box(reader, size)?),
+            BoxType::Mp4aBox => SampleEntry::Mp4a(Mp4aBox::read_box(reader, size)?),
+            BoxType::OpusBox => SampleEntry::Opus(OpusBox::read_box(reader, size)?),
+            BoxType::Tx3gBox => SampleEntry::Tx3g(Tx3gBox::read_box(reader, size)?),
+            BoxType::EncvBox | BoxType::EncaBox => {
+                SampleEntry::Encrypted(EncryptedSampleEntry::read(reader, name, size)?)
+            }
+            _ => SampleEntry::Unknown(UnknownBox::read(reader, name, size)?),
+        };
+        Ok(entry)
+    }
+
+    /// Returns the original sample entry of a protected `encv` or `enca`
+    /// entry, or the entry itself if it is not protected.
+    pub fn unprotected(&self) -> &SampleEntry {
+        match self {
+            SampleEntry::Encrypted(b) => &b.entry,
+            _ => self,
+        }
+    }
+}
+
+impl Mp4Box for SampleEntry {
+    fn box_type(&self) -> BoxType {
+        match self {
+            SampleEntry::Avc1(b) => b.box_type(),
+            SampleEntry::Hev1(b) => b.box_type(),
+            SampleEntry::Vp09(b) => b.box_type(),
+            SampleEntry::Av01(b) => b.box_type(),
+            SampleEntry::Mp4a(b) => b.box_type(),
+            SampleEntry::Opus(b) => b.box_type(),
+            SampleEntry::Tx3g(b) => b.box_type(),
+            SampleEntry::Encrypted(b) => b.box_type(),
+            SampleEntry::Unknown(b) => b.box_type(),
+        }
+    }
+
+    fn box_size(&self) -> u64 {
+        match self {
+            SampleEntry::Avc1(b) => b.box_size(),
+            SampleEntry::Hev1(b) => b.box_size(),
+            SampleEntry::Vp09(b) => b.box_size(),
+            SampleEntry::Av01(b) => b.box_size(),
+            SampleEntry::Mp4a(b) => b.box_size(),
+            SampleEntry::Opus(b) => b.box_size(),
+            SampleEntry::Tx3g(b) => b.box_size(),
+            SampleEntry::Encrypted(b) => b.box_size(),
+            SampleEntry::Unknown(b) => b.box_size(),
+        }
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        match self {
+            SampleEntry::Avc1(b) => b.summary(),
+            SampleEntry::Hev1(b) => b.summary(),
+            SampleEntry::Vp09(b) => b.summary(),
+            SampleEntry::Av01(b) => b.summary(),
+            SampleEntry::Mp4a(b) => b.summary(),
+            SampleEntry::Opus(b) => b.summary(),
+            SampleEntry::Tx3g(b) => b.summary(),
+            SampleEntry::Encrypted(b) => b.summary(),
+            SampleEntry::Unknown(b) => b.summary(),
+        }
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SampleEntry {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        match self {
+            SampleEntry::Avc1(b) => b.write_box(writer),
+            SampleEntry::Hev1(b) => b.write_box(writer),
+            SampleEntry::Vp09(b) => b.write_box(writer),
+            SampleEntry::Av01(b) => b.write_box(writer),
+            SampleEntry::Mp4a(b) => b.write_box(writer),
+            SampleEntry::Opus(b) => b.write_box(writer),
+            SampleEntry::Tx3g(b) => b.write_box(writer),
+            SampleEntry::Encrypted(b) => b.write_box(writer),
+            SampleEntry::Unknown(b) => b.write_box(writer),
+        }
+    }
+}
+
+/// A protected sample entry (`encv` or `enca`), holding the original sample
+/// entry and the protection scheme information that was appended to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EncryptedSampleEntry {
+    /// [BoxType::EncvBox] or [BoxType::EncaBox].
+    pub box_type: BoxType,
+    pub entry: Box<SampleEntry>,
+    pub sinf: SinfBox,
+
+    /// Index of sinf among the child boxes of the entry, when it was read
+    /// before other children. `None` appends it.
+    #[serde(skip)]
+    sinf_position: Option<usize>,
+}
+
+impl EncryptedSampleEntry {
+    /// Protects `entry`, recording its type in `sinf.frma`.
+    pub fn new(entry: SampleEntry, mut sinf: SinfBox) -> Self {
+        let box_type = match entry {
+            SampleEntry::Mp4a(_) | SampleEntry::Opus(_) => BoxType::EncaBox,
+            _ => BoxType::EncvBox,
+        };
+        sinf.frma.data_format = entry.box_type().into();
+        EncryptedSampleEntry {
+            box_type,
+            entry: Box::new(entry),
+            sinf,
+            sinf_position: None,
+        }
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        self.box_type
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.entry.box_size() + self.sinf.box_size()
+    }
+
+    fn read<R: Read + Seek>(reader: &mut R, name: BoxType, size: u64) -> Result<Self> {
+        if size < HEADER_SIZE {
+            return Err(Error::InvalidData("box size is smaller than its header"));
+        }
+        let mut payload = vec![0u8; (size - HEADER_SIZE) as usize];
+        reader.read_exact(&mut payload)?;
+
+        let mut reader = Cursor::new(&payload);
+        let mut current = children_start(name, &payload);
+        let mut children = 0;
+        let mut sinf = None;
+        while current + HEADER_SIZE <= payload.len() as u64 {
+            reader.set_position(current);
+            let header = BoxHeader::read(&mut reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s < HEADER_SIZE || current + s > payload.len() as u64 {
+                return Err(Error::InvalidData(
+                    "protected sample entry contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::SinfBox {
+                sinf = Some((current, s, SinfBox::read_box(&mut reader, s)?));
+                break;
+            }
+            children += 1;
+            current += s;
+        }
+        let (sinf_start, sinf_size, sinf) = sinf.ok_or(Error::BoxNotFound(BoxType::SinfBox))?;
+        let sinf_end = sinf_start + sinf_size;
+        let sinf_position = if sinf_end + HEADER_SIZE <= payload.len() as u64 {
+            Some(children)
+        } else {
+            None
+        };
+
+        // Parse the original entry from the payload without sinf, using the
+        // size of sinf on disk rather than its re-serialized size.
+        let original = BoxType::from(u32::from(&sinf.frma.data_format));
+        let mut buf = Vec::with_capacity(payload.len());
+        BoxHeader::new(original, size - sinf_size).write(&mut buf)?;
+        buf.extend_from_slice(&payload[..sinf_start as usize]);
+        buf.extend_from_slice(&payload[sinf_end as usize..]);
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader)?;
+        let entry = SampleEntry::read(&mut reader, original, header.size)?;
+
+        Ok(EncryptedSampleEntry {
+            box_type: name,
+            entry: Box::new(entry),
+            sinf,
+            sinf_position,
+        })
+    }
+}
+
+/// Returns the offset of the first child box in the payload of a sample
+/// entry, after the fields of the visual or audio sample entry.
+fn children_start(name: BoxType, payload: &[u8]) -> u64 {
+    if name == BoxType::EncvBox {
+        78
+    } else {
+        match payload.get(8..10).map(BigEndian::read_u16) {
+            Some(1) => 44,
+            Some(2) => 64,
+            _ => 28,
+        }
+    }
+}
+
+impl Mp4Box for EncryptedSampleEntry {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("{} {}", self.sinf.summary()?, self.entry.summary()?);
+        Ok(s)
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for EncryptedSampleEntry {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+
+        // The original entry with its type replaced and sinf inserted at its
+        // original position, or appended.
+        let mut buf = Vec::new();
+        self.entry.write_box(&mut buf)?;
+        if buf.len() < HEADER_SIZE as usize || buf[0..4] == [0, 0, 0, 1] {
+            return Err(Error::InvalidData(
+                "unsupported protected sample entry size",
+            ));
+        }
+        let payload = &buf[HEADER_SIZE as usize..];
+        let mut sinf_start = payload.len() as u64;
+        if let Some(position) = self.sinf_position {
+            let mut current = children_start(self.box_type, payload);
+            for _ in 0..position {
+                match payload.get(current as usize..current as usize + 4) {
+                    Some(size) if BigEndian::read_u32(size) as u64 >= HEADER_SIZE => {
+                        current += BigEndian::read_u32(size) as u64
+                    }
+                    _ => break,
+                }
+            }
+            sinf_start = cmp::min(current, sinf_start);
+        }
+        BoxHeader::new(self.box_type, size).write(writer)?;
+        writer.write_all(&payload[..sinf_start as usize])?;
+        self.sinf.write_box(writer)?;
+        writer.write_all(&payload[sinf_start as usize..])?;
+
+        Ok(size)
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct StsdBox {
     pub version: u8,
     pub flags: u32,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub avc1: Option<Avc1Box>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub hev1: Option<Hev1Box>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub vp09: Option<Vp09Box>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub mp4a: Option<Mp4aBox>,
+    #[serde(rename = "entry")]
+    pub entries: Vec<SampleEntry>,
+}
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tx3g: Option<Tx3gBox>,
+macro_rules! first_entry {
+    ($name:ident, $variant:ident, $box:ty) => {
+        /// Returns the first sample description of this type, if any,
+        /// looking through protected entries.
+        pub fn $name(&self) -> Option<&$box> {
+            self.entries
+                .iter()
+                .find_map(|entry| match entry.unprotected() {
+                    SampleEntry::$variant(b) => Some(b),
+                    _ => None,
+                })
+        }
+    };
 }
 
 impl StsdBox {
@@ -34,19 +313,26 @@ impl StsdBox {
 
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 4;
-        if let Some(ref avc1) = self.avc1 {
-            size += avc1.box_size();
-        } else if let Some(ref hev1) = self.hev1 {
-            size += hev1.box_size();
-        } else if let Some(ref vp09) = self.vp09 {
-            size += vp09.box_size();
-        } else if let Some(ref mp4a) = self.mp4a {
-            size += mp4a.box_size();
-        } else if let Some(ref tx3g) = self.tx3g {
-            size += tx3g.box_size();
+        for entry in self.entries.iter() {
+            size += entry.box_size();
         }
         size
     }
+
+    /// Returns the sample description referenced by a 1-based
+    /// `sample_description_index`, as used in stsc and tfhd.
+    pub fn entry(&self, sample_description_index: u32) -> Option<&SampleEntry> {
+        let index = sample_description_index.checked_sub(1)?;
+        self.entries.get(index as usize)
+    }
+
+    first_entry!(avc1, Avc1, Avc1Box);
+    first_entry!(hev1, Hev1, Hev1Box);
+    first_entry!(vp09, Vp09, Vp09Box);
+    first_entry!(av01, Av01, Av01Box);
+    first_entry!(mp4a, Mp4a, Mp4aBox);
+    first_entry!(opus, Opus, OpusBox);
+    first_entry!(tx3g, Tx3g, Tx3gBox);
 }
 
 impl Mp4Box for StsdBox {
@@ -63,7 +349,7 @@ impl Mp4Box for StsdBox {
     }
 
     fn summary(&self) -> Result<String> {
-        let s = String::new();
+        let s = format!("entries={}", self.entries.len());
         Ok(s)
     }
 }
@@ -74,52 +360,36 @@ impl<R: Read + Seek> ReadBox<&mut R> for StsdBox {
 
         let (version, flags) = read_box_header_ext(reader)?;
 
-        reader.read_u32::<BigEndian>()?; // XXX entry_count
-
-        let mut avc1 = None;
-        let mut hev1 = None;
-        let mut vp09 = None;
-        let mut mp4a = None;
-        let mut tx3g = None;
-
-        // Get box header.
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "stsd box contains a box with a larger size than it",
-            ));
-        }
+        let entry_count = reader.read_u32::<BigEndian>()?;
 
-        match name {
-            BoxType::Avc1Box => {
-                avc1 = Some(Avc1Box::read_box(reader, s)?);
+        let mut entries = Vec::new();
+        let end = start + size;
+        for _ in 0..entry_count {
+            let current = reader.stream_position()?;
+            if current >= end {
+                break;
             }
-            BoxType::Hev1Box => {
-                hev1 = Some(Hev1Box::read_box(reader, s)?);
-            }
-            BoxType::Vp09Box => {
-                vp09 = Some(Vp09Box::read_box(reader, s)?);
-            }
-            BoxType::Mp4aBox => {
-                mp4a = Some(Mp4aBox::read_box(reader, s)?);
-            }
-            BoxType::Tx3gBox => {
-                tx3g = Some(Tx3gBox::read_box(reader, s)?);
+
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "stsd box contains a box with a larger size than it",
+                ));
             }
-            _ => {}
+
+            entries.push(SampleEntry::read(reader, name, s)?);
+
+            skip_bytes_to(reader, current + s)?;
         }
 
-        skip_bytes_to(reader, start + size)?;
+        skip_bytes_to(reader, end)?;
 
         Ok(StsdBox {
             version,
             flags,
-            avc1,
-            hev1,
-            vp09,
-            mp4a,
-            tx3g,
+            entries,
         })
     }
 }
@@ -131,20 +401,132 @@ impl<W: Write> WriteBox<&mut W> for StsdBox {
 
         write_box_header_ext(writer, self.version, self.flags)?;
 
-        writer.write_u32::<BigEndian>(1)?; // entry_count
-
-        if let Some(ref avc1) = self.avc1 {
-            avc1.write_box(writer)?;
-        } else if let Some(ref hev1) = self.hev1 {
-            hev1.write_box(writer)?;
-        } else if let Some(ref vp09) = self.vp09 {
-            vp09.write_box(writer)?;
-        } else if let Some(ref mp4a) = self.mp4a {
-            mp4a.write_box(writer)?;
-        } else if let Some(ref tx3g) = self.tx3g {
-            tx3g.write_box(writer)?;
+        writer.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in self.entries.iter() {
+            entry.write_box(writer)?;
         }
 
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_stsd_multiple_entries() {
+        let first = Hev1Box::new(&HevcConfig {
+            width: 1280,
+            height: 720,
+            ..Default::default()
+        })
+        .unwrap();
+        let second = Hev1Box::new(&HevcConfig {
+            width: 1920,
+            height: 1080,
+            ..Default::default()
+        })
+        .unwrap();
+        let src_box = StsdBox {
+            version: 0,
+            flags: 0,
+            entries: vec![
+                SampleEntry::Hev1(first.clone()),
+                SampleEntry::Hev1(second.clone()),
+                SampleEntry::Vp09(Vp09Box::new(&Vp9Config {
+                    width: 1920,
+                    height: 1080,
+                })),
+                SampleEntry::Unknown(UnknownBox::new(BoxType::from(0x61766333), vec![0; 16])),
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::StsdBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = StsdBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.hev1(), Some(&first));
+        assert_eq!(dst_box.entry(2), Some(&SampleEntry::Hev1(second)));
+        assert_eq!(dst_box.entry(0), None);
+        assert_eq!(dst_box.entry(5), None);
+        assert!(dst_box.avc1().is_none());
+        assert!(dst_box.vp09().is_some());
+    }
+
+    #[test]
+    fn test_stsd_encrypted_entry_keeps_unknown_sinf_children() {
+        let sinf = SinfBox {
+            schm: Some(SchmBox {
+                scheme_type: str::parse("cenc").unwrap(),
+                scheme_version: 0x00010000,
+                ..SchmBox::default()
+            }),
+            unknown_boxes: vec![
+                UnknownBox::new(BoxType::from(0x74657374), vec![7; 5]).with_position(2)
+            ],
+            ..SinfBox::default()
+        };
+        let entry = SampleEntry::Mp4a(Mp4aBox::new(&AacConfig::default()));
+        let src_box = StsdBox {
+            version: 0,
+            flags: 0,
+            entries: vec![SampleEntry::Encrypted(EncryptedSampleEntry::new(
+                entry.clone(),
+                sinf,
+            ))],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = StsdBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        match dst_box.entry(1) {
+            Some(SampleEntry::Encrypted(encrypted)) => assert_eq!(*encrypted.entry, entry),
+            other => panic!("unexpected entry {:?}", other),
+        }
+    }
+    #[test]
+    fn test_stsd_encrypted_entry_keeps_sinf_position() {
+        let mp4a = Mp4aBox::new(&AacConfig::default());
+        let encrypted =
+            EncryptedSampleEntry::new(SampleEntry::Mp4a(mp4a.clone()), SinfBox::default());
+        let mut entry_buf = Vec::new();
+        mp4a.write_box(&mut entry_buf).unwrap();
+        let mut sinf_buf = Vec::new();
+        encrypted.sinf.write_box(&mut sinf_buf).unwrap();
+
+        // sinf between the audio sample entry fields and esds.
+        let payload = &entry_buf[HEADER_SIZE as usize..];
+        let mut buf = Vec::new();
+        BoxHeader::new(
+            BoxType::EncaBox,
+            HEADER_SIZE + (payload.len() + sinf_buf.len()) as u64,
+        )
+        .write(&mut buf)
+        .unwrap();
+        buf.extend_from_slice(&payload[..28]);
+        buf.extend_from_slice(&sinf_buf);
+        buf.extend_from_slice(&payload[28..]);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = EncryptedSampleEntry::read(&mut reader, header.name, header.size).unwrap();
+        assert_eq!(*dst_box.entry, SampleEntry::Mp4a(mp4a));
+
+        let mut written = Vec::new();
+        dst_box.write_box(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+}
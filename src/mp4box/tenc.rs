@@ -0,0 +1,192 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Track encryption box, holding the default encryption parameters of a
+/// protected track (ISO/IEC 23001-7).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct TencBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// Pattern encryption block counts, only stored in version 1.
+    pub default_crypt_byte_block: u8,
+    pub default_skip_byte_block: u8,
+    pub default_is_protected: u8,
+    pub default_per_sample_iv_size: u8,
+    pub default_kid: [u8; 16],
+
+    /// Only present for protected tracks with a per-sample IV size of 0.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub default_constant_iv: Vec<u8>,
+}
+
+impl TencBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::TencBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 20;
+        if self.has_constant_iv() {
+            size += 1 + self.default_constant_iv.len() as u64;
+        }
+        size
+    }
+
+    fn has_constant_iv(&self) -> bool {
+        self.default_is_protected == 1 && self.default_per_sample_iv_size == 0
+    }
+}
+
+impl Mp4Box for TencBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "is_protected={} per_sample_iv_size={} kid={}",
+            self.default_is_protected,
+            self.default_per_sample_iv_size,
+            self.default_kid
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for TencBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        reader.read_u8()?; // reserved
+        let (default_crypt_byte_block, default_skip_byte_block) = if version == 0 {
+            reader.read_u8()?; // reserved
+            (0, 0)
+        } else {
+            let pattern = reader.read_u8()?;
+            (pattern >> 4, pattern & 0x0f)
+        };
+        let default_is_protected = reader.read_u8()?;
+        let default_per_sample_iv_size = reader.read_u8()?;
+        let mut default_kid = [0u8; 16];
+        reader.read_exact(&mut default_kid)?;
+
+        let mut default_constant_iv = Vec::new();
+        if default_is_protected == 1 && default_per_sample_iv_size == 0 {
+            let iv_size = reader.read_u8()?;
+            default_constant_iv = vec![0u8; iv_size as usize];
+            reader.read_exact(&mut default_constant_iv)?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(TencBox {
+            version,
+            flags,
+            default_crypt_byte_block,
+            default_skip_byte_block,
+            default_is_protected,
+            default_per_sample_iv_size,
+            default_kid,
+            default_constant_iv,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for TencBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        writer.write_u8(0)?; // reserved
+        if self.version == 0 {
+            writer.write_u8(0)?; // reserved
+        } else {
+            writer.write_u8(
+                self.default_crypt_byte_block << 4 | self.default_skip_byte_block & 0x0f,
+            )?;
+        }
+        writer.write_u8(self.default_is_protected)?;
+        writer.write_u8(self.default_per_sample_iv_size)?;
+        writer.write_all(&self.default_kid)?;
+        if self.has_constant_iv() {
+            writer.write_u8(self.default_constant_iv.len() as u8)?;
+            writer.write_all(&self.default_constant_iv)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tenc() {
+        let src_box = TencBox {
+            version: 0,
+            flags: 0,
+            default_crypt_byte_block: 0,
+            default_skip_byte_block: 0,
+            default_is_protected: 1,
+            default_per_sample_iv_size: 8,
+            default_kid: [0x11; 16],
+            default_constant_iv: vec![],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_tenc_pattern_constant_iv() {
+        let src_box = TencBox {
+            version: 1,
+            flags: 0,
+            default_crypt_byte_block: 1,
+            default_skip_byte_block: 9,
+            default_is_protected: 1,
+            default_per_sample_iv_size: 0,
+            default_kid: [0x22; 16],
+            default_constant_iv: vec![0x33; 16],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,284 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Track fragment random access box, listing the sync samples of a track
+/// and the fragments they are in.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct TfraBox {
+    pub version: u8,
+    pub flags: u32,
+    pub track_id: u32,
+
+    /// Number of bytes minus one used for the `traf_number` of the entries.
+    pub length_size_of_traf_num: u8,
+    /// Number of bytes minus one used for the `trun_number` of the entries.
+    pub length_size_of_trun_num: u8,
+    /// Number of bytes minus one used for the `sample_number` of the entries.
+    pub length_size_of_sample_num: u8,
+
+    #[serde(skip_serializing)]
+    pub entries: Vec<TfraEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct TfraEntry {
+    /// Presentation time of the sync sample in the track timescale.
+    pub time: u64,
+    /// Offset of the `moof` box from the start of the file.
+    pub moof_offset: u64,
+    /// The traf, trun and sample numbers start from 1.
+    pub traf_number: u32,
+    pub trun_number: u32,
+    pub sample_number: u32,
+}
+
+impl TfraBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::TfraBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut entry_size = self.length_size_of_traf_num as u64
+            + self.length_size_of_trun_num as u64
+            + self.length_size_of_sample_num as u64
+            + 3;
+        if self.version == 1 {
+            entry_size += 16;
+        } else {
+            entry_size += 8;
+        }
+        HEADER_SIZE + HEADER_EXT_SIZE + 12 + entry_size * self.entries.len() as u64
+    }
+
+    /// Sets the length sizes to the smallest ones that fit the entries.
+    pub fn fit_length_sizes(&mut self) {
+        let length_size = |value: u32| (3 - value.leading_zeros().min(24) / 8) as u8;
+        let entries = &self.entries;
+        let max = |f: fn(&TfraEntry) -> u32| entries.iter().map(f).max().unwrap_or(0);
+        let traf_num = max(|entry| entry.traf_number);
+        let trun_num = max(|entry| entry.trun_number);
+        let sample_num = max(|entry| entry.sample_number);
+        self.length_size_of_traf_num = length_size(traf_num);
+        self.length_size_of_trun_num = length_size(trun_num);
+        self.length_size_of_sample_num = length_size(sample_num);
+    }
+}
+
+impl Mp4Box for TfraBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "track_id={} entry_count={}",
+            self.track_id,
+            self.entries.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for TfraBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        if version > 1 {
+            return Err(Error::InvalidData("version must be 0 or 1"));
+        }
+        let track_id = reader.read_u32::<BigEndian>()?;
+        let length_sizes = reader.read_u32::<BigEndian>()?;
+        let length_size_of_traf_num = ((length_sizes >> 4) & 0x3) as u8;
+        let length_size_of_trun_num = ((length_sizes >> 2) & 0x3) as u8;
+        let length_size_of_sample_num = (length_sizes & 0x3) as u8;
+        let entry_count = reader.read_u32::<BigEndian>()?;
+
+        let entry_size = if version == 1 { 16 } else { 8 }
+            + length_size_of_traf_num as u64
+            + length_size_of_trun_num as u64
+            + length_size_of_sample_num as u64
+            + 3;
+        if entry_count as u64 * entry_size > size {
+            return Err(Error::InvalidData(
+                "tfra entry_count indicates more entries than could fit in the box",
+            ));
+        }
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let (time, moof_offset) = if version == 1 {
+                (
+                    reader.read_u64::<BigEndian>()?,
+                    reader.read_u64::<BigEndian>()?,
+                )
+            } else {
+                (
+                    reader.read_u32::<BigEndian>()? as u64,
+                    reader.read_u32::<BigEndian>()? as u64,
+                )
+            };
+            entries.push(TfraEntry {
+                time,
+                moof_offset,
+                traf_number: reader.read_uint::<BigEndian>(length_size_of_traf_num as usize + 1)?
+                    as u32,
+                trun_number: reader.read_uint::<BigEndian>(length_size_of_trun_num as usize + 1)?
+                    as u32,
+                sample_number: reader
+                    .read_uint::<BigEndian>(length_size_of_sample_num as usize + 1)?
+                    as u32,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(TfraBox {
+            version,
+            flags,
+            track_id,
+            length_size_of_traf_num,
+            length_size_of_trun_num,
+            length_size_of_sample_num,
+            entries,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for TfraBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        if self.version > 1 {
+            return Err(Error::InvalidData("version must be 0 or 1"));
+        }
+        if self.length_size_of_traf_num > 3
+            || self.length_size_of_trun_num > 3
+            || self.length_size_of_sample_num > 3
+        {
+            return Err(Error::InvalidData("tfra length sizes must be at most 3"));
+        }
+        write_box_header_ext(writer, self.version, self.flags)?;
+        writer.write_u32::<BigEndian>(self.track_id)?;
+        writer.write_u32::<BigEndian>(
+            (self.length_size_of_traf_num as u32) << 4
+                | (self.length_size_of_trun_num as u32) << 2
+                | self.length_size_of_sample_num as u32,
+        )?;
+        writer.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in self.entries.iter() {
+            if self.version == 1 {
+                writer.write_u64::<BigEndian>(entry.time)?;
+                writer.write_u64::<BigEndian>(entry.moof_offset)?;
+            } else {
+                writer.write_u32::<BigEndian>(entry.time as u32)?;
+                writer.write_u32::<BigEndian>(entry.moof_offset as u32)?;
+            }
+            writer.write_uint::<BigEndian>(
+                entry.traf_number as u64,
+                self.length_size_of_traf_num as usize + 1,
+            )?;
+            writer.write_uint::<BigEndian>(
+                entry.trun_number as u64,
+                self.length_size_of_trun_num as usize + 1,
+            )?;
+            writer.write_uint::<BigEndian>(
+                entry.sample_number as u64,
+                self.length_size_of_sample_num as usize + 1,
+            )?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tfra() {
+        let mut src_box = TfraBox {
+            version: 0,
+            flags: 0,
+            track_id: 1,
+            entries: vec![
+                TfraEntry {
+                    time: 0,
+                    moof_offset: 1024,
+                    traf_number: 1,
+                    trun_number: 1,
+                    sample_number: 1,
+                },
+                TfraEntry {
+                    time: 2000,
+                    moof_offset: 4096,
+                    traf_number: 2,
+                    trun_number: 1,
+                    sample_number: 300,
+                },
+            ],
+            ..TfraBox::default()
+        };
+        src_box.fit_length_sizes();
+        assert_eq!(src_box.length_size_of_traf_num, 0);
+        assert_eq!(src_box.length_size_of_sample_num, 1);
+
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TfraBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TfraBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_tfra64() {
+        let mut src_box = TfraBox {
+            version: 1,
+            flags: 0,
+            track_id: 2,
+            entries: vec![TfraEntry {
+                time: u32::MAX as u64 + 1,
+                moof_offset: u32::MAX as u64 + 2,
+                traf_number: 1,
+                trun_number: 0x10000,
+                sample_number: 0x1000000,
+            }],
+            ..TfraBox::default()
+        };
+        src_box.fit_length_sizes();
+        assert_eq!(src_box.length_size_of_trun_num, 2);
+        assert_eq!(src_box.length_size_of_sample_num, 3);
+
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TfraBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TfraBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
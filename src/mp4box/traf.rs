@@ -2,13 +2,26 @@ use serde::Serialize;
 use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
-use crate::mp4box::{tfdt::TfdtBox, tfhd::TfhdBox, trun::TrunBox};
+use crate::mp4box::{
+    saio::SaioBox, saiz::SaizBox, senc::SencBox, tfdt::TfdtBox, tfhd::TfhdBox, trun::TrunBox,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
 pub struct TrafBox {
     pub tfhd: TfhdBox,
     pub tfdt: Option<TfdtBox>,
-    pub trun: Option<TrunBox>,
+
+    #[serde(rename = "trun")]
+    pub truns: Vec<TrunBox>,
+
+    #[serde(rename = "saiz", skip_serializing_if = "Vec::is_empty")]
+    pub saizs: Vec<SaizBox>,
+
+    #[serde(rename = "saio", skip_serializing_if = "Vec::is_empty")]
+    pub saios: Vec<SaioBox>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub senc: Option<SencBox>,
 }
 
 impl TrafBox {
@@ -22,9 +35,18 @@ impl TrafBox {
         if let Some(ref tfdt) = self.tfdt {
             size += tfdt.box_size();
         }
-        if let Some(ref trun) = self.trun {
+        for trun in self.truns.iter() {
             size += trun.box_size();
         }
+        for saiz in self.saizs.iter() {
+            size += saiz.box_size();
+        }
+        for saio in self.saios.iter() {
+            size += saio.box_size();
+        }
+        if let Some(ref senc) = self.senc {
+            size += senc.box_size();
+        }
         size
     }
 }
@@ -43,7 +65,7 @@ impl Mp4Box for TrafBox {
     }
 
     fn summary(&self) -> Result<String> {
-        let s = String::new();
+        let s = format!("track_id={} truns={}", self.tfhd.track_id, self.truns.len());
         Ok(s)
     }
 }
@@ -54,7 +76,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
 
         let mut tfhd = None;
         let mut tfdt = None;
-        let mut trun = None;
+        let mut truns = Vec::new();
+        let mut saizs = Vec::new();
+        let mut saios = Vec::new();
+        let mut senc = None;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -76,7 +101,16 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
                     tfdt = Some(TfdtBox::read_box(reader, s)?);
                 }
                 BoxType::TrunBox => {
-                    trun = Some(TrunBox::read_box(reader, s)?);
+                    truns.push(TrunBox::read_box(reader, s)?);
+                }
+                BoxType::SaizBox => {
+                    saizs.push(SaizBox::read_box(reader, s)?);
+                }
+                BoxType::SaioBox => {
+                    saios.push(SaioBox::read_box(reader, s)?);
+                }
+                BoxType::SencBox => {
+                    senc = Some(SencBox::read_box(reader, s)?);
                 }
                 _ => {
                     // XXX warn!()
@@ -96,7 +130,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
         Ok(TrafBox {
             tfhd: tfhd.unwrap(),
             tfdt,
-            trun,
+            truns,
+            saizs,
+            saios,
+            senc,
         })
     }
 }
@@ -110,10 +147,144 @@ impl<W: Write> WriteBox<&mut W> for TrafBox {
         if let Some(ref tfdt) = self.tfdt {
             tfdt.write_box(writer)?;
         }
-        if let Some(ref trun) = self.trun {
+        for trun in self.truns.iter() {
             trun.write_box(writer)?;
         }
+        for saiz in self.saizs.iter() {
+            saiz.write_box(writer)?;
+        }
+        for saio in self.saios.iter() {
+            saio.write_box(writer)?;
+        }
+        if let Some(ref senc) = self.senc {
+            senc.write_box(writer)?;
+        }
 
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_traf_multiple_truns() {
+        let src_box = TrafBox {
+            tfhd: TfhdBox {
+                version: 0,
+                flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                track_id: 1,
+                ..TfhdBox::default()
+            },
+            tfdt: Some(TfdtBox {
+                version: 0,
+                flags: 0,
+                base_media_decode_time: 90000,
+            }),
+            truns: vec![
+                TrunBox {
+                    version: 0,
+                    flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_SIZE,
+                    sample_count: 2,
+                    data_offset: Some(200),
+                    sample_sizes: vec![100, 50],
+                    ..TrunBox::default()
+                },
+                TrunBox {
+                    version: 0,
+                    flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_SIZE,
+                    sample_count: 1,
+                    data_offset: Some(600),
+                    sample_sizes: vec![70],
+                    ..TrunBox::default()
+                },
+            ],
+            saizs: vec![],
+            saios: vec![],
+            senc: None,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TrafBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TrafBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_traf_encryption() {
+        let entries = vec![
+            SampleEncryption {
+                iv: vec![1; 8],
+                subsamples: vec![],
+            },
+            SampleEncryption {
+                iv: vec![2; 8],
+                subsamples: vec![],
+            },
+        ];
+        let src_box = TrafBox {
+            tfhd: TfhdBox {
+                track_id: 1,
+                ..TfhdBox::default()
+            },
+            tfdt: None,
+            truns: vec![TrunBox {
+                flags: TrunBox::FLAG_SAMPLE_SIZE,
+                sample_count: 2,
+                sample_sizes: vec![100, 50],
+                ..TrunBox::default()
+            }],
+            // Auxiliary information of another type is kept alongside.
+            saizs: vec![
+                SaizBox {
+                    default_sample_info_size: 8,
+                    sample_count: 2,
+                    ..SaizBox::default()
+                },
+                SaizBox {
+                    flags: SaizBox::FLAG_AUX_INFO_TYPE,
+                    aux_info_type: str::parse("abcd").unwrap(),
+                    aux_info_type_parameter: 1,
+                    default_sample_info_size: 4,
+                    sample_count: 2,
+                    ..SaizBox::default()
+                },
+            ],
+            saios: vec![
+                SaioBox {
+                    offsets: vec![100],
+                    ..SaioBox::default()
+                },
+                SaioBox {
+                    flags: SaioBox::FLAG_AUX_INFO_TYPE,
+                    aux_info_type: str::parse("abcd").unwrap(),
+                    aux_info_type_parameter: 1,
+                    offsets: vec![116],
+                    ..SaioBox::default()
+                },
+            ],
+            senc: Some(SencBox::new(&entries)),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TrafBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TrafBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.senc.unwrap().entries(8).unwrap(), entries);
+    }
+}
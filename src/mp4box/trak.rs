@@ -16,6 +16,9 @@ pub struct TrakBox {
     pub meta: Option<MetaBox>,
 
     pub mdia: MdiaBox,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl TrakBox {
@@ -29,7 +32,13 @@ impl TrakBox {
         if let Some(ref edts) = self.edts {
             size += edts.box_size();
         }
+        if let Some(ref meta) = self.meta {
+            size += meta.box_size();
+        }
         size += self.mdia.box_size();
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -61,6 +70,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
         let mut edts = None;
         let mut meta = None;
         let mut mdia = None;
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -88,10 +99,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
                     mdia = Some(MdiaBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
                 }
             }
+            children += 1;
 
             current = reader.stream_position()?;
         }
@@ -110,6 +121,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
             edts,
             meta,
             mdia: mdia.unwrap(),
+            unknown_boxes,
         })
     }
 }
@@ -119,12 +131,61 @@ impl<W: Write> WriteBox<&mut W> for TrakBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
-        self.tkhd.write_box(writer)?;
+        let mut children = ChildWriter::new(&self.unknown_boxes);
+        children.write(writer, &self.tkhd)?;
         if let Some(ref edts) = self.edts {
-            edts.write_box(writer)?;
+            children.write(writer, edts)?;
         }
-        self.mdia.write_box(writer)?;
-
+        if let Some(ref meta) = self.meta {
+            children.write(writer, meta)?;
+        }
+        children.write(writer, &self.mdia)?;
+        children.finish(writer)?;
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::{stco::StcoBox, BoxHeader};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_trak_unknown_box_order() {
+        let mut mdia = MdiaBox::default();
+        mdia.minf.stbl.stco = Some(StcoBox::default());
+        let src_box = TrakBox {
+            tkhd: TkhdBox::default(),
+            edts: Some(EdtsBox::default()),
+            meta: None,
+            mdia,
+            unknown_boxes: vec![
+                UnknownBox::new(BoxType::from(0x74726566), vec![0; 12]).with_position(1),
+                UnknownBox::new(BoxType::from(0x75647461), vec![]).with_position(3),
+                UnknownBox::new(BoxType::from(0x75756964), vec![1; 16]).with_position(5),
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TrakBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let mut children = Vec::new();
+        while reader.position() < header.size {
+            let child = BoxHeader::read(&mut reader).unwrap();
+            children.push(child.name.to_string());
+            skip_box(&mut reader, child.size).unwrap();
+        }
+        assert_eq!(children, ["tkhd", "tref", "edts", "udta", "mdia", "uuid"]);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = TrakBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -133,7 +133,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrunBox {
             sample_cts.reserve(sample_count as usize);
         }
 
-        for _ in 0..sample_count {
+        // Without per-sample fields there is nothing to read for each sample.
+        let sample_fields_count = if sample_size > 0 { sample_count } else { 0 };
+        for _ in 0..sample_fields_count {
             if TrunBox::FLAG_SAMPLE_DURATION & flags > 0 {
                 let duration = reader.read_u32::<BigEndian>()?;
                 sample_durations.push(duration);
@@ -185,10 +187,26 @@ impl<W: Write> WriteBox<&mut W> for TrunBox {
         if let Some(v) = self.first_sample_flags {
             writer.write_u32::<BigEndian>(v)?;
         }
-        if self.sample_count != self.sample_sizes.len() as u32 {
-            return Err(Error::InvalidData("sample count out of sync"));
+        for (flag, len) in [
+            (TrunBox::FLAG_SAMPLE_DURATION, self.sample_durations.len()),
+            (TrunBox::FLAG_SAMPLE_SIZE, self.sample_sizes.len()),
+            (TrunBox::FLAG_SAMPLE_FLAGS, self.sample_flags.len()),
+            (TrunBox::FLAG_SAMPLE_CTS, self.sample_cts.len()),
+        ] {
+            if flag & self.flags > 0 && self.sample_count != len as u32 {
+                return Err(Error::InvalidData("sample count out of sync"));
+            }
         }
-        for i in 0..self.sample_count as usize {
+        let sample_fields = TrunBox::FLAG_SAMPLE_DURATION
+            | TrunBox::FLAG_SAMPLE_SIZE
+            | TrunBox::FLAG_SAMPLE_FLAGS
+            | TrunBox::FLAG_SAMPLE_CTS;
+        let sample_fields_count = if self.flags & sample_fields > 0 {
+            self.sample_count
+        } else {
+            0
+        };
+        for i in 0..sample_fields_count as usize {
             if TrunBox::FLAG_SAMPLE_DURATION & self.flags > 0 {
                 writer.write_u32::<BigEndian>(self.sample_durations[i])?;
             }
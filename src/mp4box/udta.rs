@@ -9,6 +9,9 @@ use crate::mp4box::*;
 pub struct UdtaBox {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<MetaBox>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_boxes: Vec<UnknownBox>,
 }
 
 impl UdtaBox {
@@ -21,6 +24,9 @@ impl UdtaBox {
         if let Some(meta) = &self.meta {
             size += meta.box_size();
         }
+        for unknown in self.unknown_boxes.iter() {
+            size += unknown.box_size();
+        }
         size
     }
 }
@@ -48,6 +54,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
         let start = box_start(reader)?;
 
         let mut meta = None;
+        let mut unknown_boxes = Vec::new();
+        let mut children = 0;
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -66,17 +74,20 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
                     meta = Some(MetaBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(UnknownBox::read(reader, name, s)?.with_position(children));
                 }
             }
+            children += 1;
 
             current = reader.stream_position()?;
         }
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(UdtaBox { meta })
+        Ok(UdtaBox {
+            meta,
+            unknown_boxes,
+        })
     }
 }
 
@@ -85,9 +96,11 @@ impl<W: Write> WriteBox<&mut W> for UdtaBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
+        let mut children = ChildWriter::new(&self.unknown_boxes);
         if let Some(meta) = &self.meta {
-            meta.write_box(writer)?;
+            children.write(writer, meta)?;
         }
+        children.finish(writer)?;
         Ok(size)
     }
 }
@@ -100,7 +113,10 @@ mod tests {
 
     #[test]
     fn test_udta_empty() {
-        let src_box = UdtaBox { meta: None };
+        let src_box = UdtaBox {
+            meta: None,
+            unknown_boxes: vec![],
+        };
 
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -119,6 +135,11 @@ mod tests {
     fn test_udta() {
         let src_box = UdtaBox {
             meta: Some(MetaBox::default()),
+            unknown_boxes: vec![UnknownBox::new(
+                BoxType::from(0xa978797a), // ©xyz
+                b"\x00\x12\x15\xc7+59.3293+018.0686/".to_vec(),
+            )
+            .with_position(1)],
         };
 
         let mut buf = Vec::new();
@@ -0,0 +1,163 @@
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::iter::Peekable;
+use std::slice::Iter;
+
+use crate::mp4box::*;
+
+/// A box that is not parsed by this crate, kept as its raw payload so that it
+/// can be written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnknownBox {
+    pub box_type: BoxType,
+
+    #[serde(skip_serializing)]
+    pub payload: Vec<u8>,
+
+    /// Index of the box among the children of its parent, recorded when it
+    /// is read so that it is written back at the same place. Boxes without a
+    /// position are written after the known children.
+    #[serde(skip)]
+    pub position: Option<usize>,
+}
+
+impl UnknownBox {
+    pub fn new(box_type: BoxType, payload: Vec<u8>) -> Self {
+        UnknownBox {
+            box_type,
+            payload,
+            position: None,
+        }
+    }
+
+    /// Sets the index of the box among the children of its parent.
+    pub fn with_position(mut self, position: usize) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        self.box_type
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + self.payload.len() as u64
+    }
+
+    /// Read the payload of a box whose header has already been read.
+    pub fn read<R: Read>(reader: &mut R, box_type: BoxType, size: u64) -> Result<Self> {
+        if size < HEADER_SIZE {
+            return Err(Error::InvalidData("box size is smaller than its header"));
+        }
+        let mut payload = vec![0u8; (size - HEADER_SIZE) as usize];
+        reader.read_exact(&mut payload)?;
+
+        Ok(UnknownBox::new(box_type, payload))
+    }
+}
+
+/// Writes the children of a container box, putting each of its unknown boxes
+/// back ahead of the known child at its [UnknownBox::position].
+pub(crate) struct ChildWriter<'a> {
+    unknown_boxes: Peekable<Iter<'a, UnknownBox>>,
+    position: usize,
+}
+
+impl<'a> ChildWriter<'a> {
+    pub(crate) fn new(unknown_boxes: &'a [UnknownBox]) -> Self {
+        ChildWriter {
+            unknown_boxes: unknown_boxes.iter().peekable(),
+            position: 0,
+        }
+    }
+
+    /// Writes the next known child, preceded by the unknown boxes that were
+    /// read before it.
+    pub(crate) fn write<W, B>(&mut self, writer: &mut W, child: &B) -> Result<()>
+    where
+        W: Write,
+        B: for<'w> WriteBox<&'w mut W>,
+    {
+        self.next(writer)?;
+        child.write_box(writer)?;
+        Ok(())
+    }
+
+    /// Writes the unknown boxes that were read before the next known child,
+    /// which the caller writes itself.
+    pub(crate) fn next<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        let ChildWriter {
+            unknown_boxes,
+            position,
+        } = self;
+        while let Some(unknown) =
+            unknown_boxes.next_if(|unknown| unknown.position.is_some_and(|p| p <= *position))
+        {
+            unknown.write_box(writer)?;
+            *position += 1;
+        }
+        *position += 1;
+        Ok(())
+    }
+
+    /// Writes the unknown boxes that follow the last known child.
+    pub(crate) fn finish<W: Write>(self, writer: &mut W) -> Result<()> {
+        for unknown in self.unknown_boxes {
+            unknown.write_box(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Mp4Box for UnknownBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self).unwrap())
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("type={} len={}", self.box_type, self.payload.len());
+        Ok(s)
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for UnknownBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_all(&self.payload)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_unknown() {
+        let src_box = UnknownBox::new(BoxType::from(0x70617370), vec![0, 0, 0, 1, 0, 0, 0, 1]);
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::UnknownBox(0x70617370));
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = UnknownBox::read(&mut reader, header.name, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -1,6 +1,10 @@
+use std::cmp;
 use std::collections::HashMap;
-use std::io::{Read, Seek};
+use std::convert::TryFrom;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
 use std::time::Duration;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
 
 use crate::meta::MetaBox;
 use crate::*;
@@ -12,20 +16,78 @@ pub struct Mp4Reader<R> {
     pub moov: MoovBox,
     pub moofs: Vec<MoofBox>,
     pub emsgs: Vec<EmsgBox>,
+    pub sidxs: Vec<SidxBox>,
+    pub mfra: Option<MfraBox>,
+    pub unknown_boxes: Vec<UnknownBox>,
 
+    // Offsets of the ends of the sidx boxes, which their references start from.
+    sidx_offsets: Vec<u64>,
     tracks: HashMap<u32, Mp4Track>,
     size: u64,
 }
 
 impl<R: Read + Seek> Mp4Reader<R> {
-    pub fn read_header(mut reader: R, size: u64) -> Result<Self> {
+    pub fn read_header(reader: R, size: u64) -> Result<Self> {
+        Self::read_boxes(reader, size, false)
+    }
+
+    /// Reads the header of a file whose fragments are indexed by `sidx`
+    /// boxes, as in the DASH on-demand profile, or by a `mfra` box at the
+    /// end of the file, skipping the indexed fragments. Locate them with
+    /// [`Mp4Reader::segment_at_time`] or
+    /// [`Mp4Reader::random_access_point_before`] and read them with
+    /// [`Mp4Reader::read_segment`] or [`Mp4Reader::read_fragment_at`].
+    ///
+    /// Files without either index are read as by [`Mp4Reader::read_header`].
+    pub fn read_indexed_header(reader: R, size: u64) -> Result<Self> {
+        Self::read_boxes(reader, size, true)
+    }
+
+    fn read_boxes(mut reader: R, size: u64, skip_indexed: bool) -> Result<Self> {
+        let start = reader.stream_position()?;
+
+        let mut boxes = HeaderBoxes::default();
+        if skip_indexed {
+            boxes.mfra = read_mfra(&mut reader, start, size)?;
+        }
+
+        let mut current = start;
+        while current < size {
+            // Get box header.
+            let header = BoxHeader::read(&mut reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "file contains a box with a larger size than it",
+                ));
+            }
+
+            // Break if size zero BoxHeader, which can result in dead-loop.
+            if s == 0 {
+                break;
+            }
+
+            current = if boxes.skips(name, skip_indexed) {
+                current + s
+            } else {
+                boxes.read_box(&mut reader, name, s, current, skip_indexed)?
+            };
+            current = boxes.skip_indexed_media(current);
+            reader.seek(SeekFrom::Start(current))?;
+        }
+
+        boxes.into_reader(reader, start, current, size)
+    }
+
+    pub fn read_fragment_header<FR: Read + Seek>(
+        &self,
+        mut reader: FR,
+        size: u64,
+    ) -> Result<Mp4Reader<FR>> {
         let start = reader.stream_position()?;
 
-        let mut ftyp = None;
-        let mut moov = None;
         let mut moofs = Vec::new();
         let mut moof_offsets = Vec::new();
-        let mut emsgs = Vec::new();
 
         let mut current = start;
         while current < size {
@@ -45,28 +107,15 @@ impl<R: Read + Seek> Mp4Reader<R> {
 
             // Match and parse the atom boxes.
             match name {
-                BoxType::FtypBox => {
-                    ftyp = Some(FtypBox::read_box(&mut reader, s)?);
-                }
-                BoxType::FreeBox => {
-                    skip_box(&mut reader, s)?;
-                }
                 BoxType::MdatBox => {
                     skip_box(&mut reader, s)?;
                 }
-                BoxType::MoovBox => {
-                    moov = Some(MoovBox::read_box(&mut reader, s)?);
-                }
                 BoxType::MoofBox => {
                     let moof_offset = reader.stream_position()? - 8;
                     let moof = MoofBox::read_box(&mut reader, s)?;
                     moofs.push(moof);
                     moof_offsets.push(moof_offset);
                 }
-                BoxType::EmsgBox => {
-                    let emsg = EmsgBox::read_box(&mut reader, s)?;
-                    emsgs.push(emsg);
-                }
                 _ => {
                     // XXX warn!()
                     skip_box(&mut reader, s)?;
@@ -75,66 +124,341 @@ impl<R: Read + Seek> Mp4Reader<R> {
             current = reader.stream_position()?;
         }
 
-        if ftyp.is_none() {
-            return Err(Error::BoxNotFound(BoxType::FtypBox));
-        }
-        if moov.is_none() {
-            return Err(Error::BoxNotFound(BoxType::MoovBox));
+        if moofs.is_empty() {
+            return Err(Error::BoxNotFound(BoxType::MoofBox));
         }
 
+        let tracks = build_tracks(&self.moov, &moofs, &moof_offsets, size)?;
         let size = current - start;
-        let mut tracks = if let Some(ref moov) = moov {
-            if moov.traks.iter().any(|trak| trak.tkhd.track_id == 0) {
-                return Err(Error::InvalidData("illegal track id 0"));
+
+        Ok(Mp4Reader {
+            reader,
+            ftyp: self.ftyp.clone(),
+            moov: self.moov.clone(),
+            moofs,
+            emsgs: Vec::new(),
+            sidxs: Vec::new(),
+            mfra: None,
+            unknown_boxes: Vec::new(),
+            sidx_offsets: Vec::new(),
+            tracks,
+            size,
+        })
+    }
+
+    /// Reads the fragments of a segment located with
+    /// [`Mp4Reader::segment_at_time`]. Sample ids in the returned reader
+    /// start from 1 at the first sample of the segment.
+    pub fn read_segment(&mut self, segment: &SegmentInfo) -> Result<Mp4Reader<Cursor<Vec<u8>>>> {
+        let mut data = vec![0u8; segment.size as usize];
+        self.reader.seek(SeekFrom::Start(segment.offset))?;
+        self.reader.read_exact(&mut data)?;
+        self.read_fragment_header(Cursor::new(data), segment.size)
+    }
+
+    /// Reads the fragment whose `moof` box is at `moof_offset`, up to the
+    /// next `moof` box, e.g. the fragment of a
+    /// [`Mp4Reader::random_access_point_before`]. Sample ids in the returned
+    /// reader start from 1 at the first sample of the fragment.
+    pub fn read_fragment_at(&mut self, moof_offset: u64) -> Result<Mp4Reader<Cursor<Vec<u8>>>> {
+        self.reader.seek(SeekFrom::Start(moof_offset))?;
+        let header = BoxHeader::read(&mut self.reader)?;
+        if header.name != BoxType::MoofBox {
+            return Err(Error::BoxNotFound(BoxType::MoofBox));
+        }
+        let mut end = moof_offset + header.size;
+        while end + HEADER_SIZE <= self.size {
+            self.reader.seek(SeekFrom::Start(end))?;
+            let header = BoxHeader::read(&mut self.reader)?;
+            if header.size == 0 || matches!(header.name, BoxType::MoofBox | BoxType::MfraBox) {
+                break;
             }
-            moov.traks
-                .iter()
-                .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak)))
-                .collect()
+            end += header.size;
+        }
+
+        let mut data = vec![0u8; (end - moof_offset) as usize];
+        self.reader.seek(SeekFrom::Start(moof_offset))?;
+        self.reader.read_exact(&mut data)?;
+        let size = data.len() as u64;
+        self.read_fragment_header(Cursor::new(data), size)
+    }
+
+    pub fn read_sample(&mut self, track_id: u32, sample_id: u32) -> Result<Option<Mp4Sample>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            track.read_sample(&mut self.reader, sample_id)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// Returns the IV and subsample map of a sample in a protected track, or
+    /// `None` if the track or sample is not protected. Protected tracks have
+    /// an `encv` or `enca` sample entry, see [`Mp4Track::sinf`].
+    pub fn sample_encryption(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+    ) -> Result<Option<SampleEncryption>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            track.sample_encryption(&mut self.reader, sample_id)
         } else {
-            HashMap::new()
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// Reads a sample and decrypts it with the key of its key ID in `keys`,
+    /// see [`Mp4Track::decrypt_sample`]. Samples that are not protected are
+    /// returned as stored.
+    #[cfg(feature = "encryption")]
+    pub fn read_decrypted_sample(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+        keys: &HashMap<[u8; 16], [u8; 16]>,
+    ) -> Result<Option<Mp4Sample>> {
+        let track = self
+            .tracks
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+        let mut sample = match track.read_sample(&mut self.reader, sample_id)? {
+            Some(sample) => sample,
+            None => return Ok(None),
         };
+        if let Some(encryption) = track.sample_encryption(&mut self.reader, sample_id)? {
+            track.decrypt_sample(&mut sample, &encryption, keys)?;
+        }
+        Ok(Some(sample))
+    }
 
-        // Update tracks if any fragmented (moof) boxes are found.
-        if !moofs.is_empty() {
-            let mut default_sample_duration = 0;
-            if let Some(ref moov) = moov {
-                if let Some(ref mvex) = &moov.mvex {
-                    default_sample_duration = mvex.trex.default_sample_duration
-                }
+    /// Reads a sample into `buf`, reusing its allocation, and returns the
+    /// sample's timing and flags. `buf` is resized to the sample size.
+    pub fn read_sample_into(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+        buf: &mut Vec<u8>,
+    ) -> Result<Option<SampleInfo>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            track.read_sample_into(&mut self.reader, sample_id, buf)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// Returns an iterator over the samples of a track in decode order.
+    pub fn samples(&mut self, track_id: u32) -> Result<Mp4Samples<'_, R>> {
+        let sample_count = self.sample_count(track_id)?;
+        Ok(Mp4Samples {
+            reader: self,
+            track_id,
+            sample_id: 1,
+            sample_count,
+        })
+    }
+
+    /// Returns an iterator over the samples of all tracks, yielding the track
+    /// id with each sample. Samples left out of the sample index, see
+    /// [`Mp4Track::sample_index`], are skipped.
+    pub fn interleaved_samples(&mut self, order: SampleOrder) -> Mp4InterleavedSamples<'_, R> {
+        let mut cursors: Vec<(u32, u32)> = self.tracks.keys().map(|id| (*id, 1)).collect();
+        cursors.sort_unstable();
+        Mp4InterleavedSamples {
+            reader: self,
+            order,
+            cursors,
+        }
+    }
+}
+
+impl Mp4Reader<Cursor<Bytes>> {
+    /// Reads the header of a file held in memory. Samples taken with
+    /// [`Mp4Reader::get_sample`] share the buffer instead of copying it.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self> {
+        let size = bytes.len() as u64;
+        Self::read_header(Cursor::new(bytes), size)
+    }
+
+    /// Memory maps `file` and reads its header, see [`Mp4Reader::from_bytes`].
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified while the reader or any sample taken
+    /// from it is alive, see [`memmap2::Mmap`].
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_mmap(file: &std::fs::File) -> Result<Self> {
+        let mmap = memmap2::Mmap::map(file)?;
+        Self::from_bytes(Bytes::from_owner(mmap))
+    }
+
+    /// Returns a sample whose bytes are a slice of the underlying buffer.
+    pub fn get_sample(&self, track_id: u32, sample_id: u32) -> Result<Option<Mp4Sample>> {
+        let track = match self.tracks.get(&track_id) {
+            Some(track) => track,
+            None => return Err(Error::TrakNotFound(track_id)),
+        };
+        let info = match track.sample_location(sample_id)? {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+
+        let data = self.reader.get_ref();
+        let start = usize::try_from(info.offset).unwrap_or(usize::MAX);
+        let end = start.saturating_add(info.size as usize);
+        if end > data.len() {
+            return Err(Error::IoError(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(Some(track.sample_with_bytes(info, data.slice(start..end))))
+    }
+}
+
+/// Iterator over the samples of a track, see [`Mp4Reader::samples`].
+#[derive(Debug)]
+pub struct Mp4Samples<'a, R> {
+    reader: &'a mut Mp4Reader<R>,
+    track_id: u32,
+    sample_id: u32,
+    sample_count: u32,
+}
+
+impl<R: Read + Seek> Iterator for Mp4Samples<'_, R> {
+    type Item = Result<Mp4Sample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.sample_id > self.sample_count {
+            return None;
+        }
+        let sample_id = self.sample_id;
+        self.sample_id += 1;
+        match self.reader.read_sample(self.track_id, sample_id) {
+            Ok(Some(sample)) => Some(Ok(sample)),
+            Ok(None) => {
+                self.sample_id = self.sample_count + 1;
+                None
             }
+            Err(err) => {
+                self.sample_id = self.sample_count + 1;
+                Some(Err(err))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.sample_count + 1).saturating_sub(self.sample_id) as usize;
+        (0, Some(remaining))
+    }
+}
 
-            for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
-                for traf in moof.trafs.iter() {
-                    let track_id = traf.tfhd.track_id;
-                    if let Some(track) = tracks.get_mut(&track_id) {
-                        track.default_sample_duration = default_sample_duration;
-                        track.moof_offsets.push(moof_offset);
-                        track.trafs.push(traf.clone())
-                    } else {
-                        return Err(Error::TrakNotFound(track_id));
-                    }
+/// Order of the samples yielded by [`Mp4Reader::interleaved_samples`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleOrder {
+    /// Position of the sample data in the file, to read the file sequentially.
+    FileOffset,
+    /// Decode time, samples of the same time ordered by track id.
+    DecodeTime,
+}
+
+/// Iterator over the samples of all tracks, see
+/// [`Mp4Reader::interleaved_samples`].
+#[derive(Debug)]
+pub struct Mp4InterleavedSamples<'a, R> {
+    reader: &'a mut Mp4Reader<R>,
+    order: SampleOrder,
+    // The next sample id of each track, ordered by track id.
+    cursors: Vec<(u32, u32)>,
+}
+
+impl<R: Read + Seek> Iterator for Mp4InterleavedSamples<'_, R> {
+    type Item = Result<(u32, Mp4Sample)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut next: Option<(usize, u128)> = None;
+        for (i, (track_id, sample_id)) in self.cursors.iter().enumerate() {
+            let track = &self.reader.tracks[track_id];
+            let info = match track.sample_info(*sample_id) {
+                Some(info) => info,
+                None => continue,
+            };
+            let key = match self.order {
+                SampleOrder::FileOffset => info.offset as u128,
+                SampleOrder::DecodeTime => {
+                    info.start_time as u128 * 1_000_000_000 / cmp::max(track.timescale(), 1) as u128
                 }
+            };
+            match next {
+                Some((_, next_key)) if next_key <= key => {}
+                _ => next = Some((i, key)),
             }
         }
 
-        Ok(Mp4Reader {
-            reader,
-            ftyp: ftyp.unwrap(),
-            moov: moov.unwrap(),
-            moofs,
-            emsgs,
-            size,
-            tracks,
-        })
+        let (i, _) = next?;
+        let (track_id, sample_id) = self.cursors[i];
+        self.cursors[i].1 += 1;
+        match self.reader.read_sample(track_id, sample_id) {
+            Ok(Some(sample)) => Some(Ok((track_id, sample))),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
     }
+}
 
-    pub fn read_fragment_header<FR: Read + Seek>(
+#[cfg(feature = "async")]
+impl<R: AsyncRead + AsyncSeek + Unpin> Mp4Reader<R> {
+    /// Asynchronous version of [Mp4Reader::read_header].
+    ///
+    /// Only the header boxes are read into memory, media data is skipped.
+    pub async fn read_header_async(reader: R, size: u64) -> Result<Self> {
+        Self::read_boxes_async(reader, size, false).await
+    }
+
+    /// Asynchronous version of [Mp4Reader::read_indexed_header].
+    pub async fn read_indexed_header_async(reader: R, size: u64) -> Result<Self> {
+        Self::read_boxes_async(reader, size, true).await
+    }
+
+    async fn read_boxes_async(mut reader: R, size: u64, skip_indexed: bool) -> Result<Self> {
+        let start = reader.stream_position().await?;
+
+        let mut boxes = HeaderBoxes::default();
+        if skip_indexed {
+            boxes.mfra = read_mfra_async(&mut reader, start, size).await?;
+        }
+
+        let mut current = start;
+        while current < size {
+            // Get box header.
+            let header = BoxHeader::read_async(&mut reader).await?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "file contains a box with a larger size than it",
+                ));
+            }
+
+            // Break if size zero BoxHeader, which can result in dead-loop.
+            if s == 0 {
+                break;
+            }
+
+            current = if boxes.skips(name, skip_indexed) {
+                current + s
+            } else {
+                let mut data = read_box_data_async(&mut reader, s).await?;
+                boxes.read_box(&mut data, name, s, current, skip_indexed)?
+            };
+            current = boxes.skip_indexed_media(current);
+            reader.seek(SeekFrom::Start(current)).await?;
+        }
+
+        boxes.into_reader(reader, start, current, size)
+    }
+
+    /// Asynchronous version of [Mp4Reader::read_fragment_header].
+    pub async fn read_fragment_header_async<FR: AsyncRead + AsyncSeek + Unpin>(
         &self,
         mut reader: FR,
         size: u64,
     ) -> Result<Mp4Reader<FR>> {
-        let start = reader.stream_position()?;
+        let start = reader.stream_position().await?;
 
         let mut moofs = Vec::new();
         let mut moof_offsets = Vec::new();
@@ -142,7 +466,7 @@ impl<R: Read + Seek> Mp4Reader<R> {
         let mut current = start;
         while current < size {
             // Get box header.
-            let header = BoxHeader::read(&mut reader)?;
+            let header = BoxHeader::read_async(&mut reader).await?;
             let BoxHeader { name, size: s } = header;
             if s > size {
                 return Err(Error::InvalidData(
@@ -157,52 +481,26 @@ impl<R: Read + Seek> Mp4Reader<R> {
 
             // Match and parse the atom boxes.
             match name {
-                BoxType::MdatBox => {
-                    skip_box(&mut reader, s)?;
-                }
                 BoxType::MoofBox => {
-                    let moof_offset = reader.stream_position()? - 8;
-                    let moof = MoofBox::read_box(&mut reader, s)?;
+                    let moof_offset = reader.stream_position().await? - 8;
+                    let moof = read_box_async(&mut reader, s).await?;
                     moofs.push(moof);
                     moof_offsets.push(moof_offset);
                 }
                 _ => {
                     // XXX warn!()
-                    skip_box(&mut reader, s)?;
+                    skip_box_async(&mut reader, s).await?;
                 }
             }
-            current = reader.stream_position()?;
+            current = reader.stream_position().await?;
         }
 
         if moofs.is_empty() {
             return Err(Error::BoxNotFound(BoxType::MoofBox));
         }
 
+        let tracks = build_tracks(&self.moov, &moofs, &moof_offsets, size)?;
         let size = current - start;
-        let mut tracks: HashMap<u32, Mp4Track> = self
-            .moov
-            .traks
-            .iter()
-            .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak)))
-            .collect();
-
-        let mut default_sample_duration = 0;
-        if let Some(ref mvex) = &self.moov.mvex {
-            default_sample_duration = mvex.trex.default_sample_duration
-        }
-
-        for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
-            for traf in moof.trafs.iter() {
-                let track_id = traf.tfhd.track_id;
-                if let Some(track) = tracks.get_mut(&track_id) {
-                    track.default_sample_duration = default_sample_duration;
-                    track.moof_offsets.push(moof_offset);
-                    track.trafs.push(traf.clone())
-                } else {
-                    return Err(Error::TrakNotFound(track_id));
-                }
-            }
-        }
 
         Ok(Mp4Reader {
             reader,
@@ -210,11 +508,77 @@ impl<R: Read + Seek> Mp4Reader<R> {
             moov: self.moov.clone(),
             moofs,
             emsgs: Vec::new(),
+            sidxs: Vec::new(),
+            mfra: None,
+            unknown_boxes: Vec::new(),
+            sidx_offsets: Vec::new(),
             tracks,
             size,
         })
     }
 
+    /// Asynchronous version of [Mp4Reader::read_sample].
+    pub async fn read_sample_async(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+    ) -> Result<Option<Mp4Sample>> {
+        let track = match self.tracks.get(&track_id) {
+            Some(track) => track,
+            None => return Err(Error::TrakNotFound(track_id)),
+        };
+        let info = match track.sample_location(sample_id)? {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+
+        let mut buffer = vec![0x0u8; info.size as usize];
+        self.reader.seek(SeekFrom::Start(info.offset)).await?;
+        self.reader.read_exact(&mut buffer).await?;
+
+        Ok(Some(track.sample_with_bytes(info, Bytes::from(buffer))))
+    }
+}
+
+/// Read a whole box into memory and parse it with its [ReadBox] impl. The
+/// reader is expected to be positioned right after the box header.
+#[cfg(feature = "async")]
+async fn read_box_async<R, T>(reader: &mut R, size: u64) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: for<'a> ReadBox<&'a mut Cursor<Vec<u8>>>,
+{
+    let mut data = read_box_data_async(reader, size).await?;
+    T::read_box(&mut data, size)
+}
+
+/// Read a whole box into memory, positioned right after its header like a
+/// reader passed to [ReadBox::read_box].
+#[cfg(feature = "async")]
+async fn read_box_data_async<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    size: u64,
+) -> Result<Cursor<Vec<u8>>> {
+    if size < HEADER_SIZE {
+        return Err(Error::InvalidData("box size is smaller than its header"));
+    }
+    // Leave room for the header so offsets match the position in the box.
+    let mut buf = vec![0u8; size as usize];
+    reader.read_exact(&mut buf[HEADER_SIZE as usize..]).await?;
+
+    let mut cursor = Cursor::new(buf);
+    cursor.set_position(HEADER_SIZE);
+    Ok(cursor)
+}
+
+#[cfg(feature = "async")]
+async fn skip_box_async<S: AsyncSeek + Unpin>(seeker: &mut S, size: u64) -> Result<()> {
+    let start = seeker.stream_position().await? - HEADER_SIZE;
+    seeker.seek(SeekFrom::Start(start + size)).await?;
+    Ok(())
+}
+
+impl<R> Mp4Reader<R> {
     pub fn size(&self) -> u64 {
         self.size
     }
@@ -255,24 +619,115 @@ impl<R: Read + Seek> Mp4Reader<R> {
         }
     }
 
-    pub fn read_sample(&mut self, track_id: u32, sample_id: u32) -> Result<Option<Mp4Sample>> {
+    pub fn sample_offset(&mut self, track_id: u32, sample_id: u32) -> Result<u64> {
         if let Some(track) = self.tracks.get(&track_id) {
-            track.read_sample(&mut self.reader, sample_id)
+            track.sample_offset(sample_id)
         } else {
             Err(Error::TrakNotFound(track_id))
         }
     }
 
-    pub fn sample_offset(&mut self, track_id: u32, sample_id: u32) -> Result<u64> {
+    pub fn sample_at_time(&self, track_id: u32, time: Duration) -> Result<Option<u32>> {
         if let Some(track) = self.tracks.get(&track_id) {
-            track.sample_offset(sample_id)
+            track.sample_at_time(time)
         } else {
             Err(Error::TrakNotFound(track_id))
         }
     }
-}
 
-impl<R> Mp4Reader<R> {
+    pub fn sync_sample_before(&self, track_id: u32, time: Duration) -> Result<Option<u32>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            track.sync_sample_before(time)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// Returns the segments of a track indexed by `sidx` boxes, in file
+    /// order.
+    pub fn segments(&self, track_id: u32) -> Vec<SegmentInfo> {
+        let to_duration = |time: u64, timescale: u32| {
+            Duration::from_nanos((time as u128 * 1_000_000_000 / timescale as u128) as u64)
+        };
+
+        let mut segments = Vec::new();
+        for (sidx, sidx_end) in self.sidxs.iter().zip(self.sidx_offsets.iter()) {
+            if sidx.reference_id != track_id || sidx.timescale == 0 {
+                continue;
+            }
+            let mut offset = sidx_end + sidx.first_offset;
+            let mut time = sidx.earliest_presentation_time;
+            for reference in sidx.references.iter() {
+                // Media referenced through another sidx is listed by that one.
+                if !reference.reference_type {
+                    segments.push(SegmentInfo {
+                        offset,
+                        size: reference.referenced_size as u64,
+                        start_time: to_duration(time, sidx.timescale),
+                        duration: to_duration(reference.subsegment_duration as u64, sidx.timescale),
+                        starts_with_sap: reference.starts_with_sap,
+                    });
+                }
+                offset += reference.referenced_size as u64;
+                time += reference.subsegment_duration as u64;
+            }
+        }
+        segments.sort_by_key(|segment| segment.offset);
+        segments
+    }
+
+    /// Returns the first segment of a track that ends after `time`, using
+    /// the `sidx` boxes rather than the `moof` boxes of the file.
+    pub fn segment_at_time(&self, track_id: u32, time: Duration) -> Result<Option<SegmentInfo>> {
+        if !self.tracks.contains_key(&track_id) {
+            return Err(Error::TrakNotFound(track_id));
+        }
+        let segments = self.segments(track_id);
+        if segments.is_empty() {
+            return Err(Error::BoxNotFound(BoxType::SidxBox));
+        }
+        Ok(segments
+            .into_iter()
+            .find(|segment| time < segment.start_time + segment.duration))
+    }
+
+    /// Returns the last sync sample of a track listed in the `mfra` box at
+    /// or before `time`, where decoding has to start to present `time`.
+    pub fn random_access_point_before(
+        &self,
+        track_id: u32,
+        time: Duration,
+    ) -> Result<Option<RandomAccessPoint>> {
+        let track = self
+            .tracks
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+        let tfra = self
+            .mfra
+            .as_ref()
+            .and_then(|mfra| mfra.tfras.iter().find(|tfra| tfra.track_id == track_id))
+            .ok_or(Error::BoxInTrakNotFound(track_id, BoxType::TfraBox))?;
+
+        let timescale = match track.timescale() {
+            0 => return Err(Error::InvalidData("track timescale must not be 0")),
+            timescale => timescale as u128,
+        };
+        let to_duration =
+            |time: u64| Duration::from_nanos((time as u128 * 1_000_000_000 / timescale) as u64);
+        Ok(tfra
+            .entries
+            .iter()
+            .map(|entry| RandomAccessPoint {
+                time: to_duration(entry.time),
+                moof_offset: entry.moof_offset,
+                traf_number: entry.traf_number,
+                trun_number: entry.trun_number,
+                sample_number: entry.sample_number,
+            })
+            .filter(|point| point.time <= time)
+            .max_by_key(|point| point.time))
+    }
+
     pub fn metadata(&self) -> impl Metadata<'_> {
         self.moov.udta.as_ref().and_then(|udta| {
             udta.meta.as_ref().and_then(|meta| match meta {
@@ -282,3 +737,247 @@ impl<R> Mp4Reader<R> {
         })
     }
 }
+
+/// Top level boxes collected while reading a file header.
+#[derive(Default)]
+struct HeaderBoxes {
+    ftyp: Option<FtypBox>,
+    moov: Option<MoovBox>,
+    moofs: Vec<MoofBox>,
+    moof_offsets: Vec<u64>,
+    emsgs: Vec<EmsgBox>,
+    sidxs: Vec<SidxBox>,
+    sidx_offsets: Vec<u64>,
+    mfra: Option<MfraBox>,
+    unknown_boxes: Vec<UnknownBox>,
+    // Ranges of the media indexed by the sidx boxes, skipped when reached.
+    indexed_media: Vec<(u64, u64)>,
+}
+
+impl HeaderBoxes {
+    /// Returns the position to read on from, past any indexed media that
+    /// starts at or spans `offset`.
+    fn skip_indexed_media(&self, mut offset: u64) -> u64 {
+        while let Some(&(_, end)) = self
+            .indexed_media
+            .iter()
+            .find(|(start, end)| *start <= offset && offset < *end)
+        {
+            offset = end;
+        }
+        offset
+    }
+
+    /// Returns whether a box is passed over without reading its data.
+    fn skips(&self, name: BoxType, skip_indexed: bool) -> bool {
+        match name {
+            BoxType::FreeBox | BoxType::MdatBox => true,
+            BoxType::MoofBox => skip_indexed && (!self.sidxs.is_empty() || self.mfra.is_some()),
+            BoxType::MfraBox => self.mfra.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Parses a top level box from a reader positioned right after its
+    /// header. `offset` is the position of the box in the file, and the
+    /// position of the next box to read is returned.
+    fn read_box<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        name: BoxType,
+        size: u64,
+        offset: u64,
+        skip_indexed: bool,
+    ) -> Result<u64> {
+        let next = offset + size;
+        match name {
+            BoxType::FtypBox => {
+                self.ftyp = Some(FtypBox::read_box(reader, size)?);
+            }
+            BoxType::MoovBox => {
+                self.moov = Some(MoovBox::read_box(reader, size)?);
+            }
+            BoxType::SidxBox => {
+                let sidx = SidxBox::read_box(reader, size)?;
+                // References to other sidx boxes are followed by reading on.
+                // The indexed media is skipped once reached, as more sidx
+                // boxes, e.g. of other tracks, may come before it.
+                if skip_indexed && sidx.references.iter().all(|r| !r.reference_type) {
+                    let indexed_size: u64 = sidx
+                        .references
+                        .iter()
+                        .map(|r| r.referenced_size as u64)
+                        .sum();
+                    let media_start = next + sidx.first_offset;
+                    self.indexed_media
+                        .push((media_start, media_start + indexed_size));
+                }
+                self.sidxs.push(sidx);
+                self.sidx_offsets.push(offset + size);
+            }
+            BoxType::MfraBox => {
+                self.mfra = Some(MfraBox::read_box(reader, size)?);
+            }
+            BoxType::MoofBox => {
+                self.moofs.push(MoofBox::read_box(reader, size)?);
+                self.moof_offsets.push(offset);
+            }
+            BoxType::EmsgBox => {
+                self.emsgs.push(EmsgBox::read_box(reader, size)?);
+            }
+            _ => {
+                self.unknown_boxes
+                    .push(UnknownBox::read(reader, name, size)?);
+            }
+        }
+        Ok(next)
+    }
+
+    /// Builds the reader of a header spanning from `start` to `current`.
+    fn into_reader<R>(
+        self,
+        reader: R,
+        start: u64,
+        current: u64,
+        size: u64,
+    ) -> Result<Mp4Reader<R>> {
+        let ftyp = self.ftyp.ok_or(Error::BoxNotFound(BoxType::FtypBox))?;
+        let moov = self.moov.ok_or(Error::BoxNotFound(BoxType::MoovBox))?;
+        if moov.traks.iter().any(|trak| trak.tkhd.track_id == 0) {
+            return Err(Error::InvalidData("illegal track id 0"));
+        }
+
+        let tracks = build_tracks(&moov, &self.moofs, &self.moof_offsets, size)?;
+
+        Ok(Mp4Reader {
+            reader,
+            ftyp,
+            moov,
+            moofs: self.moofs,
+            emsgs: self.emsgs,
+            sidxs: self.sidxs,
+            mfra: self.mfra,
+            unknown_boxes: self.unknown_boxes,
+            sidx_offsets: self.sidx_offsets,
+            size: current - start,
+            tracks,
+        })
+    }
+}
+
+/// Reads the `mfra` box at the end of the file through its `mfro` box,
+/// leaving the reader at `start`.
+fn read_mfra<R: Read + Seek>(reader: &mut R, start: u64, size: u64) -> Result<Option<MfraBox>> {
+    let mfro_size = MfroBox::default().box_size();
+    let mut mfra = None;
+    if size >= start + mfro_size {
+        let mut mfro = vec![0u8; mfro_size as usize];
+        reader.seek(SeekFrom::Start(size - mfro_size))?;
+        reader.read_exact(&mut mfro)?;
+        if let Some(mfra_offset) = mfra_offset(&mfro, start, size)? {
+            let mut buf = vec![0u8; (size - mfra_offset) as usize];
+            reader.seek(SeekFrom::Start(mfra_offset))?;
+            reader.read_exact(&mut buf)?;
+            mfra = parse_mfra(&buf)?;
+        }
+    }
+    reader.seek(SeekFrom::Start(start))?;
+    Ok(mfra)
+}
+
+/// Asynchronous version of [read_mfra].
+#[cfg(feature = "async")]
+async fn read_mfra_async<R>(reader: &mut R, start: u64, size: u64) -> Result<Option<MfraBox>>
+where
+    R: AsyncRead + AsyncSeek + Unpin,
+{
+    let mfro_size = MfroBox::default().box_size();
+    let mut mfra = None;
+    if size >= start + mfro_size {
+        let mut mfro = vec![0u8; mfro_size as usize];
+        reader.seek(SeekFrom::Start(size - mfro_size)).await?;
+        reader.read_exact(&mut mfro).await?;
+        if let Some(mfra_offset) = mfra_offset(&mfro, start, size)? {
+            let mut buf = vec![0u8; (size - mfra_offset) as usize];
+            reader.seek(SeekFrom::Start(mfra_offset)).await?;
+            reader.read_exact(&mut buf).await?;
+            mfra = parse_mfra(&buf)?;
+        }
+    }
+    reader.seek(SeekFrom::Start(start)).await?;
+    Ok(mfra)
+}
+
+/// Returns the position of the `mfra` box that a file ending with the
+/// `mfro` box in `buf` points back to, if any.
+fn mfra_offset(buf: &[u8], start: u64, size: u64) -> Result<Option<u64>> {
+    let mut reader = Cursor::new(buf);
+    let header = BoxHeader::read(&mut reader)?;
+    if header.name != BoxType::MfroBox || header.size != buf.len() as u64 {
+        return Ok(None);
+    }
+    let mfro = MfroBox::read_box(&mut reader, header.size)?;
+    let mfra_size = mfro.parent_size as u64;
+    if mfra_size < HEADER_SIZE + header.size || size < start + mfra_size {
+        return Ok(None);
+    }
+    Ok(Some(size - mfra_size))
+}
+
+fn parse_mfra(buf: &[u8]) -> Result<Option<MfraBox>> {
+    let mut reader = Cursor::new(buf);
+    let header = BoxHeader::read(&mut reader)?;
+    if header.name != BoxType::MfraBox || header.size != buf.len() as u64 {
+        return Ok(None);
+    }
+    Ok(Some(MfraBox::read_box(&mut reader, header.size)?))
+}
+
+fn build_tracks(
+    moov: &MoovBox,
+    moofs: &[MoofBox],
+    moof_offsets: &[u64],
+    end: u64,
+) -> Result<HashMap<u32, Mp4Track>> {
+    let mut tracks: HashMap<u32, Mp4Track> = moov
+        .traks
+        .iter()
+        .map(|trak| {
+            (
+                trak.tkhd.track_id,
+                Mp4Track::from(trak, moov.mvhd.timescale),
+            )
+        })
+        .collect();
+
+    // Fragments fall back to the defaults of the trex of their track.
+    if let Some(ref mvex) = &moov.mvex {
+        for (track_id, track) in tracks.iter_mut() {
+            if let Some(trex) = mvex.trex(*track_id) {
+                track.default_sample_description_index = trex.default_sample_description_index;
+                track.default_sample_duration = trex.default_sample_duration;
+                track.default_sample_size = trex.default_sample_size;
+                track.default_sample_flags = trex.default_sample_flags;
+            }
+        }
+    }
+
+    // Update tracks if any fragmented (moof) boxes are found.
+    for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
+        for traf in moof.trafs.iter() {
+            let track_id = traf.tfhd.track_id;
+            if let Some(track) = tracks.get_mut(&track_id) {
+                track.moof_offsets.push(*moof_offset);
+                track.trafs.push(traf.clone())
+            } else {
+                return Err(Error::TrakNotFound(track_id));
+            }
+        }
+    }
+
+    for track in tracks.values_mut() {
+        track.build_sample_index(end);
+    }
+
+    Ok(tracks)
+}
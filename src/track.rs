@@ -1,16 +1,37 @@
 use bytes::BytesMut;
 use std::cmp;
-use std::convert::TryFrom;
+use std::collections::VecDeque;
+use std::convert::{TryFrom, TryInto};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::time::Duration;
 
+#[cfg(feature = "encryption")]
+use crate::encryption::{self, SampleEncryptor};
+use crate::mp4box::senc::{read_sample_encryption, sample_aux_info, scheme_aux_info};
+use crate::mp4box::tfdt::TfdtBox;
+use crate::mp4box::tfhd::TfhdBox;
 use crate::mp4box::traf::TrafBox;
 use crate::mp4box::trak::TrakBox;
+use crate::mp4box::trex::TrexBox;
 use crate::mp4box::trun::TrunBox;
 use crate::mp4box::{
-    avc1::Avc1Box, co64::Co64Box, ctts::CttsBox, ctts::CttsEntry, hev1::Hev1Box, mp4a::Mp4aBox,
-    smhd::SmhdBox, stco::StcoBox, stsc::StscEntry, stss::StssBox, stts::SttsEntry, tx3g::Tx3gBox,
-    vmhd::VmhdBox, vp09::Vp09Box,
+    avc1::Avc1Box,
+    co64::Co64Box,
+    ctts::CttsBox,
+    ctts::CttsEntry,
+    edts::EdtsBox,
+    elst::{ElstBox, ElstEntry},
+    hev1::{Hev1Box, HvcCBox},
+    mp4a::Mp4aBox,
+    opus::OpusBox,
+    smhd::SmhdBox,
+    stco::StcoBox,
+    stsc::StscEntry,
+    stss::StssBox,
+    stts::SttsEntry,
+    tx3g::Tx3gBox,
+    vmhd::VmhdBox,
+    vp09::Vp09Box,
 };
 use crate::*;
 
@@ -20,6 +41,41 @@ pub struct TrackConfig {
     pub timescale: u32,
     pub language: String,
     pub media_conf: MediaConfig,
+
+    /// Edit list of the track, empty for none. Segment durations are in the
+    /// movie timescale and media times in the track timescale. A zero
+    /// duration on the last entry extends it to the end of the media.
+    pub edit_list: Vec<ElstEntry>,
+
+    /// How [Mp4Writer] groups samples into chunks. `None` uses the
+    /// interleave duration of [Mp4Config] if set, otherwise the default
+    /// strategy. Ignored by [Mp4FragmentedWriter].
+    pub chunk_strategy: Option<ChunkStrategy>,
+
+    /// Encrypt the samples of the track with common encryption. The sample
+    /// entry is written as `encv` or `enca`, and the IVs and subsample maps
+    /// as auxiliary information. Requires the `encryption` feature, adding
+    /// the track fails without it.
+    pub encryption: Option<EncryptionConfig>,
+}
+
+/// Decides when a chunk of samples is complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkStrategy {
+    /// A fixed number of samples per chunk.
+    SampleCount(u32),
+    /// At least this duration of samples per chunk.
+    Duration(Duration),
+    /// At least this many bytes of sample data per chunk.
+    ByteSize(u32),
+    /// Every sample in a chunk of its own.
+    SingleSample,
+}
+
+impl Default for ChunkStrategy {
+    fn default() -> Self {
+        ChunkStrategy::Duration(Duration::from_secs(1))
+    }
 }
 
 impl From<MediaConfig> for TrackConfig {
@@ -28,8 +84,10 @@ impl From<MediaConfig> for TrackConfig {
             MediaConfig::AvcConfig(avc_conf) => Self::from(avc_conf),
             MediaConfig::HevcConfig(hevc_conf) => Self::from(hevc_conf),
             MediaConfig::AacConfig(aac_conf) => Self::from(aac_conf),
+            MediaConfig::OpusConfig(opus_conf) => Self::from(opus_conf),
             MediaConfig::TtxtConfig(ttxt_conf) => Self::from(ttxt_conf),
             MediaConfig::Vp9Config(vp9_config) => Self::from(vp9_config),
+            MediaConfig::Av1Config(av1_config) => Self::from(av1_config),
         }
     }
 }
@@ -41,6 +99,9 @@ impl From<AvcConfig> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::AvcConfig(avc_conf),
+            edit_list: Vec::new(),
+            chunk_strategy: None,
+            encryption: None,
         }
     }
 }
@@ -52,6 +113,9 @@ impl From<HevcConfig> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::HevcConfig(hevc_conf),
+            edit_list: Vec::new(),
+            chunk_strategy: None,
+            encryption: None,
         }
     }
 }
@@ -63,6 +127,23 @@ impl From<AacConfig> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::AacConfig(aac_conf),
+            edit_list: Vec::new(),
+            chunk_strategy: None,
+            encryption: None,
+        }
+    }
+}
+
+impl From<OpusConfig> for TrackConfig {
+    fn from(opus_conf: OpusConfig) -> Self {
+        Self {
+            track_type: TrackType::Audio,
+            timescale: 48000,
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::OpusConfig(opus_conf),
+            edit_list: Vec::new(),
+            chunk_strategy: None,
+            encryption: None,
         }
     }
 }
@@ -74,6 +155,9 @@ impl From<TtxtConfig> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::TtxtConfig(txtt_conf),
+            edit_list: Vec::new(),
+            chunk_strategy: None,
+            encryption: None,
         }
     }
 }
@@ -85,31 +169,93 @@ impl From<Vp9Config> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::Vp9Config(vp9_conf),
+            edit_list: Vec::new(),
+            chunk_strategy: None,
+            encryption: None,
+        }
+    }
+}
+
+impl From<Av1Config> for TrackConfig {
+    fn from(av1_conf: Av1Config) -> Self {
+        Self {
+            track_type: TrackType::Video,
+            timescale: 1000,               // XXX
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::Av1Config(av1_conf),
+            edit_list: Vec::new(),
+            chunk_strategy: None,
+            encryption: None,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct Mp4Track {
-    pub trak: TrakBox,
-    pub trafs: Vec<TrafBox>,
-    pub moof_offsets: Vec<u64>,
+    // The sample index is built from these boxes when the track is read,
+    // so they are only exposed read-only.
+    pub(crate) trak: TrakBox,
+    pub(crate) trafs: Vec<TrafBox>,
+    pub(crate) moof_offsets: Vec<u64>,
+
+    /// Timescale of the movie, which edit list segment durations are in.
+    pub(crate) movie_timescale: u32,
 
     // Fragmented Tracks Defaults.
-    pub default_sample_duration: u32,
+    pub(crate) default_sample_description_index: u32,
+    pub(crate) default_sample_duration: u32,
+    pub(crate) default_sample_size: u32,
+    pub(crate) default_sample_flags: u32,
+
+    // Built from the boxes above by `build_sample_index`.
+    sample_index: Vec<SampleInfo>,
+    sync_samples: Vec<u32>,
+    traf_first_samples: Vec<u32>,
+    senc_entries: Vec<Vec<SampleEncryption>>,
+    composition_offset_range: (i64, i64),
 }
 
 impl Mp4Track {
-    pub(crate) fn from(trak: &TrakBox) -> Self {
+    pub(crate) fn from(trak: &TrakBox, movie_timescale: u32) -> Self {
         let trak = trak.clone();
         Self {
             trak,
             trafs: Vec::new(),
             moof_offsets: Vec::new(),
+            movie_timescale,
+            default_sample_description_index: 1,
             default_sample_duration: 0,
+            default_sample_size: 0,
+            default_sample_flags: 0,
+            sample_index: Vec::new(),
+            sync_samples: Vec::new(),
+            traf_first_samples: Vec::new(),
+            senc_entries: Vec::new(),
+            composition_offset_range: (0, 0),
         }
     }
 
+    /// Returns the boxes of the track, as read.
+    pub fn trak(&self) -> &TrakBox {
+        &self.trak
+    }
+
+    /// Returns the fragments of the track, in the order of the `moof` boxes
+    /// they are in.
+    pub fn trafs(&self) -> &[TrafBox] {
+        &self.trafs
+    }
+
+    /// Returns the offsets of the `moof` boxes of the [Mp4Track::trafs].
+    pub fn moof_offsets(&self) -> &[u64] {
+        &self.moof_offsets
+    }
+
+    /// Returns the default sample duration from the `trex` box of the track.
+    pub fn default_sample_duration(&self) -> u32 {
+        self.default_sample_duration
+    }
+
     pub fn track_id(&self) -> u32 {
         self.trak.tkhd.track_id
     }
@@ -119,39 +265,45 @@ impl Mp4Track {
     }
 
     pub fn media_type(&self) -> Result<MediaType> {
-        if self.trak.mdia.minf.stbl.stsd.avc1.is_some() {
-            Ok(MediaType::H264)
-        } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some() {
-            Ok(MediaType::H265)
-        } else if self.trak.mdia.minf.stbl.stsd.vp09.is_some() {
-            Ok(MediaType::VP9)
-        } else if self.trak.mdia.minf.stbl.stsd.mp4a.is_some() {
-            Ok(MediaType::AAC)
-        } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some() {
-            Ok(MediaType::TTXT)
-        } else {
-            Err(Error::InvalidData("unsupported media type"))
+        let entry = self.trak.mdia.minf.stbl.stsd.entries.first();
+        match entry.map(SampleEntry::unprotected) {
+            Some(SampleEntry::Avc1(_)) => Ok(MediaType::H264),
+            Some(SampleEntry::Hev1(_)) => Ok(MediaType::H265),
+            Some(SampleEntry::Vp09(_)) => Ok(MediaType::VP9),
+            Some(SampleEntry::Av01(_)) => Ok(MediaType::AV1),
+            Some(SampleEntry::Mp4a(_)) => Ok(MediaType::AAC),
+            Some(SampleEntry::Opus(_)) => Ok(MediaType::OPUS),
+            Some(SampleEntry::Tx3g(_)) => Ok(MediaType::TTXT),
+            _ => Err(Error::InvalidData("unsupported media type")),
         }
     }
 
     pub fn box_type(&self) -> Result<FourCC> {
-        if self.trak.mdia.minf.stbl.stsd.avc1.is_some() {
-            Ok(FourCC::from(BoxType::Avc1Box))
-        } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some() {
-            Ok(FourCC::from(BoxType::Hev1Box))
-        } else if self.trak.mdia.minf.stbl.stsd.vp09.is_some() {
-            Ok(FourCC::from(BoxType::Vp09Box))
-        } else if self.trak.mdia.minf.stbl.stsd.mp4a.is_some() {
-            Ok(FourCC::from(BoxType::Mp4aBox))
-        } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some() {
-            Ok(FourCC::from(BoxType::Tx3gBox))
-        } else {
-            Err(Error::InvalidData("unsupported sample entry box"))
+        match self.trak.mdia.minf.stbl.stsd.entries.first() {
+            Some(SampleEntry::Unknown(_)) | None => {
+                Err(Error::InvalidData("unsupported sample entry box"))
+            }
+            Some(entry) => Ok(FourCC::from(entry.box_type())),
         }
     }
 
+    /// Returns the sample descriptions of the track, see
+    /// [`Mp4Sample::sample_description_index`].
+    pub fn sample_entries(&self) -> &[SampleEntry] {
+        &self.trak.mdia.minf.stbl.stsd.entries
+    }
+
+    /// Returns the protection scheme information of the first protected
+    /// sample description, or `None` if the track is not encrypted.
+    pub fn sinf(&self) -> Option<&SinfBox> {
+        self.sample_entries().iter().find_map(|entry| match entry {
+            SampleEntry::Encrypted(encrypted) => Some(&encrypted.sinf),
+            _ => None,
+        })
+    }
+
     pub fn width(&self) -> u16 {
-        if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
+        if let Some(avc1) = self.trak.mdia.minf.stbl.stsd.avc1() {
             avc1.width
         } else {
             self.trak.tkhd.width.value()
@@ -159,7 +311,7 @@ impl Mp4Track {
     }
 
     pub fn height(&self) -> u16 {
-        if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
+        if let Some(avc1) = self.trak.mdia.minf.stbl.stsd.avc1() {
             avc1.height
         } else {
             self.trak.tkhd.height.value()
@@ -176,7 +328,7 @@ impl Mp4Track {
     }
 
     pub fn sample_freq_index(&self) -> Result<SampleFreqIndex> {
-        if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
+        if let Some(mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a() {
             if let Some(ref esds) = mp4a.esds {
                 SampleFreqIndex::try_from(esds.es_desc.dec_config.dec_specific.freq_index)
             } else {
@@ -188,7 +340,7 @@ impl Mp4Track {
     }
 
     pub fn channel_config(&self) -> Result<ChannelConfig> {
-        if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
+        if let Some(mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a() {
             if let Some(ref esds) = mp4a.esds {
                 ChannelConfig::try_from(esds.es_desc.dec_config.dec_specific.chan_conf)
             } else {
@@ -213,8 +365,380 @@ impl Mp4Track {
         )
     }
 
+    /// Returns the edit list of the track, empty if it has none.
+    pub fn edit_list(&self) -> &[ElstEntry] {
+        match self.trak.edts {
+            Some(EdtsBox {
+                elst: Some(ref elst),
+            }) => &elst.entries,
+            _ => &[],
+        }
+    }
+
+    /// Returns the presentation timeline of the track. Without an edit list
+    /// the media is presented as is.
+    pub fn edit_segments(&self) -> Result<Vec<EditSegment>> {
+        let edit_list = self.edit_list();
+        if edit_list.is_empty() {
+            return Ok(vec![EditSegment {
+                presentation_time: 0,
+                duration: self.trak.mdia.mdhd.duration,
+                media_time: Some(0),
+                media_rate: 1 << 16,
+            }]);
+        }
+
+        // Segment durations are in the movie timescale.
+        let timescale = self.timescale() as u128;
+        let movie_timescale = match self.movie_timescale {
+            0 => timescale,
+            movie_timescale => movie_timescale as u128,
+        };
+        if movie_timescale == 0 {
+            return Err(Error::InvalidData("track timescale must not be 0"));
+        }
+        let mut presentation_time = 0u64;
+        let mut segments = Vec::with_capacity(edit_list.len());
+        for entry in edit_list {
+            let duration =
+                u64::try_from(entry.segment_duration as u128 * timescale / movie_timescale)
+                    .map_err(|_| Error::InvalidData("edit list segment duration overflows"))?;
+            segments.push(EditSegment {
+                presentation_time,
+                duration,
+                media_time: if entry.is_empty_edit() {
+                    None
+                } else {
+                    Some(entry.media_time as u64)
+                },
+                media_rate: entry.rate(),
+            });
+            presentation_time = presentation_time
+                .checked_add(duration)
+                .ok_or(Error::InvalidData("edit list duration overflows"))?;
+        }
+        Ok(segments)
+    }
+
+    /// Maps a media (composition) time to the time it is presented at, both in
+    /// the media timescale. Returns `None` if the edit list skips it.
+    pub fn presentation_time(&self, media_time: u64) -> Result<Option<u64>> {
+        let segments = self.edit_segments()?;
+        let last = segments.len() - 1;
+        for (i, segment) in segments.iter().enumerate() {
+            let start = match segment.media_time {
+                Some(start) if media_time >= start => start,
+                _ => continue,
+            };
+            if segment.media_rate == 0 {
+                if media_time == start {
+                    return Ok(Some(segment.presentation_time));
+                }
+                continue;
+            }
+            let offset = (((media_time - start) as u128) << 16) / segment.media_rate as u128;
+            if offset < segment.duration as u128 || (i == last && segment.duration == 0) {
+                let time = u64::try_from(segment.presentation_time as u128 + offset)
+                    .map_err(|_| Error::InvalidData("presentation time overflows"))?;
+                return Ok(Some(time));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Maps a presentation time to the media time presented at it, both in the
+    /// media timescale. Returns `None` during empty edits and past the end.
+    pub fn media_time(&self, presentation_time: u64) -> Result<Option<u64>> {
+        let segments = self.edit_segments()?;
+        let last = segments.len() - 1;
+        for (i, segment) in segments.iter().enumerate() {
+            if presentation_time < segment.presentation_time {
+                break;
+            }
+            let offset = presentation_time - segment.presentation_time;
+            if offset < segment.duration || (i == last && segment.duration == 0) {
+                let start = match segment.media_time {
+                    Some(start) => start,
+                    None => return Ok(None),
+                };
+                let time = start as u128 + ((offset as u128 * segment.media_rate as u128) >> 16);
+                let time =
+                    u64::try_from(time).map_err(|_| Error::InvalidData("media time overflows"))?;
+                return Ok(Some(time));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the duration of the presentation timeline, which is the
+    /// media duration unless the track has an edit list.
+    pub fn presentation_duration(&self) -> Result<Duration> {
+        let edit_list = self.edit_list();
+        if edit_list.is_empty() || self.movie_timescale == 0 {
+            return Ok(self.duration());
+        }
+        let duration = edit_list
+            .iter()
+            .try_fold(0u64, |duration, entry| {
+                duration.checked_add(entry.segment_duration)
+            })
+            .ok_or(Error::InvalidData("edit list duration overflows"))?;
+        let micros = duration as u128 * 1_000_000 / self.movie_timescale as u128;
+        let micros = u64::try_from(micros)
+            .map_err(|_| Error::InvalidData("edit list duration overflows"))?;
+        Ok(Duration::from_micros(micros))
+    }
+
+    /// Returns the samples of the track in decode order, `sample_id - 1`
+    /// indexing the sample. Samples which the sample tables or fragments fail
+    /// to describe, and all samples after them, are left out.
+    pub fn sample_index(&self) -> &[SampleInfo] {
+        &self.sample_index
+    }
+
+    pub fn sample_info(&self, sample_id: u32) -> Option<&SampleInfo> {
+        self.sample_index.get(sample_id.checked_sub(1)? as usize)
+    }
+
+    /// Builds the sample index, leaving out samples which end past `end`.
+    pub(crate) fn build_sample_index(&mut self, end: u64) {
+        self.sample_index = if !self.trafs.is_empty() {
+            self.index_trun_samples(end)
+        } else {
+            self.index_stbl_samples(end)
+        };
+
+        self.sync_samples = Vec::new();
+        let mut min_offset = 0;
+        let mut max_offset = 0;
+        for (i, info) in self.sample_index.iter().enumerate() {
+            if info.is_sync {
+                self.sync_samples.push(i as u32 + 1);
+            }
+            min_offset = cmp::min(min_offset, info.rendering_offset as i64);
+            max_offset = cmp::max(max_offset, info.rendering_offset as i64);
+        }
+        self.composition_offset_range = (min_offset, max_offset);
+
+        self.traf_first_samples = Vec::with_capacity(self.trafs.len());
+        let mut first_sample = 1u32;
+        for traf in self.trafs.iter() {
+            self.traf_first_samples.push(first_sample);
+            for trun in traf.truns.iter() {
+                first_sample = first_sample.saturating_add(trun.sample_count);
+            }
+        }
+
+        // senc entries vary in size, so they are decoded once up front.
+        // Entries of a senc that fails to decode are left out.
+        self.senc_entries = self
+            .trafs
+            .iter()
+            .map(|traf| {
+                let sample_description_index = traf
+                    .tfhd
+                    .sample_description_index
+                    .unwrap_or(self.default_sample_description_index);
+                match (&traf.senc, self.tenc(sample_description_index)) {
+                    (Some(senc), Some(tenc)) => senc
+                        .entries(tenc.default_per_sample_iv_size)
+                        .unwrap_or_default(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+
+    fn index_stbl_samples(&self, end: u64) -> Vec<SampleInfo> {
+        let stbl = &self.trak.mdia.minf.stbl;
+        let sample_count = stbl.stsz.sample_count as usize;
+        let chunk_count = match (&stbl.stco, &stbl.co64) {
+            (Some(stco), _) => stco.entries.len() as u32,
+            (None, Some(co64)) => co64.entries.len() as u32,
+            (None, None) => return Vec::new(),
+        };
+        let mut durations = stbl.stts.entries.iter().flat_map(|entry| {
+            std::iter::repeat(entry.sample_delta).take(entry.sample_count as usize)
+        });
+        let mut rendering_offsets = stbl.ctts.iter().flat_map(|ctts| {
+            ctts.entries.iter().flat_map(|entry| {
+                std::iter::repeat(entry.sample_offset).take(entry.sample_count as usize)
+            })
+        });
+        let mut sync_samples = stbl
+            .stss
+            .as_ref()
+            .map(|stss| stss.entries.iter().peekable());
+
+        let mut samples = Vec::with_capacity(cmp::min(sample_count, stbl.stsz.sample_sizes.len()));
+        let mut start_time = 0u64;
+        for (i, entry) in stbl.stsc.entries.iter().enumerate() {
+            let last_chunk = match stbl.stsc.entries.get(i + 1) {
+                Some(next) => cmp::min(next.first_chunk.saturating_sub(1), chunk_count),
+                None => chunk_count,
+            };
+            for chunk_id in entry.first_chunk..=last_chunk {
+                let mut offset = match self.chunk_offset(chunk_id) {
+                    Ok(offset) => offset,
+                    Err(_) => return samples,
+                };
+                for _ in 0..entry.samples_per_chunk {
+                    if samples.len() == sample_count {
+                        return samples;
+                    }
+                    let sample_size = if stbl.stsz.sample_size > 0 {
+                        stbl.stsz.sample_size
+                    } else {
+                        match stbl.stsz.sample_sizes.get(samples.len()) {
+                            Some(sample_size) => *sample_size,
+                            None => return samples,
+                        }
+                    };
+                    let duration = match durations.next() {
+                        Some(duration) => duration,
+                        None => return samples,
+                    };
+                    let sample_end = match offset.checked_add(sample_size as u64) {
+                        Some(sample_end) if sample_end <= end => sample_end,
+                        _ => return samples,
+                    };
+                    let sample_id = samples.len() as u32 + 1;
+                    let is_sync = match sync_samples {
+                        Some(ref mut sync_samples) => {
+                            while sync_samples.next_if(|id| **id < sample_id).is_some() {}
+                            sync_samples.next_if_eq(&&sample_id).is_some()
+                        }
+                        None => true,
+                    };
+
+                    samples.push(SampleInfo {
+                        offset,
+                        size: sample_size,
+                        start_time,
+                        duration,
+                        rendering_offset: rendering_offsets.next().unwrap_or(0),
+                        is_sync,
+                        sample_description_index: entry.sample_description_index,
+                        sample_flags: None,
+                    });
+                    offset = sample_end;
+                    start_time += duration as u64;
+                }
+            }
+        }
+        samples
+    }
+
+    fn index_trun_samples(&self, end: u64) -> Vec<SampleInfo> {
+        let mut samples = Vec::new();
+        let mut start_time = 0u64;
+        for (traf, moof_offset) in self.trafs.iter().zip(self.moof_offsets.iter()) {
+            if let Some(tfdt) = &traf.tfdt {
+                start_time = tfdt.base_media_decode_time;
+            }
+            let base_offset = traf.tfhd.base_data_offset.unwrap_or(*moof_offset);
+            let sample_description_index = traf
+                .tfhd
+                .sample_description_index
+                .unwrap_or(self.default_sample_description_index);
+
+            // A run without a data offset continues where the previous run ended.
+            let mut offset = base_offset;
+            for trun in traf.truns.iter() {
+                if let Some(data_offset) = trun.data_offset {
+                    offset = match base_offset.checked_add_signed(data_offset as i64) {
+                        Some(offset) => offset,
+                        None => return samples,
+                    };
+                }
+                for k in 0..trun.sample_count as usize {
+                    let sample_size = match self.trun_sample_size(traf, trun, k) {
+                        Some(sample_size) => sample_size,
+                        None => return samples,
+                    };
+                    let sample_end = match offset.checked_add(sample_size as u64) {
+                        Some(sample_end) if sample_end <= end => sample_end,
+                        _ => return samples,
+                    };
+                    let duration = self.trun_sample_duration(traf, trun, k);
+                    let sample_flags = self.trun_sample_flags(traf, trun, k);
+
+                    samples.push(SampleInfo {
+                        offset,
+                        size: sample_size,
+                        start_time,
+                        duration,
+                        rendering_offset: trun.sample_cts.get(k).map_or(0, |cts| *cts as i32),
+                        is_sync: sample_flags.is_sync(),
+                        sample_description_index,
+                        sample_flags: Some(sample_flags),
+                    });
+                    offset = sample_end;
+                    start_time += duration as u64;
+                }
+            }
+        }
+        samples
+    }
+
+    /// Returns the sample presented at `time` on the presentation timeline,
+    /// taking the edit list and composition offsets into account. Returns
+    /// `None` during empty edits and past the end of the track.
+    pub fn sample_at_time(&self, time: Duration) -> Result<Option<u32>> {
+        let ticks = time.as_nanos() * self.timescale() as u128 / 1_000_000_000;
+        let ticks = u64::try_from(ticks).map_err(|_| Error::InvalidData("time overflows"))?;
+        let time = match self.media_time(ticks)? {
+            Some(time) => time,
+            None => return Ok(None),
+        };
+
+        let (min_offset, max_offset) = self.composition_offset_range;
+        if min_offset == 0 && max_offset == 0 {
+            return Ok(self.sample_at_decode_time(time));
+        }
+
+        // Only samples decoded within the range of composition offsets before
+        // `time` can be presented at it. Start from the last one decoded at
+        // or before that range, even if none is decoded right at its start.
+        let earliest = time.saturating_sub(max_offset as u64);
+        let first = self
+            .sample_index
+            .partition_point(|info| info.start_time <= earliest)
+            .saturating_sub(1);
+        let time = time as i64;
+        let mut closest: Option<(i64, u32)> = None;
+        for (i, info) in self.sample_index.iter().enumerate().skip(first) {
+            if info.start_time as i64 + min_offset > time {
+                break;
+            }
+            let sample_id = i as u32 + 1;
+            let composition_time = info.start_time as i64 + info.rendering_offset as i64;
+            if composition_time <= time {
+                if composition_time + info.duration as i64 > time {
+                    return Ok(Some(sample_id));
+                }
+                match closest {
+                    Some((t, _)) if t >= composition_time => {}
+                    _ => closest = Some((composition_time, sample_id)),
+                }
+            }
+        }
+        Ok(closest.map(|(_, sample_id)| sample_id))
+    }
+
+    /// Returns the last sync sample at or before the sample presented at
+    /// `time`, where decoding has to start to seek to `time`.
+    pub fn sync_sample_before(&self, time: Duration) -> Result<Option<u32>> {
+        let sample_id = match self.sample_at_time(time)? {
+            Some(sample_id) => sample_id,
+            None => return Ok(None),
+        };
+        let idx = self.sync_samples.partition_point(|id| *id <= sample_id);
+        Ok(idx.checked_sub(1).map(|idx| self.sync_samples[idx]))
+    }
+
     pub fn bitrate(&self) -> u32 {
-        if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
+        if let Some(mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a() {
             if let Some(ref esds) = mp4a.esds {
                 esds.es_desc.dec_config.avg_bitrate
             } else {
@@ -234,22 +758,17 @@ impl Mp4Track {
 
     pub fn sample_count(&self) -> u32 {
         if !self.trafs.is_empty() {
-            let mut sample_count = 0u32;
-            for traf in self.trafs.iter() {
-                if let Some(ref trun) = traf.trun {
-                    sample_count = sample_count
-                        .checked_add(trun.sample_count)
-                        .expect("attempt to sum trun sample_count with overflow");
-                }
-            }
-            sample_count
+            self.trafs
+                .iter()
+                .flat_map(|traf| traf.truns.iter())
+                .fold(0u32, |count, trun| count.saturating_add(trun.sample_count))
         } else {
             self.trak.mdia.minf.stbl.stsz.sample_count
         }
     }
 
     pub fn video_profile(&self) -> Result<AvcProfile> {
-        if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
+        if let Some(avc1) = self.trak.mdia.minf.stbl.stsd.avc1() {
             AvcProfile::try_from((
                 avc1.avcc.avc_profile_indication,
                 avc1.avcc.profile_compatibility,
@@ -259,9 +778,28 @@ impl Mp4Track {
         }
     }
 
+    fn hvcc(&self) -> Option<&HvcCBox> {
+        self.trak.mdia.minf.stbl.stsd.hev1().map(|hev1| &hev1.hvcc)
+    }
+
+    pub fn video_parameter_set(&self) -> Result<&[u8]> {
+        if let Some(hvcc) = self.hvcc() {
+            match hvcc.video_parameter_set() {
+                Some(nal) => Ok(nal),
+                None => Err(Error::EntryInStblNotFound(
+                    self.track_id(),
+                    BoxType::HvcCBox,
+                    0,
+                )),
+            }
+        } else {
+            Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Hev1Box))
+        }
+    }
+
     pub fn sequence_parameter_set(&self) -> Result<&[u8]> {
-        if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
-            match avc1.avcc.sequence_parameter_sets.get(0) {
+        if let Some(avc1) = self.trak.mdia.minf.stbl.stsd.avc1() {
+            match avc1.avcc.sequence_parameter_sets.first() {
                 Some(nal) => Ok(nal.bytes.as_ref()),
                 None => Err(Error::EntryInStblNotFound(
                     self.track_id(),
@@ -269,14 +807,23 @@ impl Mp4Track {
                     0,
                 )),
             }
+        } else if let Some(hvcc) = self.hvcc() {
+            match hvcc.sequence_parameter_set() {
+                Some(nal) => Ok(nal),
+                None => Err(Error::EntryInStblNotFound(
+                    self.track_id(),
+                    BoxType::HvcCBox,
+                    0,
+                )),
+            }
         } else {
             Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Avc1Box))
         }
     }
 
     pub fn picture_parameter_set(&self) -> Result<&[u8]> {
-        if let Some(ref avc1) = self.trak.mdia.minf.stbl.stsd.avc1 {
-            match avc1.avcc.picture_parameter_sets.get(0) {
+        if let Some(avc1) = self.trak.mdia.minf.stbl.stsd.avc1() {
+            match avc1.avcc.picture_parameter_sets.first() {
                 Some(nal) => Ok(nal.bytes.as_ref()),
                 None => Err(Error::EntryInStblNotFound(
                     self.track_id(),
@@ -284,13 +831,127 @@ impl Mp4Track {
                     0,
                 )),
             }
+        } else if let Some(hvcc) = self.hvcc() {
+            match hvcc.picture_parameter_set() {
+                Some(nal) => Ok(nal),
+                None => Err(Error::EntryInStblNotFound(
+                    self.track_id(),
+                    BoxType::HvcCBox,
+                    0,
+                )),
+            }
         } else {
             Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Avc1Box))
         }
     }
 
+    /// Returns the HEVC configuration of the track, including profile, tier,
+    /// level and parameter sets.
+    pub fn hevc_config(&self) -> Result<HevcConfig> {
+        let hvcc = match self.hvcc() {
+            Some(hvcc) => hvcc,
+            None => return Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Hev1Box)),
+        };
+        Ok(HevcConfig {
+            width: self.width(),
+            height: self.height(),
+            box_type: self.hevc_box_type()?,
+            general_profile_space: hvcc.general_profile_space,
+            general_tier_flag: hvcc.general_tier_flag,
+            general_profile_idc: hvcc.general_profile_idc,
+            general_profile_compatibility_flags: hvcc.general_profile_compatibility_flags,
+            general_constraint_indicator_flag: hvcc.general_constraint_indicator_flag,
+            general_level_idc: hvcc.general_level_idc,
+            vid_param_set: hvcc.video_parameter_set().unwrap_or_default().to_vec(),
+            seq_param_set: hvcc.sequence_parameter_set().unwrap_or_default().to_vec(),
+            pic_param_set: hvcc.picture_parameter_set().unwrap_or_default().to_vec(),
+        })
+    }
+
+    /// Returns which sample entry (`hev1` or `hvc1`) an HEVC track uses.
+    pub fn hevc_box_type(&self) -> Result<HevcBoxType> {
+        match self.trak.mdia.minf.stbl.stsd.hev1() {
+            Some(hev1) => Ok(hev1.box_type),
+            None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Hev1Box)),
+        }
+    }
+
+    fn av1c(&self) -> Result<&Av1CBox> {
+        match self.trak.mdia.minf.stbl.stsd.av01() {
+            Some(av01) => Ok(&av01.av1c),
+            None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Av01Box)),
+        }
+    }
+
+    pub fn av1_profile(&self) -> Result<u8> {
+        Ok(self.av1c()?.seq_profile)
+    }
+
+    pub fn av1_level(&self) -> Result<u8> {
+        Ok(self.av1c()?.seq_level_idx_0)
+    }
+
+    pub fn av1_bit_depth(&self) -> Result<u8> {
+        Ok(self.av1c()?.bit_depth())
+    }
+
+    /// Returns the configuration OBUs from `av1C`, usually the sequence header.
+    pub fn av1_config_obus(&self) -> Result<&[u8]> {
+        Ok(&self.av1c()?.config_obus)
+    }
+
+    /// Returns the AV1 configuration of the track.
+    pub fn av1_config(&self) -> Result<Av1Config> {
+        let av1c = self.av1c()?;
+        Ok(Av1Config {
+            width: self.width(),
+            height: self.height(),
+            seq_profile: av1c.seq_profile,
+            seq_level_idx_0: av1c.seq_level_idx_0,
+            seq_tier_0: av1c.seq_tier_0,
+            bit_depth: av1c.bit_depth(),
+            monochrome: av1c.monochrome,
+            chroma_subsampling_x: av1c.chroma_subsampling_x,
+            chroma_subsampling_y: av1c.chroma_subsampling_y,
+            chroma_sample_position: av1c.chroma_sample_position,
+            config_obus: av1c.config_obus.clone(),
+        })
+    }
+
+    fn dops(&self) -> Result<&DopsBox> {
+        match self.trak.mdia.minf.stbl.stsd.opus() {
+            Some(opus) => Ok(&opus.dops),
+            None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::OpusBox)),
+        }
+    }
+
+    pub fn opus_pre_skip(&self) -> Result<u16> {
+        Ok(self.dops()?.pre_skip)
+    }
+
+    pub fn opus_input_sample_rate(&self) -> Result<u32> {
+        Ok(self.dops()?.input_sample_rate)
+    }
+
+    pub fn opus_channel_mapping_family(&self) -> Result<u8> {
+        Ok(self.dops()?.channel_mapping_family)
+    }
+
+    /// Returns the Opus configuration of the track.
+    pub fn opus_config(&self) -> Result<OpusConfig> {
+        let dops = self.dops()?;
+        Ok(OpusConfig {
+            channel_count: dops.output_channel_count,
+            pre_skip: dops.pre_skip,
+            input_sample_rate: dops.input_sample_rate,
+            output_gain: dops.output_gain,
+            channel_mapping_family: dops.channel_mapping_family,
+            channel_mapping_table: dops.channel_mapping_table.clone(),
+        })
+    }
+
     pub fn audio_profile(&self) -> Result<AudioObjectType> {
-        if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
+        if let Some(mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a() {
             if let Some(ref esds) = mp4a.esds {
                 AudioObjectType::try_from(esds.es_desc.dec_config.dec_specific.profile)
             } else {
@@ -317,12 +978,44 @@ impl Mp4Track {
         Ok(self.trak.mdia.minf.stbl.stsc.entries.len() - 1)
     }
 
+    fn sample_description_index(&self, sample_id: u32) -> u32 {
+        self.sample_info(sample_id)
+            .map_or(1, |info| info.sample_description_index)
+    }
+
+    /// return `(chunk_id, first_sample_id_in_chunk)`
+    fn sample_chunk(&self, sample_id: u32) -> Result<(u32, u32)> {
+        let stsc_index = self.stsc_index(sample_id)?;
+
+        let stsc = &self.trak.mdia.minf.stbl.stsc;
+        let stsc_entry = stsc.entries.get(stsc_index).unwrap();
+
+        let first_chunk = stsc_entry.first_chunk;
+        let first_sample = stsc_entry.first_sample;
+        let samples_per_chunk = stsc_entry.samples_per_chunk;
+
+        let chunk_id = sample_id
+            .checked_sub(first_sample)
+            .map(|n| n / samples_per_chunk)
+            .and_then(|n| n.checked_add(first_chunk))
+            .ok_or(Error::InvalidData(
+                "attempt to calculate stsc chunk_id with overflow",
+            ))?;
+
+        let first_sample_in_chunk = sample_id - (sample_id - first_sample) % samples_per_chunk;
+
+        Ok((chunk_id, first_sample_in_chunk))
+    }
+
     fn chunk_offset(&self, chunk_id: u32) -> Result<u64> {
         if self.trak.mdia.minf.stbl.stco.is_none() && self.trak.mdia.minf.stbl.co64.is_none() {
             return Err(Error::InvalidData("must have either stco or co64 boxes"));
         }
         if let Some(ref stco) = self.trak.mdia.minf.stbl.stco {
-            if let Some(offset) = stco.entries.get(chunk_id as usize - 1) {
+            let offset = chunk_id
+                .checked_sub(1)
+                .and_then(|i| stco.entries.get(i as usize));
+            if let Some(offset) = offset {
                 return Ok(*offset as u64);
             } else {
                 return Err(Error::EntryInStblNotFound(
@@ -332,7 +1025,10 @@ impl Mp4Track {
                 ));
             }
         } else if let Some(ref co64) = self.trak.mdia.minf.stbl.co64 {
-            if let Some(offset) = co64.entries.get(chunk_id as usize - 1) {
+            let offset = chunk_id
+                .checked_sub(1)
+                .and_then(|i| co64.entries.get(i as usize));
+            if let Some(offset) = offset {
                 return Ok(*offset);
             } else {
                 return Err(Error::EntryInStblNotFound(
@@ -345,282 +1041,265 @@ impl Mp4Track {
         Err(Error::Box2NotFound(BoxType::StcoBox, BoxType::Co64Box))
     }
 
-    fn ctts_index(&self, sample_id: u32) -> Result<(usize, u32)> {
-        let ctts = self.trak.mdia.minf.stbl.ctts.as_ref().unwrap();
-        let mut sample_count: u32 = 1;
-        for (i, entry) in ctts.entries.iter().enumerate() {
-            let next_sample_count =
-                sample_count
-                    .checked_add(entry.sample_count)
-                    .ok_or(Error::InvalidData(
-                        "attempt to sum ctts entries sample_count with overflow",
-                    ))?;
-            if sample_id < next_sample_count {
-                return Ok((i, sample_count));
-            }
-            sample_count = next_sample_count;
-        }
-
-        Err(Error::EntryInStblNotFound(
-            self.track_id(),
-            BoxType::CttsBox,
-            sample_id,
-        ))
-    }
-
-    /// return `(traf_idx, sample_idx_in_trun)`
-    fn find_traf_idx_and_sample_idx(&self, sample_id: u32) -> Option<(usize, usize)> {
-        let global_idx = sample_id - 1;
-        let mut offset = 0;
-        for traf_idx in 0..self.trafs.len() {
-            if let Some(trun) = &self.trafs[traf_idx].trun {
-                let sample_count = trun.sample_count;
-                if sample_count > (global_idx - offset) {
-                    return Some((traf_idx, (global_idx - offset) as _));
-                }
-                offset = offset
-                    .checked_add(sample_count)
-                    .expect("attempt to sum trun sample_count with overflow");
-            }
+    fn trun_sample_size(&self, traf: &TrafBox, trun: &TrunBox, sample_idx: usize) -> Option<u32> {
+        if TrunBox::FLAG_SAMPLE_SIZE & trun.flags != 0 {
+            trun.sample_sizes.get(sample_idx).copied()
+        } else {
+            traf.tfhd
+                .default_sample_size
+                .or(Some(self.default_sample_size))
         }
-        None
     }
 
-    fn sample_size(&self, sample_id: u32) -> Result<u32> {
-        if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                if let Some(size) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .unwrap()
-                    .sample_sizes
-                    .get(sample_idx)
-                {
-                    Ok(*size)
-                } else {
-                    Err(Error::EntryInTrunNotFound(
-                        self.track_id(),
-                        BoxType::TrunBox,
-                        sample_id,
-                    ))
-                }
-            } else {
-                Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox))
-            }
-        } else {
-            let stsz = &self.trak.mdia.minf.stbl.stsz;
-            if stsz.sample_size > 0 {
-                return Ok(stsz.sample_size);
-            }
-            if let Some(size) = stsz.sample_sizes.get(sample_id as usize - 1) {
-                Ok(*size)
-            } else {
-                Err(Error::EntryInStblNotFound(
-                    self.track_id(),
-                    BoxType::StszBox,
-                    sample_id,
-                ))
+    fn trun_sample_duration(&self, traf: &TrafBox, trun: &TrunBox, sample_idx: usize) -> u32 {
+        if TrunBox::FLAG_SAMPLE_DURATION & trun.flags != 0 {
+            if let Some(duration) = trun.sample_durations.get(sample_idx) {
+                return *duration;
             }
         }
+        traf.tfhd
+            .default_sample_duration
+            .unwrap_or(self.default_sample_duration)
     }
 
     fn total_sample_size(&self) -> u64 {
-        let stsz = &self.trak.mdia.minf.stbl.stsz;
-        if stsz.sample_size > 0 {
-            stsz.sample_size as u64 * self.sample_count() as u64
-        } else {
-            let mut total_size = 0;
-            for size in stsz.sample_sizes.iter() {
-                total_size += *size as u64;
-            }
-            total_size
-        }
+        self.sample_index.iter().map(|info| info.size as u64).sum()
     }
 
     pub fn sample_offset(&self, sample_id: u32) -> Result<u64> {
-        if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                let mut sample_offset = self.trafs[traf_idx]
-                    .tfhd
-                    .base_data_offset
-                    .unwrap_or(self.moof_offsets[traf_idx]);
-
-                if let Some(data_offset) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .and_then(|trun| trun.data_offset)
-                {
-                    sample_offset = sample_offset.checked_add_signed(data_offset as i64).ok_or(
-                        Error::InvalidData("attempt to calculate trun sample offset with overflow"),
-                    )?;
-                }
+        match self.sample_location(sample_id)? {
+            Some(info) => Ok(info.offset),
+            None => Err(Error::EntryInStblNotFound(
+                self.track_id(),
+                BoxType::StszBox,
+                sample_id,
+            )),
+        }
+    }
 
-                let first_sample_in_trun = sample_id - sample_idx as u32;
-                for i in first_sample_in_trun..sample_id {
-                    sample_offset = sample_offset
-                        .checked_add(self.sample_size(i)? as u64)
-                        .ok_or(Error::InvalidData(
-                            "attempt to calculate trun entry sample offset with overflow",
-                        ))?;
-                }
+    /// Returns the index entry of a sample, or `None` if the sample tables
+    /// do not describe it. A sample missing from the fragments is an error.
+    pub(crate) fn sample_location(&self, sample_id: u32) -> Result<Option<&SampleInfo>> {
+        match self.sample_info(sample_id) {
+            Some(info) => Ok(Some(info)),
+            None if !self.trafs.is_empty() => Err(Error::EntryInTrunNotFound(
+                self.track_id(),
+                BoxType::TrunBox,
+                sample_id,
+            )),
+            None => Ok(None),
+        }
+    }
 
-                Ok(sample_offset)
-            } else {
-                Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox))
-            }
+    /// Returns the sample decoded at `time`, in the media timescale.
+    fn sample_at_decode_time(&self, time: u64) -> Option<u32> {
+        let idx = self
+            .sample_index
+            .partition_point(|info| info.start_time <= time)
+            .checked_sub(1)?;
+        let info = &self.sample_index[idx];
+        if time < info.start_time + info.duration as u64 {
+            Some(idx as u32 + 1)
         } else {
-            let stsc_index = self.stsc_index(sample_id)?;
-
-            let stsc = &self.trak.mdia.minf.stbl.stsc;
-            let stsc_entry = stsc.entries.get(stsc_index).unwrap();
-
-            let first_chunk = stsc_entry.first_chunk;
-            let first_sample = stsc_entry.first_sample;
-            let samples_per_chunk = stsc_entry.samples_per_chunk;
-
-            let chunk_id = sample_id
-                .checked_sub(first_sample)
-                .map(|n| n / samples_per_chunk)
-                .and_then(|n| n.checked_add(first_chunk))
-                .ok_or(Error::InvalidData(
-                    "attempt to calculate stsc chunk_id with overflow",
-                ))?;
-
-            let chunk_offset = self.chunk_offset(chunk_id)?;
-
-            let first_sample_in_chunk = sample_id - (sample_id - first_sample) % samples_per_chunk;
-
-            let mut sample_offset = 0;
-            for i in first_sample_in_chunk..sample_id {
-                sample_offset += self.sample_size(i)?;
-            }
-
-            Ok(chunk_offset + sample_offset as u64)
+            None
         }
     }
 
-    fn sample_time(&self, sample_id: u32) -> Result<(u64, u32)> {
-        if !self.trafs.is_empty() {
-            let mut base_start_time = 0;
-            let mut default_sample_duration = self.default_sample_duration;
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                let traf = &self.trafs[traf_idx];
-                if let Some(tfdt) = &traf.tfdt {
-                    base_start_time = tfdt.base_media_decode_time;
-                }
-                if let Some(duration) = traf.tfhd.default_sample_duration {
-                    default_sample_duration = duration;
-                }
-                if let Some(trun) = &traf.trun {
-                    if TrunBox::FLAG_SAMPLE_DURATION & trun.flags != 0 {
-                        let mut start_offset = 0u64;
-                        for duration in &trun.sample_durations[..sample_idx] {
-                            start_offset = start_offset.checked_add(*duration as u64).ok_or(
-                                Error::InvalidData("attempt to sum sample durations with overflow"),
-                            )?;
-                        }
-                        let duration = trun.sample_durations[sample_idx];
-                        return Ok((base_start_time + start_offset, duration));
-                    }
-                }
-            }
-            let start_offset = ((sample_id - 1) * default_sample_duration) as u64;
-            Ok((base_start_time + start_offset, default_sample_duration))
+    fn trun_sample_flags(&self, traf: &TrafBox, trun: &TrunBox, sample_idx: usize) -> SampleFlags {
+        let flags = if TrunBox::FLAG_SAMPLE_FLAGS & trun.flags != 0 {
+            trun.sample_flags.get(sample_idx).copied()
+        } else if sample_idx == 0 && trun.first_sample_flags.is_some() {
+            trun.first_sample_flags
         } else {
-            let stts = &self.trak.mdia.minf.stbl.stts;
+            None
+        };
+        let flags = flags
+            .or(traf.tfhd.default_sample_flags)
+            .unwrap_or(self.default_sample_flags);
+        SampleFlags::from(flags)
+    }
 
-            let mut sample_count: u32 = 1;
-            let mut elapsed = 0;
+    pub(crate) fn sample_with_bytes(&self, info: &SampleInfo, bytes: Bytes) -> Mp4Sample {
+        Mp4Sample {
+            start_time: info.start_time,
+            duration: info.duration,
+            rendering_offset: info.rendering_offset,
+            is_sync: info.is_sync,
+            sample_description_index: info.sample_description_index,
+            sample_flags: info.sample_flags,
+            bytes,
+        }
+    }
 
-            for entry in stts.entries.iter() {
-                let new_sample_count =
-                    sample_count
-                        .checked_add(entry.sample_count)
-                        .ok_or(Error::InvalidData(
-                            "attempt to sum stts entries sample_count with overflow",
-                        ))?;
-                if sample_id < new_sample_count {
-                    let start_time =
-                        (sample_id - sample_count) as u64 * entry.sample_delta as u64 + elapsed;
-                    return Ok((start_time, entry.sample_delta));
-                }
+    pub(crate) fn read_sample<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        sample_id: u32,
+    ) -> Result<Option<Mp4Sample>> {
+        let info = match self.sample_location(sample_id)? {
+            Some(info) => info,
+            None => return Ok(None),
+        };
 
-                sample_count = new_sample_count;
-                elapsed += entry.sample_count as u64 * entry.sample_delta as u64;
-            }
+        let mut buffer = vec![0x0u8; info.size as usize];
+        reader.seek(SeekFrom::Start(info.offset))?;
+        reader.read_exact(&mut buffer)?;
 
-            Err(Error::EntryInStblNotFound(
-                self.track_id(),
-                BoxType::SttsBox,
-                sample_id,
-            ))
-        }
+        Ok(Some(self.sample_with_bytes(info, Bytes::from(buffer))))
     }
 
-    fn sample_rendering_offset(&self, sample_id: u32) -> i32 {
-        if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                if let Some(cts) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .and_then(|trun| trun.sample_cts.get(sample_idx))
-                {
-                    return *cts as i32;
-                }
-            }
-        } else if let Some(ref ctts) = self.trak.mdia.minf.stbl.ctts {
-            if let Ok((ctts_index, _)) = self.ctts_index(sample_id) {
-                let ctts_entry = ctts.entries.get(ctts_index).unwrap();
-                return ctts_entry.sample_offset;
-            }
-        }
-        0
-    }
+    pub(crate) fn read_sample_into<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        sample_id: u32,
+        buf: &mut Vec<u8>,
+    ) -> Result<Option<SampleInfo>> {
+        let info = match self.sample_location(sample_id)? {
+            Some(info) => info,
+            None => return Ok(None),
+        };
 
-    fn is_sync_sample(&self, sample_id: u32) -> bool {
-        if !self.trafs.is_empty() {
-            let sample_sizes_count = self.sample_count() / self.trafs.len() as u32;
-            return sample_id == 1 || sample_id % sample_sizes_count == 0;
-        }
+        buf.clear();
+        buf.resize(info.size as usize, 0);
+        reader.seek(SeekFrom::Start(info.offset))?;
+        reader.read_exact(buf)?;
 
-        if let Some(ref stss) = self.trak.mdia.minf.stbl.stss {
-            stss.entries.binary_search(&sample_id).is_ok()
-        } else {
-            true
+        Ok(Some(*info))
+    }
+
+    fn tenc(&self, sample_description_index: u32) -> Option<&TencBox> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        match stsd.entry(sample_description_index) {
+            Some(SampleEntry::Encrypted(encrypted)) => encrypted.sinf.tenc(),
+            _ => None,
         }
     }
 
-    pub(crate) fn read_sample<R: Read + Seek>(
+    /// Returns the IV and subsample map of a protected sample, from `senc`
+    /// or from the auxiliary information located by `saiz` and `saio`.
+    pub(crate) fn sample_encryption<R: Read + Seek>(
         &self,
         reader: &mut R,
         sample_id: u32,
-    ) -> Result<Option<Mp4Sample>> {
-        let sample_offset = match self.sample_offset(sample_id) {
-            Ok(offset) => offset,
-            Err(Error::EntryInStblNotFound(_, _, _)) => return Ok(None),
-            Err(err) => return Err(err),
+    ) -> Result<Option<SampleEncryption>> {
+        if self.sample_info(sample_id).is_none() {
+            return Ok(None);
+        }
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let sinf = match stsd.entry(self.sample_description_index(sample_id)) {
+            Some(SampleEntry::Encrypted(encrypted)) => &encrypted.sinf,
+            _ => return Ok(None),
         };
-        let sample_size = match self.sample_size(sample_id) {
-            Ok(size) => size,
-            Err(Error::EntryInStblNotFound(_, _, _)) => return Ok(None),
-            Err(err) => return Err(err),
+        let tenc = sinf
+            .tenc()
+            .ok_or(Error::BoxInStblNotFound(self.track_id(), BoxType::TencBox))?;
+        if tenc.default_is_protected == 0 {
+            return Ok(None);
+        }
+        let iv_size = tenc.default_per_sample_iv_size;
+        let with_constant_iv = |mut entry: SampleEncryption| {
+            if iv_size == 0 {
+                entry.iv = tenc.default_constant_iv.clone();
+            }
+            Some(entry)
         };
 
-        let mut buffer = vec![0x0u8; sample_size as usize];
-        reader.seek(SeekFrom::Start(sample_offset))?;
-        reader.read_exact(&mut buffer)?;
+        // The sizes, and the offset of the information of the sample at
+        // `first_index`, in the run of auxiliary information that contains
+        // the sample at `index`.
+        let (saiz, aux_offset, first_index, index) = if !self.trafs.is_empty() {
+            let traf_idx = self
+                .traf_first_samples
+                .partition_point(|first| *first <= sample_id)
+                - 1;
+            let traf = &self.trafs[traf_idx];
+            let index = (sample_id - self.traf_first_samples[traf_idx]) as usize;
+
+            if traf.senc.is_some() {
+                let entry = self.senc_entries[traf_idx].get(index).cloned().ok_or(
+                    Error::EntryInTrunNotFound(self.track_id(), BoxType::SencBox, sample_id),
+                )?;
+                return Ok(with_constant_iv(entry));
+            }
+            match scheme_aux_info(&traf.saizs, &traf.saios, sinf.scheme_type()) {
+                Some((saiz, saio)) if !saio.offsets.is_empty() => {
+                    let base_offset = traf
+                        .tfhd
+                        .base_data_offset
+                        .unwrap_or(self.moof_offsets[traf_idx]);
+                    (saiz, base_offset + saio.offsets[0], 0, index)
+                }
+                _ if iv_size == 0 => return Ok(with_constant_iv(SampleEncryption::default())),
+                _ => return Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::SencBox)),
+            }
+        } else {
+            let stbl = &self.trak.mdia.minf.stbl;
+            let index = sample_id as usize - 1;
+            match scheme_aux_info(&stbl.saizs, &stbl.saios, sinf.scheme_type()) {
+                Some((saiz, saio)) if saio.offsets.len() == 1 => (saiz, saio.offsets[0], 0, index),
+                Some((saiz, saio)) => {
+                    // One offset per chunk.
+                    let (chunk_id, first_sample_in_chunk) = self.sample_chunk(sample_id)?;
+                    let offset = chunk_id
+                        .checked_sub(1)
+                        .and_then(|i| saio.offsets.get(i as usize))
+                        .ok_or(Error::EntryInStblNotFound(
+                            self.track_id(),
+                            BoxType::SaioBox,
+                            chunk_id,
+                        ))?;
+                    (saiz, *offset, first_sample_in_chunk as usize - 1, index)
+                }
+                _ if iv_size == 0 => return Ok(with_constant_iv(SampleEncryption::default())),
+                _ => return Err(Error::BoxInStblNotFound(self.track_id(), BoxType::SaizBox)),
+            }
+        };
 
-        let (start_time, duration) = self.sample_time(sample_id).unwrap(); // XXX
-        let rendering_offset = self.sample_rendering_offset(sample_id);
-        let is_sync = self.is_sync_sample(sample_id);
+        let skipped: u64 = (first_index..index)
+            .map(|i| saiz.sample_info_size(i).unwrap_or(0) as u64)
+            .sum();
+        let size = saiz
+            .sample_info_size(index)
+            .ok_or(Error::InvalidData("saiz has no entry for the sample"))?;
+
+        let mut buf = vec![0u8; size as usize];
+        reader.seek(SeekFrom::Start(aux_offset + skipped))?;
+        reader.read_exact(&mut buf)?;
+        let entry = read_sample_encryption(&mut &buf[..], iv_size, size > iv_size)?;
+        Ok(with_constant_iv(entry))
+    }
 
-        Ok(Some(Mp4Sample {
-            start_time,
-            duration,
-            rendering_offset,
-            is_sync,
-            bytes: Bytes::from(buffer),
-        }))
+    /// Decrypts a sample of the track in place, given its IV and subsample
+    /// map from [`Mp4Reader::sample_encryption`] and a map from key IDs to
+    /// keys. Only the `cenc` and `cbcs` schemes are supported.
+    #[cfg(feature = "encryption")]
+    pub fn decrypt_sample(
+        &self,
+        sample: &mut Mp4Sample,
+        encryption: &SampleEncryption,
+        keys: &std::collections::HashMap<[u8; 16], [u8; 16]>,
+    ) -> Result<()> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        let sinf = match stsd.entry(sample.sample_description_index) {
+            Some(SampleEntry::Encrypted(encrypted)) => &encrypted.sinf,
+            _ => return Err(Error::InvalidData("sample is not protected")),
+        };
+        let schm = sinf
+            .schm
+            .as_ref()
+            .ok_or(Error::BoxInStblNotFound(self.track_id(), BoxType::SchmBox))?;
+        let scheme = EncryptionScheme::try_from(&schm.scheme_type)?;
+        let tenc = sinf
+            .tenc()
+            .ok_or(Error::BoxInStblNotFound(self.track_id(), BoxType::TencBox))?;
+        let key = keys
+            .get(&tenc.default_kid)
+            .ok_or(Error::KeyNotFound(tenc.default_kid))?;
+
+        let mut data = sample.bytes.to_vec();
+        encryption::decrypt_sample(scheme, tenc, key, encryption, &mut data)?;
+        sample.bytes = Bytes::from(data);
+        Ok(())
     }
 }
 
@@ -636,66 +1315,230 @@ pub(crate) struct Mp4TrackWriter {
     chunk_duration: u32,
     chunk_buffer: BytesMut,
 
-    samples_per_chunk: u32,
+    chunk_strategy: ChunkStrategy,
     duration_per_chunk: u32,
-}
 
-impl Mp4TrackWriter {
-    pub(crate) fn new(track_id: u32, config: &TrackConfig) -> Result<Self> {
-        let mut trak = TrakBox::default();
-        trak.tkhd.track_id = track_id;
-        trak.mdia.mdhd.timescale = config.timescale;
-        trak.mdia.mdhd.language = config.language.to_owned();
-        trak.mdia.hdlr.handler_type = config.track_type.into();
-        trak.mdia.minf.stbl.co64 = Some(Co64Box::default());
-        match config.media_conf {
-            MediaConfig::AvcConfig(ref avc_config) => {
-                trak.tkhd.set_width(avc_config.width);
-                trak.tkhd.set_height(avc_config.height);
-
-                let vmhd = VmhdBox::default();
-                trak.mdia.minf.vmhd = Some(vmhd);
+    // Full chunks and their decode time, waiting to be interleaved with the
+    // chunks of other tracks. `None` writes chunks as soon as they are full.
+    pending_chunks: Option<VecDeque<(u64, Bytes)>>,
+    chunk_start_time: u64,
 
-                let avc1 = Avc1Box::new(avc_config);
-                trak.mdia.minf.stbl.stsd.avc1 = Some(avc1);
-            }
-            MediaConfig::HevcConfig(ref hevc_config) => {
-                trak.tkhd.set_width(hevc_config.width);
-                trak.tkhd.set_height(hevc_config.height);
+    #[cfg(feature = "encryption")]
+    encryptor: Option<SampleEncryptor>,
+    sample_encryptions: Vec<SampleEncryption>,
+}
 
-                let vmhd = VmhdBox::default();
-                trak.mdia.minf.vmhd = Some(vmhd);
+fn new_trak(track_id: u32, config: &TrackConfig) -> Result<TrakBox> {
+    if config.timescale == 0 {
+        return Err(Error::InvalidData("track timescale must not be 0"));
+    }
+    let mut trak = TrakBox::default();
+    trak.tkhd.track_id = track_id;
+    trak.mdia.mdhd.timescale = config.timescale;
+    trak.mdia.mdhd.language = config.language.to_owned();
+    trak.mdia.hdlr.handler_type = config.track_type.into();
+    if !config.edit_list.is_empty() {
+        trak.edts = Some(EdtsBox {
+            elst: Some(ElstBox::new(config.edit_list.clone())),
+        });
+    }
+    match config.media_conf {
+        MediaConfig::AvcConfig(ref avc_config) => {
+            trak.tkhd.set_width(avc_config.width);
+            trak.tkhd.set_height(avc_config.height);
+
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
+
+            let avc1 = Avc1Box::new(avc_config);
+            trak.mdia
+                .minf
+                .stbl
+                .stsd
+                .entries
+                .push(SampleEntry::Avc1(avc1));
+        }
+        MediaConfig::HevcConfig(ref hevc_config) => {
+            trak.tkhd.set_width(hevc_config.width);
+            trak.tkhd.set_height(hevc_config.height);
+
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
+
+            let hev1 = Hev1Box::new(hevc_config)?;
+            trak.mdia
+                .minf
+                .stbl
+                .stsd
+                .entries
+                .push(SampleEntry::Hev1(hev1));
+        }
+        MediaConfig::Vp9Config(ref config) => {
+            trak.tkhd.set_width(config.width);
+            trak.tkhd.set_height(config.height);
+
+            trak.mdia
+                .minf
+                .stbl
+                .stsd
+                .entries
+                .push(SampleEntry::Vp09(Vp09Box::new(config)));
+        }
+        MediaConfig::Av1Config(ref config) => {
+            trak.tkhd.set_width(config.width);
+            trak.tkhd.set_height(config.height);
+
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
+
+            trak.mdia
+                .minf
+                .stbl
+                .stsd
+                .entries
+                .push(SampleEntry::Av01(Av01Box::new(config)));
+        }
+        MediaConfig::AacConfig(ref aac_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let mp4a = Mp4aBox::new(aac_config);
+            trak.mdia
+                .minf
+                .stbl
+                .stsd
+                .entries
+                .push(SampleEntry::Mp4a(mp4a));
+        }
+        MediaConfig::OpusConfig(ref opus_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let opus = OpusBox::new(opus_config);
+            opus.dops.check_channel_mapping()?;
+            trak.mdia
+                .minf
+                .stbl
+                .stsd
+                .entries
+                .push(SampleEntry::Opus(opus));
+        }
+        MediaConfig::TtxtConfig(ref _ttxt_config) => {
+            let tx3g = Tx3gBox::default();
+            trak.mdia
+                .minf
+                .stbl
+                .stsd
+                .entries
+                .push(SampleEntry::Tx3g(tx3g));
+        }
+    }
+    #[cfg(not(feature = "encryption"))]
+    if config.encryption.is_some() {
+        return Err(Error::InvalidData(
+            "track encryption requires the encryption feature",
+        ));
+    }
+    #[cfg(feature = "encryption")]
+    if let Some(ref encryption) = config.encryption {
+        let sinf = SampleEncryptor::new(track_id, encryption, &config.media_conf).sinf();
+        let stsd = &mut trak.mdia.minf.stbl.stsd;
+        stsd.entries = stsd
+            .entries
+            .drain(..)
+            .map(|entry| SampleEntry::Encrypted(EncryptedSampleEntry::new(entry, sinf.clone())))
+            .collect();
+    }
+    Ok(trak)
+}
 
-                let hev1 = Hev1Box::new(hevc_config);
-                trak.mdia.minf.stbl.stsd.hev1 = Some(hev1);
-            }
-            MediaConfig::Vp9Config(ref config) => {
-                trak.tkhd.set_width(config.width);
-                trak.tkhd.set_height(config.height);
+/// Encrypts a sample of a track written with [`TrackConfig::encryption`],
+/// keeping its auxiliary information.
+#[cfg(feature = "encryption")]
+fn encrypt_sample(
+    encryptor: &mut Option<SampleEncryptor>,
+    sample_encryptions: &mut Vec<SampleEncryption>,
+    sample: &Mp4Sample,
+) -> Result<Bytes> {
+    match encryptor {
+        Some(encryptor) => {
+            let (data, encryption) = encryptor.encrypt(&sample.bytes)?;
+            sample_encryptions.push(encryption);
+            Ok(Bytes::from(data))
+        }
+        None => Ok(sample.bytes.clone()),
+    }
+}
 
-                trak.mdia.minf.stbl.stsd.vp09 = Some(Vp09Box::new(config));
-            }
-            MediaConfig::AacConfig(ref aac_config) => {
-                let smhd = SmhdBox::default();
-                trak.mdia.minf.smhd = Some(smhd);
+impl Mp4TrackWriter {
+    pub(crate) fn new(
+        track_id: u32,
+        config: &TrackConfig,
+        interleave_duration: Option<Duration>,
+    ) -> Result<Self> {
+        let mut trak = new_trak(track_id, config)?;
+        trak.mdia.minf.stbl.co64 = Some(Co64Box::default());
 
-                let mp4a = Mp4aBox::new(aac_config);
-                trak.mdia.minf.stbl.stsd.mp4a = Some(mp4a);
-            }
-            MediaConfig::TtxtConfig(ref _ttxt_config) => {
-                let tx3g = Tx3gBox::default();
-                trak.mdia.minf.stbl.stsd.tx3g = Some(tx3g);
+        let chunk_strategy = match (config.chunk_strategy, interleave_duration) {
+            (Some(chunk_strategy), _) => chunk_strategy,
+            (None, Some(duration)) => ChunkStrategy::Duration(duration),
+            (None, None) => ChunkStrategy::default(),
+        };
+        let duration_per_chunk = match chunk_strategy {
+            ChunkStrategy::Duration(duration) => {
+                let ticks = duration.as_nanos() * config.timescale as u128 / 1_000_000_000;
+                cmp::max(ticks, 1).try_into().unwrap_or(u32::MAX)
             }
-        }
+            _ => 0,
+        };
         Ok(Mp4TrackWriter {
             trak,
             chunk_buffer: BytesMut::new(),
             sample_id: 1,
-            duration_per_chunk: config.timescale, // 1 second
+            chunk_strategy,
+            duration_per_chunk,
+            pending_chunks: interleave_duration.map(|_| VecDeque::new()),
+            #[cfg(feature = "encryption")]
+            encryptor: config
+                .encryption
+                .as_ref()
+                .map(|encryption| SampleEncryptor::new(track_id, encryption, &config.media_conf)),
             ..Self::default()
         })
     }
 
+    fn to_duration(&self, time: u64) -> Duration {
+        let timescale = cmp::max(self.trak.mdia.mdhd.timescale, 1) as u128;
+        Duration::from_nanos((time as u128 * 1_000_000_000 / timescale) as u64)
+    }
+
+    /// Returns the decode time of the first chunk waiting to be written.
+    pub(crate) fn pending_chunk_time(&self) -> Option<Duration> {
+        let (time, _) = self.pending_chunks.as_ref()?.front()?;
+        Some(self.to_duration(*time))
+    }
+
+    /// Returns the decode time from which chunks are still to be completed,
+    /// or `None` if no sample has been written to the track yet.
+    pub(crate) fn open_chunk_time(&self) -> Option<Duration> {
+        if self.trak.mdia.minf.stbl.stsz.sample_count == 0 {
+            None
+        } else if self.chunk_samples > 0 {
+            Some(self.to_duration(self.chunk_start_time))
+        } else {
+            Some(self.to_duration(self.trak.mdia.mdhd.duration))
+        }
+    }
+
+    pub(crate) fn write_pending_chunk<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
+        if let Some((_, chunk)) = self.pending_chunks.as_mut().and_then(|c| c.pop_front()) {
+            let chunk_offset = writer.stream_position()?;
+            writer.write_all(&chunk)?;
+            self.update_chunk_offsets(chunk_offset);
+        }
+        Ok(())
+    }
+
     fn update_sample_sizes(&mut self, size: u32) {
         if self.trak.mdia.minf.stbl.stsz.sample_count == 0 {
             if size == 0 {
@@ -798,10 +1641,11 @@ impl Mp4TrackWriter {
     }
 
     fn is_chunk_full(&self) -> bool {
-        if self.samples_per_chunk > 0 {
-            self.chunk_samples >= self.samples_per_chunk
-        } else {
-            self.chunk_duration >= self.duration_per_chunk
+        match self.chunk_strategy {
+            ChunkStrategy::SampleCount(count) => self.chunk_samples >= count,
+            ChunkStrategy::Duration(_) => self.chunk_duration >= self.duration_per_chunk,
+            ChunkStrategy::ByteSize(size) => self.chunk_buffer.len() >= size as usize,
+            ChunkStrategy::SingleSample => true,
         }
     }
 
@@ -823,10 +1667,17 @@ impl Mp4TrackWriter {
         sample: &Mp4Sample,
         movie_timescale: u32,
     ) -> Result<u64> {
-        self.chunk_buffer.extend_from_slice(&sample.bytes);
+        #[cfg(feature = "encryption")]
+        let bytes = encrypt_sample(&mut self.encryptor, &mut self.sample_encryptions, sample)?;
+        #[cfg(not(feature = "encryption"))]
+        let bytes = sample.bytes.clone();
+        if self.chunk_samples == 0 {
+            self.chunk_start_time = self.trak.mdia.mdhd.duration;
+        }
+        self.chunk_buffer.extend_from_slice(&bytes);
         self.chunk_samples += 1;
         self.chunk_duration += sample.duration;
-        self.update_sample_sizes(sample.bytes.len() as u32);
+        self.update_sample_sizes(bytes.len() as u32);
         self.update_sample_times(sample.duration);
         self.update_rendering_offsets(sample.rendering_offset);
         self.update_sync_samples(sample.is_sync);
@@ -842,7 +1693,11 @@ impl Mp4TrackWriter {
 
     fn chunk_count(&self) -> u32 {
         let co64 = self.trak.mdia.minf.stbl.co64.as_ref().unwrap();
-        co64.entries.len() as u32
+        let pending = self
+            .pending_chunks
+            .as_ref()
+            .map_or(0, |chunks| chunks.len());
+        (co64.entries.len() + pending) as u32
     }
 
     fn update_sample_to_chunk(&mut self, chunk_id: u32) {
@@ -866,16 +1721,20 @@ impl Mp4TrackWriter {
         co64.entries.push(offset);
     }
 
-    fn write_chunk<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
+    pub(crate) fn write_chunk<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
         if self.chunk_buffer.is_empty() {
             return Ok(());
         }
-        let chunk_offset = writer.stream_position()?;
-
-        writer.write_all(&self.chunk_buffer)?;
-
         self.update_sample_to_chunk(self.chunk_count() + 1);
-        self.update_chunk_offsets(chunk_offset);
+
+        if let Some(ref mut pending_chunks) = self.pending_chunks {
+            let chunk = self.chunk_buffer.split().freeze();
+            pending_chunks.push_back((self.chunk_start_time, chunk));
+        } else {
+            let chunk_offset = writer.stream_position()?;
+            writer.write_all(&self.chunk_buffer)?;
+            self.update_chunk_offsets(chunk_offset);
+        }
 
         self.chunk_buffer.clear();
         self.chunk_samples = 0;
@@ -896,17 +1755,85 @@ impl Mp4TrackWriter {
         }
     }
 
-    pub(crate) fn write_end<W: Write + Seek>(&mut self, writer: &mut W) -> Result<TrakBox> {
+    /// Writes the IVs and subsample maps of encrypted samples after the
+    /// sample data, as auxiliary information located by `saiz` and `saio`.
+    fn write_aux_info<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
+        let (data, sizes) = sample_aux_info(&self.sample_encryptions);
+        self.sample_encryptions.clear();
+        if data.is_empty() {
+            return Ok(());
+        }
+        let offset = writer.stream_position()?;
+        writer.write_all(&data)?;
+
+        let stbl = &mut self.trak.mdia.minf.stbl;
+        stbl.saizs.push(SaizBox::new(sizes));
+        stbl.saios.push(SaioBox {
+            version: if offset > u32::MAX as u64 { 1 } else { 0 },
+            offsets: vec![offset],
+            ..SaioBox::default()
+        });
+        Ok(())
+    }
+
+    fn update_edit_list(&mut self, movie_timescale: u32) -> Result<()> {
+        let timescale = self.trak.mdia.mdhd.timescale;
+        let media_duration = self.trak.mdia.mdhd.duration;
+        let elst = match self.trak.edts {
+            Some(EdtsBox {
+                elst: Some(ref mut elst),
+            }) => elst,
+            _ => return Ok(()),
+        };
+        if timescale == 0 {
+            return Err(Error::InvalidData("track timescale must not be 0"));
+        }
+
+        // An open ended last edit lasts until the end of the media.
+        let mut entries = std::mem::take(&mut elst.entries);
+        if let Some(last) = entries.last_mut() {
+            if last.segment_duration == 0 && !last.is_empty_edit() && last.rate() != 0 {
+                let remaining = media_duration.saturating_sub(last.media_time as u64);
+                let duration = ((remaining as u128) << 16) * movie_timescale as u128
+                    / (last.rate() as u128 * timescale as u128);
+                last.segment_duration = u64::try_from(duration)
+                    .map_err(|_| Error::InvalidData("edit list segment duration overflows"))?;
+            }
+        }
+        *elst = ElstBox::new(entries);
+
+        self.trak.tkhd.duration = elst
+            .entries
+            .iter()
+            .try_fold(0u64, |duration, entry| {
+                duration.checked_add(entry.segment_duration)
+            })
+            .ok_or(Error::InvalidData("edit list duration overflows"))?;
+        if self.trak.tkhd.duration > (u32::MAX as u64) {
+            self.trak.tkhd.version = 1
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_end<W: Write + Seek>(
+        &mut self,
+        writer: &mut W,
+        movie_timescale: u32,
+    ) -> Result<TrakBox> {
         self.write_chunk(writer)?;
+        self.write_aux_info(writer)?;
+        self.update_edit_list(movie_timescale)?;
 
         let max_sample_size = self.max_sample_size();
-        if let Some(ref mut mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
-            if let Some(ref mut esds) = mp4a.esds {
-                esds.es_desc.dec_config.buffer_size_db = max_sample_size;
+        for entry in self.trak.mdia.minf.stbl.stsd.entries.iter_mut() {
+            if let SampleEntry::Mp4a(ref mut mp4a) = entry {
+                if let Some(ref mut esds) = mp4a.esds {
+                    esds.es_desc.dec_config.buffer_size_db = max_sample_size;
+                }
+                // TODO
+                // mp4a.esds.es_desc.dec_config.max_bitrate
+                // mp4a.esds.es_desc.dec_config.avg_bitrate
             }
-            // TODO
-            // mp4a.esds.es_desc.dec_config.max_bitrate
-            // mp4a.esds.es_desc.dec_config.avg_bitrate
         }
         if let Ok(stco) = StcoBox::try_from(self.trak.mdia.minf.stbl.co64.as_ref().unwrap()) {
             self.trak.mdia.minf.stbl.stco = Some(stco);
@@ -916,3 +1843,218 @@ impl Mp4TrackWriter {
         Ok(self.trak.clone())
     }
 }
+
+// Sample flags used in trun boxes, see ISO/IEC 14496-12 8.8.3.1.
+const SYNC_SAMPLE_FLAGS: u32 = 0x02000000; // sample_depends_on = 2
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x01010000; // sample_depends_on = 1, is_non_sync_sample
+
+#[derive(Debug, Default)]
+pub(crate) struct Mp4FragmentTrackWriter {
+    trak: TrakBox,
+
+    base_media_decode_time: u64,
+    sample_durations: Vec<u32>,
+    sample_sizes: Vec<u32>,
+    sample_flags: Vec<u32>,
+    sample_cts: Vec<i32>,
+    sample_buffer: BytesMut,
+
+    #[cfg(feature = "encryption")]
+    encryptor: Option<SampleEncryptor>,
+    sample_encryptions: Vec<SampleEncryption>,
+}
+
+impl Mp4FragmentTrackWriter {
+    pub(crate) fn new(track_id: u32, config: &TrackConfig) -> Result<Self> {
+        let mut trak = new_trak(track_id, config)?;
+        // Samples live in the fragments, the sample tables of the init segment stay empty.
+        trak.mdia.minf.stbl.stco = Some(StcoBox::default());
+        Ok(Mp4FragmentTrackWriter {
+            trak,
+            sample_buffer: BytesMut::new(),
+            #[cfg(feature = "encryption")]
+            encryptor: config
+                .encryption
+                .as_ref()
+                .map(|encryption| SampleEncryptor::new(track_id, encryption, &config.media_conf)),
+            ..Self::default()
+        })
+    }
+
+    pub(crate) fn track_id(&self) -> u32 {
+        self.trak.tkhd.track_id
+    }
+
+    pub(crate) fn trak(&self) -> &TrakBox {
+        &self.trak
+    }
+
+    pub(crate) fn trex(&self) -> TrexBox {
+        TrexBox {
+            track_id: self.track_id(),
+            default_sample_description_index: 1,
+            ..TrexBox::default()
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sample_sizes.is_empty()
+    }
+
+    pub(crate) fn data_size(&self) -> u64 {
+        self.sample_buffer.len() as u64
+    }
+
+    pub(crate) fn timescale(&self) -> u32 {
+        self.trak.mdia.mdhd.timescale
+    }
+
+    pub(crate) fn is_video(&self) -> bool {
+        self.trak.mdia.minf.vmhd.is_some()
+    }
+
+    /// Duration of the pending samples in the track timescale.
+    pub(crate) fn pending_duration(&self) -> u64 {
+        self.sample_durations.iter().map(|d| *d as u64).sum()
+    }
+
+    /// Earliest presentation time of the pending samples in the track
+    /// timescale.
+    pub(crate) fn earliest_presentation_time(&self) -> u64 {
+        let mut decode_time = self.base_media_decode_time as i64;
+        let mut earliest = None;
+        for (duration, cts) in self.sample_durations.iter().zip(self.sample_cts.iter()) {
+            let time = decode_time + *cts as i64;
+            earliest = Some(earliest.map_or(time, |earliest: i64| earliest.min(time)));
+            decode_time += *duration as i64;
+        }
+        earliest.unwrap_or(decode_time).max(0) as u64
+    }
+
+    /// Number, starting from 1, and presentation time in the track timescale
+    /// of the first pending sync sample.
+    pub(crate) fn first_sync_sample(&self) -> Option<(u32, u64)> {
+        let mut decode_time = self.base_media_decode_time;
+        for (i, flags) in self.sample_flags.iter().enumerate() {
+            if SampleFlags::from(*flags).is_sync() {
+                let time = (decode_time as i64 + self.sample_cts[i] as i64).max(0) as u64;
+                return Some((i as u32 + 1, time));
+            }
+            decode_time += self.sample_durations[i] as u64;
+        }
+        None
+    }
+
+    /// Whether the first pending sample is a sync sample.
+    pub(crate) fn starts_with_sync(&self) -> bool {
+        self.sample_flags
+            .first()
+            .is_some_and(|flags| SampleFlags::from(*flags).is_sync())
+    }
+
+    pub(crate) fn write_sample(&mut self, sample: &Mp4Sample) -> Result<()> {
+        if sample.bytes.len() > u32::MAX as usize {
+            return Err(Error::InvalidData("sample is too large"));
+        }
+        #[cfg(feature = "encryption")]
+        let bytes = encrypt_sample(&mut self.encryptor, &mut self.sample_encryptions, sample)?;
+        #[cfg(not(feature = "encryption"))]
+        let bytes = sample.bytes.clone();
+        self.sample_buffer.extend_from_slice(&bytes);
+        self.sample_durations.push(sample.duration);
+        self.sample_sizes.push(bytes.len() as u32);
+        self.sample_flags.push(match sample.sample_flags {
+            Some(flags) => flags.into(),
+            None if sample.is_sync => SYNC_SAMPLE_FLAGS,
+            None => NON_SYNC_SAMPLE_FLAGS,
+        });
+        self.sample_cts.push(sample.rendering_offset);
+        Ok(())
+    }
+
+    /// Build the traf box describing the pending samples. `data_offset` and
+    /// `traf_offset`, the position of the traf box, are relative to the start
+    /// of the enclosing moof box.
+    pub(crate) fn traf(&self, data_offset: i32, traf_offset: u64) -> TrafBox {
+        let mut trun_flags = TrunBox::FLAG_DATA_OFFSET
+            | TrunBox::FLAG_SAMPLE_DURATION
+            | TrunBox::FLAG_SAMPLE_SIZE
+            | TrunBox::FLAG_SAMPLE_FLAGS;
+        let mut trun_version = 0;
+        if self.sample_cts.iter().any(|cts| *cts != 0) {
+            trun_flags |= TrunBox::FLAG_SAMPLE_CTS;
+        }
+        if self.sample_cts.iter().any(|cts| *cts < 0) {
+            trun_version = 1;
+        }
+        let trun = TrunBox {
+            version: trun_version,
+            flags: trun_flags,
+            sample_count: self.sample_sizes.len() as u32,
+            data_offset: Some(data_offset),
+            first_sample_flags: None,
+            sample_durations: self.sample_durations.clone(),
+            sample_sizes: self.sample_sizes.clone(),
+            sample_flags: self.sample_flags.clone(),
+            sample_cts: if trun_flags & TrunBox::FLAG_SAMPLE_CTS != 0 {
+                self.sample_cts.iter().map(|cts| *cts as u32).collect()
+            } else {
+                Vec::new()
+            },
+        };
+
+        let base_media_decode_time = self.base_media_decode_time;
+        let mut traf = TrafBox {
+            tfhd: TfhdBox {
+                flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                track_id: self.track_id(),
+                ..TfhdBox::default()
+            },
+            tfdt: Some(TfdtBox {
+                version: if base_media_decode_time > u32::MAX as u64 {
+                    1
+                } else {
+                    0
+                },
+                flags: 0,
+                base_media_decode_time,
+            }),
+            truns: vec![trun],
+            saizs: vec![],
+            saios: vec![],
+            senc: None,
+        };
+
+        let (aux_info, sizes) = sample_aux_info(&self.sample_encryptions);
+        if !aux_info.is_empty() {
+            let senc = SencBox::new(&self.sample_encryptions);
+            traf.saizs.push(SaizBox::new(sizes));
+            traf.saios.push(SaioBox {
+                offsets: vec![0],
+                ..SaioBox::default()
+            });
+            // senc is the last box of the traf, and saio points at its first
+            // entry.
+            let senc_offset = traf_offset + traf.box_size();
+            traf.saios[0].offsets[0] = senc_offset + HEADER_SIZE + HEADER_EXT_SIZE + 4;
+            traf.senc = Some(senc);
+        }
+        traf
+    }
+
+    /// Write the pending sample data and start a new fragment.
+    pub(crate) fn write_data<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.sample_buffer)?;
+
+        for duration in self.sample_durations.iter() {
+            self.base_media_decode_time += *duration as u64;
+        }
+        self.sample_buffer.clear();
+        self.sample_durations.clear();
+        self.sample_sizes.clear();
+        self.sample_flags.clear();
+        self.sample_cts.clear();
+        self.sample_encryptions.clear();
+        Ok(())
+    }
+}
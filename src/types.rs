@@ -2,6 +2,7 @@ use serde::Serialize;
 use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt;
+use std::time::Duration;
 
 use crate::mp4box::*;
 use crate::*;
@@ -86,6 +87,15 @@ impl fmt::Display for BoxType {
     }
 }
 
+impl Serialize for BoxType {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 #[derive(Default, PartialEq, Eq, Clone, Copy, Serialize)]
 pub struct FourCC {
     pub value: [u8; 4],
@@ -220,7 +230,9 @@ impl From<TrackType> for FourCC {
 const MEDIA_TYPE_H264: &str = "h264";
 const MEDIA_TYPE_H265: &str = "h265";
 const MEDIA_TYPE_VP9: &str = "vp9";
+const MEDIA_TYPE_AV1: &str = "av1";
 const MEDIA_TYPE_AAC: &str = "aac";
+const MEDIA_TYPE_OPUS: &str = "opus";
 const MEDIA_TYPE_TTXT: &str = "ttxt";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -228,7 +240,9 @@ pub enum MediaType {
     H264,
     H265,
     VP9,
+    AV1,
     AAC,
+    OPUS,
     TTXT,
 }
 
@@ -246,7 +260,9 @@ impl TryFrom<&str> for MediaType {
             MEDIA_TYPE_H264 => Ok(MediaType::H264),
             MEDIA_TYPE_H265 => Ok(MediaType::H265),
             MEDIA_TYPE_VP9 => Ok(MediaType::VP9),
+            MEDIA_TYPE_AV1 => Ok(MediaType::AV1),
             MEDIA_TYPE_AAC => Ok(MediaType::AAC),
+            MEDIA_TYPE_OPUS => Ok(MediaType::OPUS),
             MEDIA_TYPE_TTXT => Ok(MediaType::TTXT),
             _ => Err(Error::InvalidData("unsupported media type")),
         }
@@ -259,7 +275,9 @@ impl From<MediaType> for &str {
             MediaType::H264 => MEDIA_TYPE_H264,
             MediaType::H265 => MEDIA_TYPE_H265,
             MediaType::VP9 => MEDIA_TYPE_VP9,
+            MediaType::AV1 => MEDIA_TYPE_AV1,
             MediaType::AAC => MEDIA_TYPE_AAC,
+            MediaType::OPUS => MEDIA_TYPE_OPUS,
             MediaType::TTXT => MEDIA_TYPE_TTXT,
         }
     }
@@ -271,7 +289,9 @@ impl From<&MediaType> for &str {
             MediaType::H264 => MEDIA_TYPE_H264,
             MediaType::H265 => MEDIA_TYPE_H265,
             MediaType::VP9 => MEDIA_TYPE_VP9,
+            MediaType::AV1 => MEDIA_TYPE_AV1,
             MediaType::AAC => MEDIA_TYPE_AAC,
+            MediaType::OPUS => MEDIA_TYPE_OPUS,
             MediaType::TTXT => MEDIA_TYPE_TTXT,
         }
     }
@@ -572,10 +592,46 @@ pub struct AvcConfig {
     pub pic_param_set: Vec<u8>,
 }
 
+/// Sample entry used for HEVC tracks.
+///
+/// With `hvc1` all parameter sets are stored in the `hvcC` box, which is what
+/// Apple players and HLS require. `hev1` also allows them in-band.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HevcBoxType {
+    #[default]
+    Hev1,
+    Hvc1,
+}
+
+impl From<HevcBoxType> for BoxType {
+    fn from(t: HevcBoxType) -> BoxType {
+        match t {
+            HevcBoxType::Hev1 => BoxType::Hev1Box,
+            HevcBoxType::Hvc1 => BoxType::Hvc1Box,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct HevcConfig {
     pub width: u16,
     pub height: u16,
+    pub box_type: HevcBoxType,
+
+    /// Profile, tier and level as signalled in the `hvcC` box. When
+    /// `general_profile_idc` is left at zero they are taken from the SPS.
+    pub general_profile_space: u8,
+    pub general_tier_flag: bool,
+    pub general_profile_idc: u8,
+    pub general_profile_compatibility_flags: u32,
+    pub general_constraint_indicator_flag: u64,
+    pub general_level_idc: u8,
+
+    pub vid_param_set: Vec<u8>,
+    /// The chroma format and bit depths of the `hvcC` box are read from it.
+    pub seq_param_set: Vec<u8>,
+    pub pic_param_set: Vec<u8>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
@@ -584,6 +640,41 @@ pub struct Vp9Config {
     pub height: u16,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Av1Config {
+    pub width: u16,
+    pub height: u16,
+    pub seq_profile: u8,
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: bool,
+    /// 8, 10 or 12.
+    pub bit_depth: u8,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: u8,
+    /// Sequence header OBU (and any metadata OBUs) to store in `av1C`.
+    pub config_obus: Vec<u8>,
+}
+
+impl Default for Av1Config {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            seq_profile: 0,
+            seq_level_idx_0: 0,
+            seq_tier_0: false,
+            bit_depth: 8,
+            monochrome: false,
+            chroma_subsampling_x: true, // 4:2:0
+            chroma_subsampling_y: true,
+            chroma_sample_position: 0,
+            config_obus: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct AacConfig {
     pub bitrate: u32,
@@ -603,6 +694,30 @@ impl Default for AacConfig {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OpusConfig {
+    pub channel_count: u8,
+    pub pre_skip: u16,
+    pub input_sample_rate: u32,
+    pub output_gain: i16,
+    pub channel_mapping_family: u8,
+    /// Required when `channel_mapping_family` is not 0.
+    pub channel_mapping_table: Option<OpusChannelMappingTable>,
+}
+
+impl Default for OpusConfig {
+    fn default() -> Self {
+        Self {
+            channel_count: 2,
+            pre_skip: 0,
+            input_sample_rate: 48000,
+            output_gain: 0,
+            channel_mapping_family: 0,
+            channel_mapping_table: None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct TtxtConfig {}
 
@@ -611,25 +726,229 @@ pub enum MediaConfig {
     AvcConfig(AvcConfig),
     HevcConfig(HevcConfig),
     Vp9Config(Vp9Config),
+    Av1Config(Av1Config),
     AacConfig(AacConfig),
+    OpusConfig(OpusConfig),
     TtxtConfig(TtxtConfig),
 }
 
+/// Sample flags as stored in trun, tfhd and trex boxes, see ISO/IEC 14496-12
+/// 8.8.3.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SampleFlags {
+    pub is_leading: u8,
+    /// 1: depends on other samples (not an I-picture), 2: does not depend on
+    /// others (I-picture), 0: unknown.
+    pub depends_on: u8,
+    pub is_depended_on: u8,
+    pub has_redundancy: u8,
+    pub padding_value: u8,
+    pub is_non_sync: bool,
+    pub degradation_priority: u16,
+}
+
+impl SampleFlags {
+    pub fn is_sync(&self) -> bool {
+        !self.is_non_sync
+    }
+}
+
+impl From<u32> for SampleFlags {
+    fn from(flags: u32) -> Self {
+        Self {
+            is_leading: ((flags >> 26) & 0b11) as u8,
+            depends_on: ((flags >> 24) & 0b11) as u8,
+            is_depended_on: ((flags >> 22) & 0b11) as u8,
+            has_redundancy: ((flags >> 20) & 0b11) as u8,
+            padding_value: ((flags >> 17) & 0b111) as u8,
+            is_non_sync: (flags >> 16) & 0b1 != 0,
+            degradation_priority: flags as u16,
+        }
+    }
+}
+
+impl From<SampleFlags> for u32 {
+    fn from(flags: SampleFlags) -> u32 {
+        ((flags.is_leading as u32 & 0b11) << 26)
+            | ((flags.depends_on as u32 & 0b11) << 24)
+            | ((flags.is_depended_on as u32 & 0b11) << 22)
+            | ((flags.has_redundancy as u32 & 0b11) << 20)
+            | ((flags.padding_value as u32 & 0b111) << 17)
+            | ((flags.is_non_sync as u32) << 16)
+            | flags.degradation_priority as u32
+    }
+}
+
+/// A segment of a track's presentation timeline, derived from its edit list.
+/// All times are in the media timescale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditSegment {
+    /// Presentation time at which the segment starts.
+    pub presentation_time: u64,
+    /// Duration of the segment on the presentation timeline. A zero duration
+    /// on the last segment extends it to the end of the media.
+    pub duration: u64,
+    /// Media time presented at the start of the segment, `None` for an empty
+    /// edit.
+    pub media_time: Option<u64>,
+    /// Playback rate as 16.16 fixed point, 0 dwells on `media_time`.
+    pub media_rate: u32,
+}
+
+/// Clear and protected byte counts of one subsample of an encrypted sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SubsampleEntry {
+    pub bytes_of_clear_data: u16,
+    pub bytes_of_protected_data: u32,
+}
+
+/// Common encryption parameters of a sample: its initialization vector and,
+/// for subsample encryption, the clear and protected ranges of the sample.
+/// An empty `subsamples` means the whole sample is protected.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SampleEncryption {
+    pub iv: Vec<u8>,
+    pub subsamples: Vec<SubsampleEntry>,
+}
+
+const ENCRYPTION_SCHEME_CENC_FOURCC: [u8; 4] = [b'c', b'e', b'n', b'c'];
+const ENCRYPTION_SCHEME_CBCS_FOURCC: [u8; 4] = [b'c', b'b', b'c', b's'];
+
+/// Common encryption scheme, see ISO/IEC 23001-7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionScheme {
+    /// AES-CTR over the protected bytes of a sample.
+    Cenc,
+    /// AES-CBC over a pattern of protected blocks, with a constant IV.
+    Cbcs,
+}
+
+impl fmt::Display for EncryptionScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", FourCC::from(*self))
+    }
+}
+
+impl TryFrom<&FourCC> for EncryptionScheme {
+    type Error = Error;
+    fn try_from(fourcc: &FourCC) -> Result<EncryptionScheme> {
+        match fourcc.value {
+            ENCRYPTION_SCHEME_CENC_FOURCC => Ok(EncryptionScheme::Cenc),
+            ENCRYPTION_SCHEME_CBCS_FOURCC => Ok(EncryptionScheme::Cbcs),
+            _ => Err(Error::InvalidData("unsupported protection scheme")),
+        }
+    }
+}
+
+impl From<EncryptionScheme> for FourCC {
+    fn from(scheme: EncryptionScheme) -> FourCC {
+        match scheme {
+            EncryptionScheme::Cenc => ENCRYPTION_SCHEME_CENC_FOURCC.into(),
+            EncryptionScheme::Cbcs => ENCRYPTION_SCHEME_CBCS_FOURCC.into(),
+        }
+    }
+}
+
+/// Encryption of the samples written to a track, see
+/// [`TrackConfig::encryption`].
+///
+/// H.264 and H.265 samples are encrypted per NAL unit, leaving the NAL
+/// headers and non-VCL NAL units in the clear. Other samples are encrypted
+/// whole.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EncryptionConfig {
+    pub scheme: EncryptionScheme,
+    pub key_id: [u8; 16],
+    pub key: [u8; 16],
+    /// With [`EncryptionScheme::Cenc`], the first 8 bytes are the IV of the
+    /// first sample, incremented for each following sample. The track ID is
+    /// XORed into the first 4 bytes, so tracks configured with the same key
+    /// and IV still use distinct IVs. With [`EncryptionScheme::Cbcs`], the
+    /// constant IV of all samples.
+    pub iv: [u8; 16],
+}
+
+/// Location, timing and flags of a sample, see [`Mp4Track::sample_index`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleInfo {
+    /// Offset of the sample data in the file.
+    pub offset: u64,
+    pub size: u32,
+    /// Decode time in the media timescale.
+    pub start_time: u64,
+    pub duration: u32,
+    pub rendering_offset: i32,
+    pub is_sync: bool,
+    pub sample_description_index: u32,
+    pub sample_flags: Option<SampleFlags>,
+}
+
+/// Location and time span of a segment indexed by a `sidx` box, see
+/// [`Mp4Reader::segment_at_time`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentInfo {
+    /// Offset of the first box of the segment in the file.
+    pub offset: u64,
+    pub size: u64,
+    /// Earliest presentation time of the segment.
+    pub start_time: Duration,
+    pub duration: Duration,
+    pub starts_with_sap: bool,
+}
+
+/// A sync sample listed in a `tfra` box, see
+/// [`Mp4Reader::random_access_point_before`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomAccessPoint {
+    /// Presentation time of the sync sample.
+    pub time: Duration,
+    /// Offset of the `moof` box of the fragment holding the sample.
+    pub moof_offset: u64,
+    /// The traf, trun and sample numbers start from 1.
+    pub traf_number: u32,
+    pub trun_number: u32,
+    pub sample_number: u32,
+}
+
+/// A sample read from or written to a track.
 #[derive(Debug)]
 pub struct Mp4Sample {
     pub start_time: u64,
     pub duration: u32,
     pub rendering_offset: i32,
     pub is_sync: bool,
+    /// 1-based index into the track's sample descriptions (`stsd` entries).
+    /// Writers currently emit a single sample description per track and
+    /// ignore this value.
+    pub sample_description_index: u32,
+    /// Flags of samples in fragmented tracks. When writing fragments, `None`
+    /// derives them from `is_sync`.
+    pub sample_flags: Option<SampleFlags>,
     pub bytes: Bytes,
 }
 
+impl Default for Mp4Sample {
+    fn default() -> Self {
+        Self {
+            start_time: 0,
+            duration: 0,
+            rendering_offset: 0,
+            is_sync: false,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: Bytes::new(),
+        }
+    }
+}
+
 impl PartialEq for Mp4Sample {
     fn eq(&self, other: &Self) -> bool {
         self.start_time == other.start_time
             && self.duration == other.duration
             && self.rendering_offset == other.rendering_offset
             && self.is_sync == other.is_sync
+            && self.sample_description_index == other.sample_description_index
+            && self.sample_flags == other.sample_flags
             && self.bytes.len() == other.bytes.len() // XXX for easy check
     }
 }
@@ -695,17 +1014,17 @@ pub enum MetadataKey {
 
 pub trait Metadata<'a> {
     /// The video's title
-    fn title(&self) -> Option<Cow<str>>;
+    fn title(&self) -> Option<Cow<'_, str>>;
     /// The video's release year
     fn year(&self) -> Option<u32>;
     /// The video's poster (cover art)
     fn poster(&self) -> Option<&[u8]>;
     /// The video's summary
-    fn summary(&self) -> Option<Cow<str>>;
+    fn summary(&self) -> Option<Cow<'_, str>>;
 }
 
 impl<'a, T: Metadata<'a>> Metadata<'a> for &'a T {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         (**self).title()
     }
 
@@ -717,13 +1036,13 @@ impl<'a, T: Metadata<'a>> Metadata<'a> for &'a T {
         (**self).poster()
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn summary(&self) -> Option<Cow<'_, str>> {
         (**self).summary()
     }
 }
 
 impl<'a, T: Metadata<'a>> Metadata<'a> for Option<T> {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         self.as_ref().and_then(|t| t.title())
     }
 
@@ -735,7 +1054,7 @@ impl<'a, T: Metadata<'a>> Metadata<'a> for Option<T> {
         self.as_ref().and_then(|t| t.poster())
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn summary(&self) -> Option<Cow<'_, str>> {
         self.as_ref().and_then(|t| t.summary())
     }
 }
@@ -1,16 +1,59 @@
 use byteorder::{BigEndian, WriteBytesExt};
+use std::convert::TryFrom;
 use std::io::{Seek, SeekFrom, Write};
+use std::time::Duration;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
 
 use crate::mp4box::*;
-use crate::track::Mp4TrackWriter;
+use crate::track::{Mp4FragmentTrackWriter, Mp4TrackWriter};
 use crate::*;
 
+/// Options of [Mp4Writer] and [Mp4FragmentedWriter].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Mp4Config {
     pub major_brand: FourCC,
     pub minor_version: u32,
     pub compatible_brands: Vec<FourCC>,
     pub timescale: u32,
+
+    /// Reserve this many bytes in front of the mdat box for the moov box,
+    /// producing a faststart file that can be played while downloading.
+    ///
+    /// The space is written as a `free` box. If the moov box does not fit in
+    /// it, [Mp4Writer::write_end] still appends the moov box after the mdat
+    /// box, so the file stays playable, but returns [Error::MoovTooLarge]
+    /// with the size the reservation needed.
+    /// Not supported by [Mp4FragmentedWriter], which always writes the moov
+    /// box first.
+    pub reserved_moov_size: Option<u64>,
+
+    /// Cut chunks of this duration and write the chunks of all tracks in
+    /// decode time order, so that players read the file sequentially.
+    ///
+    /// Chunks are held back until every track with samples has been written
+    /// up to their decode time, so samples should be written in roughly
+    /// decode order. A track that falls more than this duration behind the
+    /// others is treated as ended and no longer holds back their chunks.
+    /// Ignored by [Mp4FragmentedWriter].
+    pub interleave_duration: Option<Duration>,
+}
+
+impl Default for Mp4Config {
+    fn default() -> Self {
+        Self {
+            major_brand: FourCC::from(*b"isom"),
+            minor_version: 512,
+            compatible_brands: vec![
+                FourCC::from(*b"isom"),
+                FourCC::from(*b"iso2"),
+                FourCC::from(*b"mp41"),
+            ],
+            timescale: 1000,
+            reserved_moov_size: None,
+            interleave_duration: None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -18,8 +61,12 @@ pub struct Mp4Writer<W> {
     writer: W,
     tracks: Vec<Mp4TrackWriter>,
     mdat_pos: u64,
+    free_pos: Option<u64>,
+    reserved_moov_size: u64,
+    interleave_duration: Option<Duration>,
     timescale: u32,
     duration: u64,
+    unknown_boxes: Vec<UnknownBox>,
 }
 
 impl<W> Mp4Writer<W> {
@@ -36,15 +83,13 @@ impl<W> Mp4Writer<W> {
     ///
     /// # fn main() -> mp4::Result<()> {
     /// let config = Mp4Config {
-    ///     major_brand: str::parse("isom").unwrap(),
-    ///     minor_version: 512,
     ///     compatible_brands: vec![
     ///         str::parse("isom").unwrap(),
     ///         str::parse("iso2").unwrap(),
     ///         str::parse("avc1").unwrap(),
     ///         str::parse("mp41").unwrap(),
     ///     ],
-    ///     timescale: 1000,
+    ///     ..Default::default()
     /// };
     ///
     /// let data = Cursor::new(Vec::<u8>::new());
@@ -68,6 +113,18 @@ impl<W: Write + Seek> Mp4Writer<W> {
         };
         ftyp.write_box(&mut writer)?;
 
+        let mut free_pos = None;
+        let mut reserved_moov_size = 0;
+        if let Some(size) = config.reserved_moov_size {
+            if size < HEADER_SIZE {
+                return Err(Error::InvalidData("reserved moov size is too small"));
+            }
+            free_pos = Some(writer.stream_position()?);
+            reserved_moov_size = size;
+            BoxHeader::new(BoxType::FreeBox, size).write(&mut writer)?;
+            writer.write_all(&vec![0u8; (size - HEADER_SIZE) as usize])?;
+        }
+
         // TODO largesize
         let mdat_pos = writer.stream_position()?;
         BoxHeader::new(BoxType::MdatBox, HEADER_SIZE).write(&mut writer)?;
@@ -80,18 +137,28 @@ impl<W: Write + Seek> Mp4Writer<W> {
             writer,
             tracks,
             mdat_pos,
+            free_pos,
+            reserved_moov_size,
+            interleave_duration: config.interleave_duration,
             timescale,
             duration,
+            unknown_boxes: Vec::new(),
         })
     }
 
     pub fn add_track(&mut self, config: &TrackConfig) -> Result<()> {
         let track_id = self.tracks.len() as u32 + 1;
-        let track = Mp4TrackWriter::new(track_id, config)?;
+        let track = Mp4TrackWriter::new(track_id, config, self.interleave_duration)?;
         self.tracks.push(track);
         Ok(())
     }
 
+    /// Adds a top level box to write after the media data, such as one of
+    /// the [Mp4Reader::unknown_boxes] of a file being copied.
+    pub fn add_unknown_box(&mut self, unknown: UnknownBox) {
+        self.unknown_boxes.push(unknown);
+    }
+
     fn update_durations(&mut self, track_dur: u64) {
         if track_dur > self.duration {
             self.duration = track_dur;
@@ -111,13 +178,58 @@ impl<W: Write + Seek> Mp4Writer<W> {
 
         self.update_durations(track_dur);
 
+        if let Some(interleave_duration) = self.interleave_duration {
+            // Chunks before the earliest incomplete one can't be preceded by
+            // any chunk still to come. Tracks without samples yet don't hold
+            // back the others, and neither do tracks that fell more than the
+            // interleave duration behind, as they have likely ended or
+            // stalled. Their open chunk is closed instead.
+            let latest = self
+                .tracks
+                .iter()
+                .filter_map(|track| track.open_chunk_time())
+                .max();
+            let mut until: Option<Duration> = None;
+            for track in self.tracks.iter_mut() {
+                let time = match track.open_chunk_time() {
+                    Some(time) => time,
+                    None => continue,
+                };
+                if latest.is_some_and(|latest| time + interleave_duration < latest) {
+                    track.write_chunk(&mut self.writer)?;
+                } else {
+                    until = Some(until.map_or(time, |until| until.min(time)));
+                }
+            }
+            self.write_pending_chunks(until)?;
+        }
+
         Ok(())
     }
 
+    /// Writes the pending chunks of all tracks in decode time order, up to
+    /// the ones starting at `until`, or all of them.
+    fn write_pending_chunks(&mut self, until: Option<Duration>) -> Result<()> {
+        loop {
+            let next = self
+                .tracks
+                .iter()
+                .enumerate()
+                .filter_map(|(i, track)| track.pending_chunk_time().map(|time| (time, i)))
+                .min();
+            match next {
+                Some((time, i)) if until.map_or(true, |until| time <= until) => {
+                    self.tracks[i].write_pending_chunk(&mut self.writer)?;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
     fn update_mdat_size(&mut self) -> Result<()> {
         let mdat_end = self.writer.stream_position()?;
         let mdat_size = mdat_end - self.mdat_pos;
-        if mdat_size > std::u32::MAX as u64 {
+        if mdat_size > u32::MAX as u64 {
             self.writer.seek(SeekFrom::Start(self.mdat_pos))?;
             self.writer.write_u32::<BigEndian>(1)?;
             self.writer.seek(SeekFrom::Start(self.mdat_pos + 8))?;
@@ -134,16 +246,654 @@ impl<W: Write + Seek> Mp4Writer<W> {
         let mut moov = MoovBox::default();
 
         for track in self.tracks.iter_mut() {
-            moov.traks.push(track.write_end(&mut self.writer)?);
+            track.write_chunk(&mut self.writer)?;
+        }
+        self.write_pending_chunks(None)?;
+        for track in self.tracks.iter_mut() {
+            moov.traks
+                .push(track.write_end(&mut self.writer, self.timescale)?);
         }
         self.update_mdat_size()?;
+        for unknown in self.unknown_boxes.iter() {
+            unknown.write_box(&mut self.writer)?;
+        }
+
+        // Edit lists may have changed the track durations.
+        self.duration = moov
+            .traks
+            .iter()
+            .map(|trak| trak.tkhd.duration)
+            .max()
+            .unwrap_or(0);
 
         moov.mvhd.timescale = self.timescale;
         moov.mvhd.duration = self.duration;
         if moov.mvhd.duration > (u32::MAX as u64) {
             moov.mvhd.version = 1
         }
+
+        if let Some(free_pos) = self.free_pos {
+            // The rest of the reserved space must be covered by a free box.
+            let moov_size = moov.box_size();
+            if moov_size == self.reserved_moov_size
+                || moov_size + HEADER_SIZE <= self.reserved_moov_size
+            {
+                let end = self.writer.stream_position()?;
+                self.writer.seek(SeekFrom::Start(free_pos))?;
+                moov.write_box(&mut self.writer)?;
+                if moov_size < self.reserved_moov_size {
+                    BoxHeader::new(BoxType::FreeBox, self.reserved_moov_size - moov_size)
+                        .write(&mut self.writer)?;
+                }
+                self.writer.seek(SeekFrom::Start(end))?;
+                return Ok(());
+            }
+            moov.write_box(&mut self.writer)?;
+            return Err(Error::MoovTooLarge(moov_size));
+        }
+
         moov.write_box(&mut self.writer)?;
         Ok(())
     }
 }
+
+/// Writer for fragmented MP4 files.
+///
+/// The init segment (`ftyp` and a `moov` box with `mvex`) is written first,
+/// followed by one `moof` + `mdat` pair every time a fragment is flushed.
+/// Samples are buffered in memory until [Mp4FragmentedWriter::flush_fragment]
+/// is called, so the inner writer does not need to implement [Seek]. This
+/// makes it suitable for streaming to pipes, sockets or HTTP bodies. Only
+/// writing the segment index, see [Mp4FragmentedWriter::set_segment_index],
+/// needs to seek back to the start of the fragments.
+///
+/// # Examples
+///
+/// ```rust
+/// use mp4::{Mp4Config, Mp4FragmentedWriter};
+/// use std::time::Duration;
+///
+/// # fn main() -> mp4::Result<()> {
+/// let config = Mp4Config {
+///     major_brand: str::parse("iso5").unwrap(),
+///     compatible_brands: vec![str::parse("iso5").unwrap(), str::parse("iso6").unwrap()],
+///     ..Default::default()
+/// };
+///
+/// // Any `std::io::Write` works, e.g. `std::io::stdout()` or a `TcpStream`.
+/// let mut writer = Mp4FragmentedWriter::write_start(Vec::<u8>::new(), &config)?;
+/// writer.set_fragment_duration(Some(Duration::from_secs(2)));
+/// writer.write_end()?;
+///
+/// let data: Vec<u8> = writer.into_writer();
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Mp4FragmentedWriter<W> {
+    writer: W,
+    fragments: FragmentsWriter,
+}
+
+#[derive(Debug)]
+struct FragmentsWriter {
+    tracks: Vec<Mp4FragmentTrackWriter>,
+    timescale: u32,
+    sequence_number: u32,
+    init_written: bool,
+    fragment_duration: Option<Duration>,
+    segment_index: Option<SegmentIndex>,
+    random_access_index: Option<Vec<TfraBox>>,
+    // Number of bytes written so far.
+    position: u64,
+}
+
+/// References of the `sidx` box, written at the end into a `free` box
+/// reserved for it after the init segment.
+#[derive(Debug, Default)]
+struct SegmentIndex {
+    max_subsegments: u32,
+    // Position of the reserved slot.
+    offset: u64,
+    earliest_presentation_time: u64,
+    references: Vec<SidxReference>,
+}
+
+impl SegmentIndex {
+    /// Size of a version 1 `sidx` box with `max_subsegments` references.
+    fn slot_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 28 + 12 * self.max_subsegments as u64
+    }
+}
+
+impl<W> Mp4FragmentedWriter<W> {
+    fn new(writer: W, config: &Mp4Config, position: u64) -> Self {
+        Self {
+            writer,
+            fragments: FragmentsWriter {
+                tracks: Vec::new(),
+                timescale: config.timescale,
+                sequence_number: 0,
+                init_written: false,
+                fragment_duration: None,
+                segment_index: None,
+                random_access_index: None,
+                position,
+            },
+        }
+    }
+
+    pub fn add_track(&mut self, config: &TrackConfig) -> Result<()> {
+        if self.fragments.init_written {
+            return Err(Error::InvalidData(
+                "cannot add a track after the init segment is written",
+            ));
+        }
+        let track_id = self.fragments.tracks.len() as u32 + 1;
+        let track = Mp4FragmentTrackWriter::new(track_id, config)?;
+        self.fragments.tracks.push(track);
+        Ok(())
+    }
+
+    /// Flush fragments automatically once they reach `duration`.
+    ///
+    /// A new fragment is started at the first sync sample of the reference
+    /// track (the first video track, or the first track if there is no video)
+    /// after `duration` worth of samples is pending. With `None` (the default)
+    /// fragments are only written by [Mp4FragmentedWriter::flush_fragment].
+    pub fn set_fragment_duration(&mut self, duration: Option<Duration>) {
+        self.fragments.fragment_duration = duration;
+    }
+
+    /// Index the fragments of the reference track with a `sidx` box ahead of
+    /// them, as in the DASH on-demand profile.
+    ///
+    /// Room for a version 1 sidx with up to `max_subsegments` references is
+    /// reserved as a `free` box after the init segment. The sidx is written
+    /// into it by [Mp4FragmentedWriter::write_segment_index], which needs a
+    /// seekable writer; until then the space stays a `free` box. Writing more
+    /// subsegments, i.e. fragments with samples of the reference track, is
+    /// an error. Must be set before the init segment is written.
+    pub fn set_segment_index(&mut self, max_subsegments: Option<u32>) -> Result<()> {
+        if self.fragments.init_written {
+            return Err(Error::InvalidData(
+                "cannot change the segment index after the init segment is written",
+            ));
+        }
+        self.fragments.segment_index = max_subsegments.map(|max_subsegments| SegmentIndex {
+            max_subsegments,
+            ..SegmentIndex::default()
+        });
+        Ok(())
+    }
+
+    /// Write a `mfra` box listing the first sync sample of every track in
+    /// each fragment at the end of the file, so readers can seek to
+    /// fragments by time without parsing every `moof` box.
+    ///
+    /// The `mfra` box is written by [Mp4FragmentedWriter::write_end]. Must be
+    /// enabled before the first fragment is written.
+    pub fn set_random_access_index(&mut self, enabled: bool) -> Result<()> {
+        if self.fragments.sequence_number > 0 {
+            return Err(Error::InvalidData(
+                "cannot change the random access index after a fragment is written",
+            ));
+        }
+        self.fragments.random_access_index = if enabled { Some(Vec::new()) } else { None };
+        Ok(())
+    }
+
+    /// Consume self, returning the inner writer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Seek> Mp4FragmentedWriter<W> {
+    /// Write the pending samples and the `sidx` box enabled by
+    /// [Mp4FragmentedWriter::set_segment_index] into the space reserved for
+    /// it, returning to the end of the fragments. Fragments written
+    /// afterwards are not indexed.
+    pub fn write_segment_index(&mut self) -> Result<()> {
+        self.flush_fragment()?;
+        if let Some((offset, sidx)) = self.fragments.segment_index()? {
+            let end = self.writer.stream_position()?;
+            self.writer.seek(SeekFrom::Start(offset))?;
+            self.writer.write_all(&sidx)?;
+            self.writer.seek(SeekFrom::Start(end))?;
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Mp4FragmentedWriter<W> {
+    pub fn write_start(mut writer: W, config: &Mp4Config) -> Result<Self> {
+        check_fragmented_config(config)?;
+        let size = write_ftyp(&mut writer, config)?;
+        Ok(Self::new(writer, config, size))
+    }
+
+    /// Write the `moov` box of the init segment.
+    ///
+    /// This is done automatically before the first fragment, but can be called
+    /// explicitly to emit the init segment before any sample is available.
+    pub fn write_init_segment(&mut self) -> Result<()> {
+        self.fragments.write_init_segment(&mut self.writer)
+    }
+
+    pub fn write_sample(&mut self, track_id: u32, sample: &Mp4Sample) -> Result<()> {
+        if self.fragments.is_fragment_full(track_id, sample)? {
+            self.flush_fragment()?;
+        }
+        self.fragments.write_sample(track_id, sample)
+    }
+
+    /// Write the buffered samples of all tracks as a single `moof` + `mdat`
+    /// fragment. Does nothing if no samples are pending.
+    pub fn flush_fragment(&mut self) -> Result<()> {
+        self.fragments.write_fragment(&mut self.writer)
+    }
+
+    pub fn write_end(&mut self) -> Result<()> {
+        self.flush_fragment()?;
+        self.fragments.write_random_access_index(&mut self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncWrite + AsyncSeek + Unpin> Mp4FragmentedWriter<W> {
+    /// Asynchronous version of [Mp4FragmentedWriter::write_segment_index].
+    pub async fn write_segment_index_async(&mut self) -> Result<()> {
+        self.flush_fragment_async().await?;
+        if let Some((offset, sidx)) = self.fragments.segment_index()? {
+            let end = self.writer.stream_position().await?;
+            self.writer.seek(SeekFrom::Start(offset)).await?;
+            self.writer.write_all(&sidx).await?;
+            self.writer.seek(SeekFrom::Start(end)).await?;
+            self.writer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncWrite + Unpin> Mp4FragmentedWriter<W> {
+    /// Asynchronous version of [Mp4FragmentedWriter::write_start].
+    pub async fn write_start_async(mut writer: W, config: &Mp4Config) -> Result<Self> {
+        check_fragmented_config(config)?;
+        let mut buf = Vec::new();
+        let size = write_ftyp(&mut buf, config)?;
+        writer.write_all(&buf).await?;
+        Ok(Self::new(writer, config, size))
+    }
+
+    /// Asynchronous version of [Mp4FragmentedWriter::write_init_segment].
+    pub async fn write_init_segment_async(&mut self) -> Result<()> {
+        let mut buf = Vec::new();
+        self.fragments.write_init_segment(&mut buf)?;
+        self.writer.write_all(&buf).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [Mp4FragmentedWriter::write_sample].
+    pub async fn write_sample_async(&mut self, track_id: u32, sample: &Mp4Sample) -> Result<()> {
+        if self.fragments.is_fragment_full(track_id, sample)? {
+            self.flush_fragment_async().await?;
+        }
+        self.fragments.write_sample(track_id, sample)
+    }
+
+    /// Asynchronous version of [Mp4FragmentedWriter::flush_fragment].
+    ///
+    /// The fragment is serialized in memory before being written out.
+    pub async fn flush_fragment_async(&mut self) -> Result<()> {
+        let mut buf = Vec::new();
+        self.fragments.write_fragment(&mut buf)?;
+        self.writer.write_all(&buf).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [Mp4FragmentedWriter::write_end].
+    pub async fn write_end_async(&mut self) -> Result<()> {
+        self.flush_fragment_async().await?;
+        let mut buf = Vec::new();
+        self.fragments.write_random_access_index(&mut buf)?;
+        self.writer.write_all(&buf).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+fn check_fragmented_config(config: &Mp4Config) -> Result<()> {
+    if config.reserved_moov_size.is_some() {
+        return Err(Error::InvalidData(
+            "reserved moov size is not supported for fragmented files",
+        ));
+    }
+    Ok(())
+}
+
+fn write_ftyp<W: Write>(writer: &mut W, config: &Mp4Config) -> Result<u64> {
+    let ftyp = FtypBox {
+        major_brand: config.major_brand,
+        minor_version: config.minor_version,
+        compatible_brands: config.compatible_brands.clone(),
+    };
+    ftyp.write_box(writer)
+}
+
+impl FragmentsWriter {
+    fn reference_track_id(&self) -> u32 {
+        self.tracks
+            .iter()
+            .find(|track| track.is_video())
+            .map(|track| track.track_id())
+            .unwrap_or(1)
+    }
+
+    fn track(&self, track_id: u32) -> Result<&Mp4FragmentTrackWriter> {
+        if track_id == 0 {
+            return Err(Error::TrakNotFound(track_id));
+        }
+        self.tracks
+            .get(track_id as usize - 1)
+            .ok_or(Error::TrakNotFound(track_id))
+    }
+
+    fn is_fragment_full(&self, track_id: u32, sample: &Mp4Sample) -> Result<bool> {
+        let track = self.track(track_id)?;
+        let fragment_duration = match self.fragment_duration {
+            Some(duration) => duration,
+            None => return Ok(false),
+        };
+        if !sample.is_sync || track_id != self.reference_track_id() {
+            return Ok(false);
+        }
+        let pending =
+            Duration::from_micros(track.pending_duration() * 1_000_000 / track.timescale() as u64);
+        Ok(pending >= fragment_duration)
+    }
+
+    fn write_sample(&mut self, track_id: u32, sample: &Mp4Sample) -> Result<()> {
+        self.track(track_id)?;
+        self.tracks[track_id as usize - 1].write_sample(sample)
+    }
+
+    fn write_init_segment<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        if self.init_written {
+            return Ok(());
+        }
+
+        let mut moov = MoovBox::default();
+        moov.mvhd.timescale = self.timescale;
+        moov.mvhd.next_track_id = self.tracks.len() as u32 + 1;
+        for track in self.tracks.iter() {
+            moov.traks.push(track.trak().clone());
+        }
+        moov.mvex = Some(MvexBox {
+            mehd: None,
+            trexs: self.tracks.iter().map(|track| track.trex()).collect(),
+        });
+
+        moov.write_box(writer)?;
+        self.position += moov.box_size();
+
+        if let Some(ref mut index) = self.segment_index {
+            let size = index.slot_size();
+            BoxHeader::new(BoxType::FreeBox, size).write(writer)?;
+            writer.write_all(&vec![0u8; (size - HEADER_SIZE) as usize])?;
+            index.offset = self.position;
+            self.position += size;
+        }
+        writer.flush()?;
+
+        self.init_written = true;
+        Ok(())
+    }
+
+    fn write_fragment<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        self.write_init_segment(writer)?;
+
+        if self.tracks.iter().all(|track| track.is_empty()) {
+            return Ok(());
+        }
+
+        // Fragments without samples of the reference track are part of the
+        // previous subsegment. A new subsegment must fit in the space reserved
+        // for the sidx, which is checked before anything is written.
+        let subsegment = match self.segment_index {
+            Some(ref index) => {
+                let reference_track = self.track(self.reference_track_id())?;
+                let is_new_subsegment = !reference_track.is_empty() || index.references.is_empty();
+                if is_new_subsegment && index.references.len() >= index.max_subsegments as usize {
+                    return Err(Error::InvalidData(
+                        "more subsegments than reserved in the segment index",
+                    ));
+                }
+                let subsegment_duration = u32::try_from(reference_track.pending_duration())
+                    .map_err(|_| Error::InvalidData("fragment duration is too large"))?;
+                let starts_with_sync = reference_track.starts_with_sync();
+                let reference = SidxReference {
+                    reference_type: false,
+                    referenced_size: 0,
+                    subsegment_duration,
+                    starts_with_sap: starts_with_sync,
+                    sap_type: if starts_with_sync { 1 } else { 0 },
+                    sap_delta_time: 0,
+                };
+                Some((
+                    is_new_subsegment,
+                    reference,
+                    reference_track.earliest_presentation_time(),
+                ))
+            }
+            None => None,
+        };
+
+        let moof_offset = self.position;
+        if let Some(ref mut tfras) = self.random_access_index {
+            let tracks = self.tracks.iter().filter(|track| !track.is_empty());
+            for (traf_index, track) in tracks.enumerate() {
+                let (sample_number, time) = match track.first_sync_sample() {
+                    Some(sample) => sample,
+                    None => continue,
+                };
+                let entry = TfraEntry {
+                    time,
+                    moof_offset,
+                    traf_number: traf_index as u32 + 1,
+                    trun_number: 1,
+                    sample_number,
+                };
+                match tfras
+                    .iter_mut()
+                    .find(|tfra| tfra.track_id == track.track_id())
+                {
+                    Some(tfra) => tfra.entries.push(entry),
+                    None => tfras.push(TfraBox {
+                        track_id: track.track_id(),
+                        entries: vec![entry],
+                        ..TfraBox::default()
+                    }),
+                }
+            }
+        }
+
+        let fragment_size = self.write_moof_mdat(writer)?;
+        self.position += fragment_size;
+
+        let (index, (is_new_subsegment, mut reference, earliest_presentation_time)) =
+            match (self.segment_index.as_mut(), subsegment) {
+                (Some(index), Some(subsegment)) => (index, subsegment),
+                _ => return Ok(()),
+            };
+        let referenced_size = u32::try_from(fragment_size)
+            .map_err(|_| Error::InvalidData("fragment is too large"))?;
+        match index.references.last_mut() {
+            Some(last) if !is_new_subsegment => last.referenced_size += referenced_size,
+            _ => {
+                if index.references.is_empty() {
+                    index.earliest_presentation_time = earliest_presentation_time;
+                }
+                reference.referenced_size = referenced_size;
+                index.references.push(reference);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the pending samples as a `moof` + `mdat` fragment, returning
+    /// its size.
+    fn write_moof_mdat<W: Write>(&mut self, writer: &mut W) -> Result<u64> {
+        let mdat_data_size: u64 = self.tracks.iter().map(|track| track.data_size()).sum();
+        let mdat_header_size = if HEADER_SIZE + mdat_data_size > u32::MAX as u64 {
+            HEADER_SIZE + 8
+        } else {
+            HEADER_SIZE
+        };
+
+        self.sequence_number += 1;
+        let mut moof = MoofBox {
+            mfhd: MfhdBox {
+                sequence_number: self.sequence_number,
+                ..MfhdBox::default()
+            },
+            psshs: Vec::new(),
+            trafs: Vec::new(),
+        };
+        // The traf sizes do not depend on the data offsets, so lay out the
+        // moof once to learn its size and then fill in the real offsets.
+        for track in self.tracks.iter().filter(|track| !track.is_empty()) {
+            moof.trafs.push(track.traf(0, 0));
+        }
+        let mut data_offset = moof.box_size() + mdat_header_size;
+        let mut traf_offset =
+            moof.box_size() - moof.trafs.iter().map(|traf| traf.box_size()).sum::<u64>();
+        for (traf, track) in moof
+            .trafs
+            .iter_mut()
+            .zip(self.tracks.iter().filter(|track| !track.is_empty()))
+        {
+            let offset = i32::try_from(data_offset)
+                .map_err(|_| Error::InvalidData("fragment data offset is too large"))?;
+            *traf = track.traf(offset, traf_offset);
+            traf_offset += traf.box_size();
+            data_offset += track.data_size();
+        }
+        moof.write_box(writer)?;
+
+        BoxHeader::new(BoxType::MdatBox, mdat_header_size + mdat_data_size).write(writer)?;
+        for track in self.tracks.iter_mut() {
+            track.write_data(writer)?;
+        }
+        // Push the complete fragment downstream, e.g. to a socket.
+        writer.flush()?;
+
+        Ok(moof.box_size() + mdat_header_size + mdat_data_size)
+    }
+
+    /// Returns the position of the slot reserved for the `sidx` box and the
+    /// box to write into it, followed by a `free` box covering the unused
+    /// references, if the segment index is enabled.
+    fn segment_index(&mut self) -> Result<Option<(u64, Vec<u8>)>> {
+        let index = match self.segment_index.take() {
+            Some(index) if !index.references.is_empty() => index,
+            _ => return Ok(None),
+        };
+        let reference_track = self.track(self.reference_track_id())?;
+        let slot_size = index.slot_size();
+        let mut sidx = SidxBox {
+            version: 1,
+            flags: 0,
+            reference_id: reference_track.track_id(),
+            timescale: reference_track.timescale(),
+            earliest_presentation_time: index.earliest_presentation_time,
+            first_offset: 0,
+            references: index.references,
+        };
+        // The free box is part of the gap between the sidx box and the first
+        // fragment.
+        sidx.first_offset = slot_size - sidx.box_size();
+
+        let mut data = Vec::with_capacity(slot_size as usize);
+        sidx.write_box(&mut data)?;
+        if sidx.first_offset > 0 {
+            BoxHeader::new(BoxType::FreeBox, sidx.first_offset).write(&mut data)?;
+            data.resize(slot_size as usize, 0);
+        }
+        Ok(Some((index.offset, data)))
+    }
+
+    /// Write the `mfra` box, if the random access index is enabled.
+    fn write_random_access_index<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        let mut tfras = match self.random_access_index.take() {
+            Some(tfras) if !tfras.is_empty() => tfras,
+            _ => return Ok(()),
+        };
+        for tfra in tfras.iter_mut() {
+            if tfra
+                .entries
+                .iter()
+                .any(|entry| entry.time > u32::MAX as u64 || entry.moof_offset > u32::MAX as u64)
+            {
+                tfra.version = 1;
+            }
+            tfra.fit_length_sizes();
+        }
+        let mut mfra = MfraBox {
+            tfras,
+            mfro: MfroBox::default(),
+        };
+        mfra.mfro.parent_size = u32::try_from(mfra.box_size())
+            .map_err(|_| Error::InvalidData("mfra box is too large"))?;
+        self.position += mfra.write_box(writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_segment_index_full() {
+        let config = Mp4Config {
+            major_brand: str::parse("iso6").unwrap(),
+            minor_version: 0,
+            compatible_brands: vec![str::parse("iso6").unwrap()],
+            timescale: 1000,
+            reserved_moov_size: None,
+            interleave_duration: None,
+        };
+        let mut writer =
+            Mp4FragmentedWriter::write_start(Cursor::new(Vec::new()), &config).unwrap();
+        writer.set_segment_index(Some(1)).unwrap();
+        writer.set_random_access_index(true).unwrap();
+        writer
+            .add_track(&TrackConfig::from(AacConfig::default()))
+            .unwrap();
+
+        for n in 0..2u32 {
+            let sample = Mp4Sample {
+                start_time: n as u64 * 40,
+                duration: 40,
+                rendering_offset: 0,
+                is_sync: true,
+                sample_description_index: 1,
+                sample_flags: None,
+                bytes: Bytes::from(vec![n as u8; 10]),
+            };
+            writer.write_sample(1, &sample).unwrap();
+            assert_eq!(writer.flush_fragment().is_ok(), n == 0);
+        }
+        let tfras = writer.fragments.random_access_index.as_ref().unwrap();
+        assert_eq!(tfras[0].entries.len(), 1);
+        let position = writer.fragments.position;
+        assert_eq!(writer.into_writer().into_inner().len() as u64, position);
+    }
+}
@@ -1,9 +1,11 @@
 use mp4::{
-    AudioObjectType, AvcProfile, ChannelConfig, MediaType, Metadata, Mp4Reader, SampleFreqIndex,
-    TrackType,
+    AudioObjectType, AvcProfile, ChannelConfig, MediaType, Metadata, Mp4Box, Mp4Reader, ReadBox,
+    SampleFreqIndex, TrackType, WriteBox,
 };
+use std::cell::Cell;
 use std::fs::{self, File};
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
+use std::rc::Rc;
 use std::time::Duration;
 
 #[test]
@@ -43,6 +45,8 @@ fn test_read_mp4() {
             duration: 512,
             rendering_offset: 0,
             is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
             bytes: mp4::Bytes::from(vec![0x0u8; 751]),
         }
     );
@@ -60,6 +64,8 @@ fn test_read_mp4() {
             duration: 1024,
             rendering_offset: 0,
             is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
             bytes: mp4::Bytes::from(vec![0x0u8; 179]),
         }
     );
@@ -72,6 +78,8 @@ fn test_read_mp4() {
             duration: 1024,
             rendering_offset: 0,
             is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
             bytes: mp4::Bytes::from(vec![0x0u8; 180]),
         }
     );
@@ -84,6 +92,8 @@ fn test_read_mp4() {
             duration: 896,
             rendering_offset: 0,
             is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
             bytes: mp4::Bytes::from(vec![0x0u8; 160]),
         }
     );
@@ -132,13 +142,12 @@ fn test_read_extended_audio_object_type() {
     );
     assert_eq!(
         track
-            .trak
+            .trak()
             .mdia
             .minf
             .stbl
             .stsd
-            .mp4a
-            .as_ref()
+            .mp4a()
             .unwrap()
             .esds
             .as_ref()
@@ -203,9 +212,2658 @@ fn test_read_fragments() {
             duration: 512,
             rendering_offset: 0,
             is_sync: true,
+            sample_description_index: 1,
+            sample_flags: Some(mp4::SampleFlags {
+                depends_on: 2,
+                ..Default::default()
+            }),
             bytes: mp4::Bytes::from(vec![0x0u8; 751]),
         }
     );
     let eos = mp4_fragment.read_sample(1, 2);
     assert!(eos.is_err());
 }
+
+#[test]
+fn test_write_fragments() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso5").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![
+            str::parse("iso5").unwrap(),
+            str::parse("iso6").unwrap(),
+            str::parse("mp41").unwrap(),
+        ],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4FragmentedWriter::write_start(data, &config).unwrap();
+
+    let media_conf = mp4::MediaConfig::AvcConfig(mp4::AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0d, 0xac, 0xd9, 0x41, 0x41, 0xfa, 0x10],
+        pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb, 0x22, 0xc0],
+    });
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+
+    for fragment in 0..3u32 {
+        for i in 0..4u32 {
+            let n = fragment * 4 + i;
+            let sample = mp4::Mp4Sample {
+                start_time: n as u64 * 40,
+                duration: 40,
+                rendering_offset: if i == 0 { 0 } else { 40 },
+                is_sync: i == 0,
+                sample_description_index: 1,
+                sample_flags: None,
+                bytes: mp4::Bytes::from(vec![n as u8; 100 + n as usize]),
+            };
+            writer.write_sample(1, &sample).unwrap();
+        }
+        for i in 0..2u32 {
+            let n = fragment * 2 + i;
+            let sample = mp4::Mp4Sample {
+                start_time: n as u64 * 80,
+                duration: 80,
+                rendering_offset: 0,
+                is_sync: true,
+                sample_description_index: 1,
+                sample_flags: None,
+                bytes: mp4::Bytes::from(vec![0xff - n as u8; 10 + n as usize]),
+            };
+            writer.write_sample(2, &sample).unwrap();
+        }
+        writer.flush_fragment().unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data: Vec<u8> = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    assert_eq!(mp4.moofs.len(), 3);
+    assert_eq!(mp4.tracks().len(), 2);
+    assert_eq!(mp4.sample_count(1).unwrap(), 12);
+    assert_eq!(mp4.sample_count(2).unwrap(), 6);
+
+    for n in 0..12u32 {
+        let sample = mp4.read_sample(1, n + 1).unwrap().unwrap();
+        assert_eq!(sample.start_time, n as u64 * 40);
+        assert_eq!(sample.duration, 40);
+        assert_eq!(sample.rendering_offset, if n % 4 == 0 { 0 } else { 40 });
+        assert_eq!(
+            sample.bytes,
+            mp4::Bytes::from(vec![n as u8; 100 + n as usize])
+        );
+    }
+    for n in 0..6u32 {
+        let sample = mp4.read_sample(2, n + 1).unwrap().unwrap();
+        assert_eq!(sample.start_time, n as u64 * 80);
+        assert_eq!(sample.duration, 80);
+        assert_eq!(
+            sample.bytes,
+            mp4::Bytes::from(vec![0xff - n as u8; 10 + n as usize])
+        );
+    }
+}
+
+#[test]
+fn test_write_fragments_defaults() {
+    let config = mp4::Mp4Config {
+        reserved_moov_size: Some(1024),
+        ..Default::default()
+    };
+    assert!(mp4::Mp4FragmentedWriter::write_start(Vec::<u8>::new(), &config).is_err());
+
+    let config = mp4::Mp4Config::default();
+    let mut writer = mp4::Mp4FragmentedWriter::write_start(Vec::<u8>::new(), &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    let sample = mp4::Mp4Sample {
+        duration: 1024,
+        is_sync: true,
+        bytes: mp4::Bytes::from_static(&[1, 2, 3]),
+        ..Default::default()
+    };
+    writer.write_sample(1, &sample).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.ftyp.major_brand, str::parse("isom").unwrap());
+    let read = mp4.read_sample(1, 1).unwrap().unwrap();
+    assert_eq!(read.sample_description_index, 1);
+    assert_eq!(read.bytes, sample.bytes);
+}
+
+/// A writer that can only be appended to, like a pipe or a socket.
+struct StreamWriter(Vec<u8>);
+
+impl std::io::Write for StreamWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_write_fragments_streaming() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso5").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso5").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(StreamWriter(Vec::new()), &config).unwrap();
+    writer.set_fragment_duration(Some(Duration::from_secs(1)));
+
+    let media_conf = mp4::MediaConfig::AvcConfig(mp4::AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0d, 0xac, 0xd9, 0x41, 0x41, 0xfa, 0x10],
+        pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb, 0x22, 0xc0],
+    });
+    let mut track_config = mp4::TrackConfig::from(media_conf);
+    track_config.timescale = 0;
+    assert!(writer.add_track(&track_config).is_err());
+    track_config.timescale = 1000;
+    writer.add_track(&track_config).unwrap();
+
+    // 4 seconds at 25 fps with a sync sample every 10 frames.
+    for n in 0..100u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: n as u64 * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: n % 10 == 0,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![n as u8; 10]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().0;
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    // A fragment is cut at the first sync sample after each second.
+    assert_eq!(mp4.moofs.len(), 4);
+    assert_eq!(mp4.sample_count(1).unwrap(), 100);
+    let sample = mp4.read_sample(1, 51).unwrap().unwrap();
+    assert_eq!(sample.start_time, 2000);
+    assert_eq!(sample.bytes, mp4::Bytes::from(vec![50u8; 10]));
+}
+
+#[test]
+fn test_write_segment_index() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("dash").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap(), str::parse("msix").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    writer.set_fragment_duration(Some(Duration::from_secs(1)));
+    // Two of the reserved references are left unused.
+    writer.set_segment_index(Some(6)).unwrap();
+
+    let media_conf = mp4::MediaConfig::AvcConfig(mp4::AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0d, 0xac, 0xd9, 0x41, 0x41, 0xfa, 0x10],
+        pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb, 0x22, 0xc0],
+    });
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+
+    // Fragments are cut at the sync samples 30, 60 and 90.
+    for n in 0..100u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: n as u64 * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: n % 10 == 0,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![n as u8; 10]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    assert!(writer.set_segment_index(None).is_err());
+    writer.write_end().unwrap();
+    writer.write_segment_index().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut expected = vec![
+        mp4::BoxType::FtypBox,
+        mp4::BoxType::MoovBox,
+        mp4::BoxType::SidxBox,
+        mp4::BoxType::FreeBox,
+    ];
+    for _ in 0..4 {
+        expected.extend([mp4::BoxType::MoofBox, mp4::BoxType::MdatBox]);
+    }
+    assert_eq!(top_level_boxes(&data), expected);
+
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    assert_eq!(mp4.moofs.len(), 4);
+    assert_eq!(mp4.sidxs.len(), 1);
+    assert_eq!(mp4.sample_count(1).unwrap(), 100);
+
+    let mut mp4 = Mp4Reader::read_indexed_header(Cursor::new(data), size).unwrap();
+    assert!(mp4.moofs.is_empty());
+    assert_eq!(mp4.sidxs[0].reference_id, 1);
+    assert_eq!(mp4.sidxs[0].first_offset, 24);
+    assert_eq!(mp4.sidxs[0].timescale, 1000);
+    let segments = mp4.segments(1);
+    assert_eq!(segments.len(), 4);
+    assert_eq!(segments[3].duration, Duration::from_millis(400));
+
+    let segment = mp4
+        .segment_at_time(1, Duration::from_millis(2500))
+        .unwrap()
+        .unwrap();
+    assert_eq!(segment.start_time, Duration::from_millis(2400));
+    assert_eq!(segment.duration, Duration::from_millis(1200));
+    assert!(segment.starts_with_sap);
+    assert_eq!(segment, segments[2]);
+    assert_eq!(
+        mp4.segment_at_time(1, Duration::from_secs(4)).unwrap(),
+        None
+    );
+    assert!(mp4.segment_at_time(2, Duration::ZERO).is_err());
+
+    let mut fragment = mp4.read_segment(&segment).unwrap();
+    assert_eq!(fragment.sample_count(1).unwrap(), 30);
+    let sample = fragment.read_sample(1, 1).unwrap().unwrap();
+    assert_eq!(sample.start_time, 2400);
+    assert!(sample.is_sync);
+    assert_eq!(sample.bytes, mp4::Bytes::from(vec![60u8; 10]));
+
+    // Fragments beyond the reserved references are rejected.
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    writer.set_segment_index(Some(1)).unwrap();
+    writer
+        .add_track(&mp4::TrackConfig::from(mp4::AacConfig::default()))
+        .unwrap();
+    for n in 0..2u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: n as u64 * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![n as u8; 10]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+        assert_eq!(writer.flush_fragment().is_ok(), n == 0);
+    }
+}
+
+#[test]
+fn test_read_consecutive_segment_indexes() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("dash").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap(), str::parse("msix").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    writer.set_segment_index(Some(2)).unwrap();
+    for _ in 0..2 {
+        writer
+            .add_track(&mp4::TrackConfig::from(mp4::AacConfig::default()))
+            .unwrap();
+    }
+    for fragment in 0..2u32 {
+        for n in fragment * 10..fragment * 10 + 10 {
+            for track_id in 1..=2 {
+                let sample = mp4::Mp4Sample {
+                    start_time: n as u64 * 100,
+                    duration: 100,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    sample_description_index: 1,
+                    sample_flags: None,
+                    bytes: mp4::Bytes::from(vec![track_id as u8; 10]),
+                };
+                writer.write_sample(track_id, &sample).unwrap();
+            }
+        }
+        writer.flush_fragment().unwrap();
+    }
+    writer.write_end().unwrap();
+    writer.write_segment_index().unwrap();
+    let data = writer.into_writer().into_inner();
+
+    // Put a sidx for each track back to back, as in muxed on-demand files.
+    let mut reader = Cursor::new(&data);
+    let header = loop {
+        let header = mp4::BoxHeader::read(&mut reader).unwrap();
+        if header.name == mp4::BoxType::SidxBox {
+            break header;
+        }
+        reader.set_position(reader.position() - 8 + header.size);
+    };
+    let offset = reader.position() as usize - 8;
+    let mut first = mp4::SidxBox::read_box(&mut reader, header.size).unwrap();
+    let second = mp4::SidxBox {
+        reference_id: 2,
+        ..first.clone()
+    };
+    first.first_offset += second.box_size();
+    let mut muxed = data[..offset].to_vec();
+    first.write_box(&mut muxed).unwrap();
+    second.write_box(&mut muxed).unwrap();
+    muxed.extend_from_slice(&data[offset + header.size as usize..]);
+
+    let size = muxed.len() as u64;
+    let mut mp4 = Mp4Reader::read_indexed_header(Cursor::new(muxed), size).unwrap();
+    assert!(mp4.moofs.is_empty());
+    assert_eq!(mp4.sidxs.len(), 2);
+    for track_id in 1..=2 {
+        let segments = mp4.segments(track_id);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments, mp4.segments(1));
+        let segment = mp4
+            .segment_at_time(track_id, Duration::from_millis(1500))
+            .unwrap()
+            .unwrap();
+        assert_eq!(segment.start_time, Duration::from_secs(1));
+        let mut fragment = mp4.read_segment(&segment).unwrap();
+        let sample = fragment.read_sample(track_id, 1).unwrap().unwrap();
+        assert_eq!(sample.start_time, 1000);
+        assert_eq!(sample.bytes, mp4::Bytes::from(vec![track_id as u8; 10]));
+    }
+}
+
+fn write_random_access_fragments(segment_index: bool) -> Vec<u8> {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    writer.set_fragment_duration(Some(Duration::from_secs(1)));
+    writer
+        .set_segment_index(segment_index.then_some(4))
+        .unwrap();
+    writer.set_random_access_index(true).unwrap();
+
+    let media_conf = mp4::MediaConfig::AvcConfig(mp4::AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0d, 0xac, 0xd9, 0x41, 0x41, 0xfa, 0x10],
+        pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb, 0x22, 0xc0],
+    });
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+
+    // Fragments are cut at the video sync samples 30, 60 and 90.
+    for n in 0..100u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: n as u64 * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: n % 10 == 0,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![n as u8; 10]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+        if n % 2 == 0 {
+            let sample = mp4::Mp4Sample {
+                start_time: n as u64 * 40,
+                duration: 80,
+                rendering_offset: 0,
+                is_sync: true,
+                sample_description_index: 1,
+                sample_flags: None,
+                bytes: mp4::Bytes::from(vec![0xff - n as u8; 5]),
+            };
+            writer.write_sample(2, &sample).unwrap();
+        }
+    }
+    assert!(writer.set_random_access_index(false).is_err());
+    writer.write_end().unwrap();
+    writer.write_segment_index().unwrap();
+    writer.into_writer().into_inner()
+}
+
+#[test]
+fn test_write_random_access_index() {
+    for segment_index in [false, true] {
+        let data = write_random_access_fragments(segment_index);
+        let size = data.len() as u64;
+        let boxes = top_level_boxes(&data);
+        assert_eq!(boxes.last(), Some(&mp4::BoxType::MfraBox));
+        assert_eq!(boxes.contains(&mp4::BoxType::SidxBox), segment_index);
+
+        let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+        assert_eq!(mp4.moofs.len(), 4);
+        assert_eq!(mp4.sample_count(1).unwrap(), 100);
+        assert_eq!(mp4.sample_count(2).unwrap(), 50);
+        let mfra = mp4.mfra.unwrap();
+        assert_eq!(mfra.tfras.len(), 2);
+        assert_eq!(mfra.mfro.parent_size as u64, mfra.box_size());
+
+        let mut mp4 = Mp4Reader::read_indexed_header(Cursor::new(data), size).unwrap();
+        assert!(mp4.moofs.is_empty());
+        let tfra = &mp4.mfra.as_ref().unwrap().tfras[0];
+        assert_eq!(tfra.track_id, 1);
+        assert_eq!(tfra.entries.len(), 4);
+
+        let point = mp4
+            .random_access_point_before(1, Duration::from_millis(2500))
+            .unwrap()
+            .unwrap();
+        assert_eq!(point.time, Duration::from_millis(2400));
+        assert_eq!((point.traf_number, point.sample_number), (1, 1));
+        let mut fragment = mp4.read_fragment_at(point.moof_offset).unwrap();
+        assert_eq!(fragment.moofs.len(), 1);
+        assert_eq!(fragment.sample_count(1).unwrap(), 30);
+        let sample = fragment.read_sample(1, 1).unwrap().unwrap();
+        assert_eq!(sample.start_time, 2400);
+        assert!(sample.is_sync);
+        assert_eq!(sample.bytes, mp4::Bytes::from(vec![60u8; 10]));
+
+        let point = mp4
+            .random_access_point_before(2, Duration::from_millis(3999))
+            .unwrap()
+            .unwrap();
+        assert_eq!(point.time, Duration::from_millis(3600));
+        assert_eq!((point.traf_number, point.sample_number), (2, 1));
+        let mut fragment = mp4.read_fragment_at(point.moof_offset).unwrap();
+        let sample = fragment.read_sample(2, 1).unwrap().unwrap();
+        assert_eq!(sample.bytes, mp4::Bytes::from(vec![0xff - 90u8; 5]));
+
+        assert!(mp4
+            .random_access_point_before(1, Duration::ZERO)
+            .unwrap()
+            .is_some());
+        assert!(mp4.random_access_point_before(3, Duration::ZERO).is_err());
+    }
+
+    // A track with a timescale of 0 is an error rather than a panic.
+    let mut data = write_random_access_fragments(false);
+    let mdhd = data.windows(4).position(|w| w == b"mdhd").unwrap() - 4;
+    assert_eq!(data[mdhd + 8], 0); // version
+    data[mdhd + 20..mdhd + 24].copy_from_slice(&[0; 4]);
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_indexed_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.tracks()[&1].timescale(), 0);
+    assert!(matches!(
+        mp4.random_access_point_before(1, Duration::ZERO),
+        Err(mp4::Error::InvalidData(_))
+    ));
+}
+
+fn write_faststart(reserved_moov_size: Option<u64>) -> Vec<u8> {
+    let (result, data) = try_write_faststart(reserved_moov_size);
+    result.unwrap();
+    data
+}
+
+fn try_write_faststart(reserved_moov_size: Option<u64>) -> (mp4::Result<()>, Vec<u8>) {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size,
+        interleave_duration: None,
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    for n in 0..50u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: n as u64 * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![n as u8; 20]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    let result = writer.write_end();
+    (result, writer.into_writer().into_inner())
+}
+
+fn top_level_boxes(data: &[u8]) -> Vec<mp4::BoxType> {
+    let mut reader = Cursor::new(data);
+    let mut boxes = Vec::new();
+    while (reader.position() as usize) < data.len() {
+        let start = reader.position();
+        let header = mp4::BoxHeader::read(&mut reader).unwrap();
+        boxes.push(header.name);
+        reader.set_position(start + header.size);
+    }
+    boxes
+}
+
+#[test]
+fn test_write_faststart() {
+    use mp4::BoxType::*;
+
+    let data = write_faststart(Some(4096));
+    assert_eq!(
+        top_level_boxes(&data),
+        vec![FtypBox, MoovBox, FreeBox, MdatBox]
+    );
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), 50);
+    let sample = mp4.read_sample(1, 50).unwrap().unwrap();
+    assert_eq!(sample.bytes, mp4::Bytes::from(vec![49u8; 20]));
+
+    // Falls back to moov after mdat if the reserved space is too small, and
+    // reports it.
+    let (result, data) = try_write_faststart(Some(64));
+    assert!(matches!(result, Err(mp4::Error::MoovTooLarge(size)) if size > 64));
+    assert_eq!(
+        top_level_boxes(&data),
+        vec![FtypBox, FreeBox, MdatBox, MoovBox]
+    );
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let sample = mp4.read_sample(1, 50).unwrap().unwrap();
+    assert_eq!(sample.bytes, mp4::Bytes::from(vec![49u8; 20]));
+}
+
+#[test]
+fn test_edit_metadata_in_place() {
+    use mp4::BoxType::*;
+
+    let data = write_faststart(Some(4096));
+    let size = data.len() as u64;
+    let mut editor = mp4::Mp4MetadataEditor::read_header(Cursor::new(data), size).unwrap();
+    assert!(editor.metadata().title().is_none());
+    editor.set_title("Edited").unwrap();
+    editor.set_year(2024).unwrap();
+    editor.save().unwrap();
+    assert_eq!(editor.size(), size);
+
+    let data = editor.into_inner().into_inner();
+    assert_eq!(data.len() as u64, size);
+    assert_eq!(
+        top_level_boxes(&data),
+        vec![FtypBox, MoovBox, FreeBox, MdatBox]
+    );
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.metadata().title(), Some("Edited".into()));
+    assert_eq!(mp4.metadata().year(), Some(2024));
+    let sample = mp4.read_sample(1, 50).unwrap().unwrap();
+    assert_eq!(sample.bytes, mp4::Bytes::from(vec![49u8; 20]));
+
+    // A shorter title leaves the other tags alone and pads the rest with free.
+    let data = fs::read("tests/samples/big_buck_bunny_metadata.m4v").unwrap();
+    let size = data.len() as u64;
+    let mut editor = mp4::Mp4MetadataEditor::read_header(Cursor::new(data), size).unwrap();
+    editor.set_title("Bunny").unwrap();
+    editor.save().unwrap();
+    let data = editor.into_inner().into_inner();
+    assert_eq!(data.len() as u64, size);
+    assert_eq!(
+        top_level_boxes(&data),
+        vec![FtypBox, MoovBox, FreeBox, MdatBox]
+    );
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let want = get_reader("tests/samples/big_buck_bunny_metadata.m4v");
+    assert_eq!(mp4.metadata().title(), Some("Bunny".into()));
+    assert_eq!(mp4.metadata().year(), want.metadata().year());
+    assert_eq!(mp4.metadata().summary(), want.metadata().summary());
+    assert_eq!(mp4.metadata().poster(), want.metadata().poster());
+    assert_eq!(mp4.moov.traks, want.moov.traks);
+}
+
+#[test]
+fn test_edit_metadata_move_moov() {
+    use mp4::BoxType::*;
+
+    let data = fs::read("tests/samples/minimal.mp4").unwrap();
+    let size = data.len() as u64;
+    let mut editor = mp4::Mp4MetadataEditor::read_header(Cursor::new(data), size).unwrap();
+    editor.set_poster(vec![0xff; 4096]).unwrap();
+    editor.save().unwrap();
+    assert!(editor.size() > size);
+
+    // The old moov and the free box after it become a single free box.
+    let data = editor.into_inner().into_inner();
+    assert_eq!(
+        top_level_boxes(&data),
+        vec![FtypBox, FreeBox, MdatBox, MoovBox]
+    );
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    let mut want = get_reader("tests/samples/minimal.mp4");
+    assert_eq!(mp4.metadata().poster(), Some(&[0xffu8; 4096][..]));
+    for track_id in [1, 2] {
+        let sample_count = want.sample_count(track_id).unwrap();
+        assert_eq!(mp4.sample_count(track_id).unwrap(), sample_count);
+        for sample_id in 1..=sample_count {
+            assert_eq!(
+                mp4.read_sample(track_id, sample_id).unwrap(),
+                want.read_sample(track_id, sample_id).unwrap()
+            );
+        }
+    }
+
+    // Once moov is last in the file it can shrink in place.
+    let mut editor = mp4::Mp4MetadataEditor::read_header(Cursor::new(data), size).unwrap();
+    editor.remove(mp4::MetadataKey::Poster).unwrap();
+    editor.save().unwrap();
+    assert_eq!(editor.size(), size);
+    let data = editor.into_inner().into_inner();
+    assert_eq!(
+        top_level_boxes(&data),
+        vec![FtypBox, FreeBox, MdatBox, MoovBox, FreeBox]
+    );
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert!(mp4.metadata().poster().is_none());
+}
+
+#[test]
+fn test_edit_moov_meta() {
+    let data = write_faststart(Some(4096));
+    let size = data.len() as u64;
+    let mut editor = mp4::Mp4MetadataEditor::read_header(Cursor::new(data), size).unwrap();
+    assert!(editor.meta().is_none());
+    let mut ilst = mp4::IlstBox::default();
+    ilst.items.insert(
+        mp4::MetadataKey::Title,
+        mp4::IlstItemBox {
+            data: mp4::DataBox {
+                data: b"Moov meta".to_vec(),
+                data_type: mp4::DataType::Text,
+            },
+        },
+    );
+    let meta = mp4::MetaBox::Mdir { ilst: Some(ilst) };
+    *editor.meta_mut() = Some(meta.clone());
+    editor.save().unwrap();
+
+    // The udta helpers leave the moov level meta alone.
+    editor.set_title("Udta meta").unwrap();
+    editor.save().unwrap();
+    assert_eq!(editor.meta(), Some(&meta));
+
+    let data = editor.into_inner().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    assert_eq!(mp4.moov.meta, Some(meta));
+    assert_eq!(mp4.metadata().title(), Some("Udta meta".into()));
+
+    let mut editor = mp4::Mp4MetadataEditor::read_header(Cursor::new(data), size).unwrap();
+    *editor.meta_mut() = None;
+    editor.save().unwrap();
+    let data = editor.into_inner().into_inner();
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.moov.meta, None);
+    assert_eq!(mp4.metadata().title(), Some("Udta meta".into()));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_read_mp4_async() {
+    let f = tokio::fs::File::open("tests/samples/minimal.mp4")
+        .await
+        .unwrap();
+    let size = f.metadata().await.unwrap().len();
+    let mut mp4 = Mp4Reader::read_header_async(f, size).await.unwrap();
+    let mut sync_mp4 = get_reader("tests/samples/minimal.mp4");
+
+    assert_eq!(mp4.size(), sync_mp4.size());
+    assert_eq!(mp4.moov, sync_mp4.moov);
+    assert_eq!(mp4.unknown_boxes, sync_mp4.unknown_boxes);
+    for track_id in [1, 2] {
+        let sample_count = mp4.sample_count(track_id).unwrap();
+        for sample_id in 1..=sample_count {
+            let sample = mp4.read_sample_async(track_id, sample_id).await.unwrap();
+            let sync_sample = sync_mp4.read_sample(track_id, sample_id).unwrap();
+            assert_eq!(sample, sync_sample);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_write_fragments_async() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso5").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso5").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start_async(Cursor::new(Vec::<u8>::new()), &config)
+            .await
+            .unwrap();
+    writer.set_fragment_duration(Some(Duration::from_secs(1)));
+    writer.set_segment_index(Some(4)).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+
+    for n in 0..100u32 {
+        let sample = mp4::Mp4Sample {
+            start_time: n as u64 * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![n as u8; 10]),
+        };
+        writer.write_sample_async(1, &sample).await.unwrap();
+    }
+    writer.write_end_async().await.unwrap();
+    writer.write_segment_index_async().await.unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_indexed_header_async(Cursor::new(data.clone()), size)
+        .await
+        .unwrap();
+    assert!(mp4.moofs.is_empty());
+    assert_eq!(mp4.segments(1).len(), 4);
+
+    let mut mp4 = Mp4Reader::read_header_async(Cursor::new(data), size)
+        .await
+        .unwrap();
+    assert_eq!(mp4.moofs.len(), 4);
+    assert_eq!(mp4.sample_count(1).unwrap(), 100);
+    let sample = mp4.read_sample_async(1, 100).await.unwrap().unwrap();
+    assert_eq!(sample.start_time, 3960);
+    assert_eq!(sample.bytes, mp4::Bytes::from(vec![99u8; 10]));
+}
+
+#[test]
+fn test_preserve_unknown_boxes() {
+    let mp4 = get_reader("tests/samples/extended_audio_object_type.mp4");
+    let iods = mp4::BoxType::from(0x696f6473);
+    assert_eq!(mp4.moov.unknown_boxes.len(), 1);
+    assert_eq!(mp4.moov.unknown_boxes[0].box_type, iods);
+
+    let mut buf = Vec::new();
+    mp4.moov.write_box(&mut buf).unwrap();
+    assert_eq!(buf.len() as u64, mp4.moov.box_size());
+    let mut reader = Cursor::new(&buf);
+    let header = mp4::BoxHeader::read(&mut reader).unwrap();
+    let moov = mp4::MoovBox::read_box(&mut reader, header.size).unwrap();
+    assert_eq!(moov.unknown_boxes, mp4.moov.unknown_boxes);
+
+    let mp4 = get_reader("tests/samples/big_buck_bunny_metadata.m4v");
+    let stbl = &mp4.tracks().get(&2).unwrap().trak().mdia.minf.stbl;
+    let types: Vec<String> = stbl
+        .unknown_boxes
+        .iter()
+        .map(|b| b.box_type.to_string())
+        .collect();
+    assert_eq!(types, vec!["sgpd", "sbgp"]);
+}
+
+#[test]
+fn test_write_unknown_boxes() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: Some(4096),
+        interleave_duration: None,
+    };
+    let mut writer = mp4::Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let uuid = mp4::UnknownBox::new(mp4::BoxType::from(0x75756964), vec![7; 20]);
+    for unknown in mp4.unknown_boxes.iter() {
+        writer.add_unknown_box(unknown.clone());
+    }
+    writer.add_unknown_box(uuid.clone());
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let copy = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(copy.unknown_boxes.last(), Some(&uuid));
+    assert_eq!(copy.unknown_boxes.len(), mp4.unknown_boxes.len() + 1);
+}
+
+#[test]
+fn test_write_hvc1() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let media_conf = mp4::MediaConfig::HevcConfig(mp4::HevcConfig {
+        width: 640,
+        height: 480,
+        box_type: mp4::HevcBoxType::Hvc1,
+        general_profile_idc: 1,
+        general_level_idc: 93,
+        vid_param_set: vec![0x40, 0x01, 0x0c, 0x01],
+        seq_param_set: vec![0x42, 0x01, 0x01, 0x01, 0x60],
+        pic_param_set: vec![0x44, 0x01, 0xc1, 0x72],
+        ..Default::default()
+    });
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::H265);
+    assert_eq!(track.box_type().unwrap().to_string(), "hvc1");
+    assert_eq!(track.hevc_box_type().unwrap(), mp4::HevcBoxType::Hvc1);
+    let hev1 = track.trak().mdia.minf.stbl.stsd.hev1().unwrap();
+    assert_eq!(hev1.box_type, mp4::HevcBoxType::Hvc1);
+    assert_eq!(
+        track.video_parameter_set().unwrap(),
+        [0x40, 0x01, 0x0c, 0x01]
+    );
+    assert_eq!(
+        track.sequence_parameter_set().unwrap(),
+        [0x42, 0x01, 0x01, 0x01, 0x60]
+    );
+    assert_eq!(
+        track.picture_parameter_set().unwrap(),
+        [0x44, 0x01, 0xc1, 0x72]
+    );
+
+    let hevc_config = track.hevc_config().unwrap();
+    assert_eq!(hevc_config.general_profile_idc, 1);
+    assert_eq!(hevc_config.general_level_idc, 93);
+    assert_eq!(hevc_config.width, 640);
+
+    // Parameter sets that don't fit in hvcC are rejected when adding the
+    // track.
+    let mut writer = mp4::Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let media_conf = mp4::MediaConfig::HevcConfig(mp4::HevcConfig {
+        pic_param_set: vec![0x44; 0x10000],
+        ..Default::default()
+    });
+    assert!(writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .is_err());
+}
+
+#[test]
+fn test_write_av1() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("av01").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let sequence_header = vec![
+        0x0a, 0x0b, 0x00, 0x00, 0x00, 0x42, 0xab, 0xbf, 0xc3, 0x71, 0xab,
+    ];
+    let media_conf = mp4::MediaConfig::Av1Config(mp4::Av1Config {
+        width: 1280,
+        height: 720,
+        seq_profile: 0,
+        seq_level_idx_0: 5,
+        bit_depth: 10,
+        config_obus: sequence_header.clone(),
+        ..Default::default()
+    });
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::AV1);
+    assert_eq!(track.box_type().unwrap().to_string(), "av01");
+    assert_eq!(track.width(), 1280);
+    assert_eq!(track.av1_profile().unwrap(), 0);
+    assert_eq!(track.av1_level().unwrap(), 5);
+    assert_eq!(track.av1_bit_depth().unwrap(), 10);
+    assert_eq!(track.av1_config_obus().unwrap(), sequence_header.as_slice());
+}
+
+#[test]
+fn test_write_opus() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("opus").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let media_conf = mp4::MediaConfig::OpusConfig(mp4::OpusConfig {
+        channel_count: 2,
+        pre_skip: 312,
+        input_sample_rate: 44100,
+        ..Default::default()
+    });
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    for i in 0..3u64 {
+        let sample = mp4::Mp4Sample {
+            start_time: i * 960,
+            duration: 960,
+            rendering_offset: 0,
+            is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![0xfc; 40]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::OPUS);
+    assert_eq!(track.track_type().unwrap(), TrackType::Audio);
+    assert_eq!(track.box_type().unwrap().to_string(), "Opus");
+    assert_eq!(track.timescale(), 48000);
+    assert_eq!(track.opus_pre_skip().unwrap(), 312);
+    assert_eq!(track.opus_input_sample_rate().unwrap(), 44100);
+    assert_eq!(track.opus_channel_mapping_family().unwrap(), 0);
+    assert_eq!(track.sample_count(), 3);
+
+    let sample = mp4.read_sample(1, 2).unwrap().unwrap();
+    assert_eq!(sample.start_time, 960);
+    assert_eq!(sample.bytes.len(), 40);
+}
+
+#[test]
+fn test_write_opus_rejects_missing_channel_mapping() {
+    let config = mp4::Mp4Config::default();
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let media_conf = mp4::MediaConfig::OpusConfig(mp4::OpusConfig {
+        channel_mapping_family: 1,
+        channel_mapping_table: None,
+        ..Default::default()
+    });
+    assert!(writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .is_err());
+}
+
+#[test]
+fn test_read_sample_description_index() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let hevc_config = mp4::HevcConfig {
+        width: 640,
+        height: 480,
+        ..Default::default()
+    };
+    writer
+        .add_track(&mp4::TrackConfig::from(hevc_config.clone()))
+        .unwrap();
+    for i in 0..4u64 {
+        // One sample per chunk.
+        let sample = mp4::Mp4Sample {
+            start_time: i * 1000,
+            duration: 1000,
+            rendering_offset: 0,
+            is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![i as u8; 10]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    let mut data = writer.into_writer().into_inner();
+
+    // Add a second sample description used from the third chunk on. The moov
+    // is at the end of the file, so rewriting it leaves the chunk offsets intact.
+    let size = data.len() as u64;
+    let mut moov = Mp4Reader::read_header(Cursor::new(&data), size)
+        .unwrap()
+        .moov;
+    let stbl = &mut moov.traks[0].mdia.minf.stbl;
+    stbl.stsd.entries.push(mp4::SampleEntry::Hev1(
+        mp4::Hev1Box::new(&mp4::HevcConfig {
+            width: 1280,
+            height: 720,
+            ..hevc_config
+        })
+        .unwrap(),
+    ));
+    assert_eq!(stbl.stsc.entries.len(), 1);
+    let mut entry = stbl.stsc.entries[0].clone();
+    entry.first_chunk = 3;
+    entry.first_sample = 3;
+    entry.sample_description_index = 2;
+    stbl.stsc.entries.push(entry);
+
+    let moov_start = data.len() - moov_size(&data);
+    data.truncate(moov_start);
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.sample_entries().len(), 2);
+    for (sample_id, index) in [(1, 1), (2, 1), (3, 2), (4, 2)] {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.sample_description_index, index);
+        assert_eq!(sample.bytes[0], sample_id as u8 - 1);
+    }
+}
+
+#[test]
+fn test_read_zero_first_chunk() {
+    let mut data = write_faststart(None);
+    let size = data.len() as u64;
+    let mut moov = Mp4Reader::read_header(Cursor::new(&data), size)
+        .unwrap()
+        .moov;
+    moov.traks[0].mdia.minf.stbl.stsc.entries[0].first_chunk = 0;
+    let moov_start = data.len() - moov_size(&data);
+    data.truncate(moov_start);
+    moov.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert!(!matches!(mp4.read_sample(1, 1), Ok(Some(_))));
+}
+
+fn moov_size(data: &[u8]) -> usize {
+    let mut reader = Cursor::new(data);
+    let mut size = 0;
+    while (reader.position() as usize) < data.len() {
+        let header = mp4::BoxHeader::read(&mut reader).unwrap();
+        size = header.size as usize;
+        reader.set_position(reader.position() + header.size - mp4::HEADER_SIZE);
+    }
+    size
+}
+
+#[test]
+fn test_read_overflowing_trun_sample_count() {
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::<u8>::new()), &Default::default())
+            .unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_init_segment().unwrap();
+    let mut data = writer.into_writer().into_inner();
+
+    let traf = mp4::TrafBox {
+        tfhd: mp4::TfhdBox {
+            flags: mp4::TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF | mp4::TfhdBox::FLAG_DEFAULT_SAMPLE_SIZE,
+            track_id: 1,
+            default_sample_size: Some(1000),
+            ..Default::default()
+        },
+        truns: vec![mp4::TrunBox {
+            sample_count: u32::MAX,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let moof = mp4::MoofBox {
+        mfhd: mp4::MfhdBox {
+            sequence_number: 1,
+            ..Default::default()
+        },
+        psshs: vec![],
+        trafs: vec![traf.clone(), traf],
+    };
+    moof.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), u32::MAX);
+}
+
+#[test]
+fn test_read_multiple_truns_and_trafs() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso5").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso5").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_init_segment().unwrap();
+    let mut data = writer.into_writer().into_inner();
+
+    // One moof with two trafs for the same track. The first traf has two runs,
+    // the second of which continues right after the first one.
+    let tfhd = mp4::TfhdBox {
+        flags: mp4::TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF
+            | mp4::TfhdBox::FLAG_DEFAULT_SAMPLE_DURATION
+            | mp4::TfhdBox::FLAG_DEFAULT_SAMPLE_SIZE,
+        track_id: 1,
+        default_sample_duration: Some(50),
+        default_sample_size: Some(7),
+        ..Default::default()
+    };
+    let mut moof = mp4::MoofBox {
+        mfhd: mp4::MfhdBox {
+            sequence_number: 1,
+            ..Default::default()
+        },
+        psshs: vec![],
+        trafs: vec![
+            mp4::TrafBox {
+                tfhd: tfhd.clone(),
+                tfdt: Some(mp4::TfdtBox::default()),
+                truns: vec![
+                    mp4::TrunBox {
+                        flags: mp4::TrunBox::FLAG_DATA_OFFSET
+                            | mp4::TrunBox::FLAG_SAMPLE_DURATION
+                            | mp4::TrunBox::FLAG_SAMPLE_SIZE,
+                        sample_count: 2,
+                        data_offset: Some(0),
+                        sample_durations: vec![100, 100],
+                        sample_sizes: vec![10, 20],
+                        ..Default::default()
+                    },
+                    mp4::TrunBox {
+                        sample_count: 1,
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            mp4::TrafBox {
+                tfhd: mp4::TfhdBox {
+                    default_sample_duration: Some(100),
+                    default_sample_size: Some(5),
+                    ..tfhd
+                },
+                tfdt: None,
+                truns: vec![mp4::TrunBox {
+                    flags: mp4::TrunBox::FLAG_DATA_OFFSET,
+                    sample_count: 2,
+                    data_offset: Some(0),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ],
+    };
+    let data_start = moof.box_size() as i32 + 8;
+    moof.trafs[0].truns[0].data_offset = Some(data_start);
+    moof.trafs[1].truns[0].data_offset = Some(data_start + 37);
+    moof.write_box(&mut data).unwrap();
+
+    let sizes = [10, 20, 7, 5, 5];
+    let payload: Vec<u8> = sizes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, size)| vec![i as u8 + 1; *size])
+        .collect();
+    mp4::BoxHeader::new(mp4::BoxType::MdatBox, 8 + payload.len() as u64)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&payload);
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), 5);
+
+    let expected = [(0, 100), (100, 100), (200, 50), (250, 100), (350, 100)];
+    for (i, (start_time, duration)) in expected.iter().enumerate() {
+        let sample_id = i as u32 + 1;
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.start_time, *start_time);
+        assert_eq!(sample.duration, *duration);
+        assert_eq!(sample.bytes.len(), sizes[i]);
+        assert!(sample.bytes.iter().all(|b| *b == sample_id as u8));
+    }
+}
+
+#[test]
+fn test_read_fragment_sample_flags() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso5").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso5").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_init_segment().unwrap();
+    let mut data = writer.into_writer().into_inner();
+
+    let sync = mp4::SampleFlags {
+        depends_on: 2,
+        ..Default::default()
+    };
+    let non_sync = mp4::SampleFlags {
+        depends_on: 1,
+        is_non_sync: true,
+        ..Default::default()
+    };
+    assert_eq!(mp4::SampleFlags::from(u32::from(non_sync)), non_sync);
+
+    // The first run marks only its first sample as sync and relies on the
+    // tfhd default for the rest; the second run carries per-sample flags.
+    let mut moof = mp4::MoofBox {
+        mfhd: mp4::MfhdBox {
+            sequence_number: 1,
+            ..Default::default()
+        },
+        psshs: vec![],
+        trafs: vec![mp4::TrafBox {
+            tfhd: mp4::TfhdBox {
+                flags: mp4::TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF
+                    | mp4::TfhdBox::FLAG_DEFAULT_SAMPLE_DURATION
+                    | mp4::TfhdBox::FLAG_DEFAULT_SAMPLE_SIZE
+                    | mp4::TfhdBox::FLAG_DEFAULT_SAMPLE_FLAGS,
+                track_id: 1,
+                default_sample_duration: Some(100),
+                default_sample_size: Some(4),
+                default_sample_flags: Some(non_sync.into()),
+                ..Default::default()
+            },
+            tfdt: Some(mp4::TfdtBox::default()),
+            truns: vec![
+                mp4::TrunBox {
+                    flags: mp4::TrunBox::FLAG_DATA_OFFSET | mp4::TrunBox::FLAG_FIRST_SAMPLE_FLAGS,
+                    sample_count: 3,
+                    data_offset: Some(0),
+                    first_sample_flags: Some(sync.into()),
+                    ..Default::default()
+                },
+                mp4::TrunBox {
+                    flags: mp4::TrunBox::FLAG_SAMPLE_FLAGS,
+                    sample_count: 2,
+                    sample_flags: vec![non_sync.into(), sync.into()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }],
+    };
+    let data_start = moof.box_size() as i32 + 8;
+    moof.trafs[0].truns[0].data_offset = Some(data_start);
+    moof.write_box(&mut data).unwrap();
+
+    let payload = vec![0u8; 5 * 4];
+    mp4::BoxHeader::new(mp4::BoxType::MdatBox, 8 + payload.len() as u64)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&payload);
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), 5);
+
+    let expected = [sync, non_sync, non_sync, non_sync, sync];
+    for (i, flags) in expected.iter().enumerate() {
+        let sample = mp4.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        assert_eq!(sample.sample_flags, Some(*flags));
+        assert_eq!(sample.is_sync, flags.is_sync());
+    }
+}
+
+#[test]
+fn test_read_per_track_trex_defaults() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso5").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso5").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let media_conf = mp4::MediaConfig::AvcConfig(mp4::AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0d, 0xac, 0xd9, 0x41, 0x41, 0xfa, 0x10],
+        pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb, 0x22, 0xc0],
+    });
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_init_segment().unwrap();
+    let init = writer.into_writer().into_inner();
+    let size = init.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(init), size).unwrap();
+    assert_eq!(mp4.moov.mvex.as_ref().unwrap().trexs.len(), 2);
+
+    // The fragment relies on the trex of each track for all sample values.
+    let sync = mp4::SampleFlags {
+        depends_on: 2,
+        ..Default::default()
+    };
+    let non_sync = mp4::SampleFlags {
+        depends_on: 1,
+        is_non_sync: true,
+        ..Default::default()
+    };
+    let mut moov = mp4.moov.clone();
+    moov.mvex.as_mut().unwrap().trexs = vec![
+        mp4::TrexBox {
+            track_id: 2,
+            default_sample_description_index: 1,
+            default_sample_duration: 1024,
+            default_sample_size: 6,
+            default_sample_flags: sync.into(),
+            ..Default::default()
+        },
+        mp4::TrexBox {
+            track_id: 1,
+            default_sample_description_index: 1,
+            default_sample_duration: 40,
+            default_sample_size: 10,
+            default_sample_flags: non_sync.into(),
+            ..Default::default()
+        },
+    ];
+    let mut data = Vec::new();
+    mp4.ftyp.write_box(&mut data).unwrap();
+    moov.write_box(&mut data).unwrap();
+
+    let traf = |track_id: u32, sample_count: u32| mp4::TrafBox {
+        tfhd: mp4::TfhdBox {
+            flags: mp4::TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+            track_id,
+            ..Default::default()
+        },
+        tfdt: Some(mp4::TfdtBox::default()),
+        truns: vec![mp4::TrunBox {
+            flags: mp4::TrunBox::FLAG_DATA_OFFSET,
+            sample_count,
+            data_offset: Some(0),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let mut moof = mp4::MoofBox {
+        mfhd: mp4::MfhdBox::default(),
+        psshs: vec![],
+        trafs: vec![traf(1, 3), traf(2, 2)],
+    };
+    let data_start = moof.box_size() as i32 + 8;
+    moof.trafs[0].truns[0].data_offset = Some(data_start);
+    moof.trafs[1].truns[0].data_offset = Some(data_start + 3 * 10);
+    moof.write_box(&mut data).unwrap();
+
+    let mut payload = vec![1u8; 3 * 10];
+    payload.extend_from_slice(&[2u8; 2 * 6]);
+    mp4::BoxHeader::new(mp4::BoxType::MdatBox, 8 + payload.len() as u64)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&payload);
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), 3);
+    assert_eq!(mp4.sample_count(2).unwrap(), 2);
+
+    for n in 0..3u32 {
+        let sample = mp4.read_sample(1, n + 1).unwrap().unwrap();
+        assert_eq!(sample.start_time, n as u64 * 40);
+        assert_eq!(sample.duration, 40);
+        assert_eq!(sample.sample_flags, Some(non_sync));
+        assert_eq!(sample.sample_description_index, 1);
+        assert_eq!(sample.bytes, mp4::Bytes::from(vec![1u8; 10]));
+    }
+    for n in 0..2u32 {
+        let sample = mp4.read_sample(2, n + 1).unwrap().unwrap();
+        assert_eq!(sample.start_time, n as u64 * 1024);
+        assert_eq!(sample.duration, 1024);
+        assert!(sample.is_sync);
+        assert_eq!(sample.bytes, mp4::Bytes::from(vec![2u8; 6]));
+    }
+}
+
+#[test]
+fn test_write_edit_list() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    let mut track_conf = mp4::TrackConfig::from(media_conf);
+    track_conf.timescale = 2000;
+    // Start presenting after 500ms and skip the first 100 ticks (priming).
+    track_conf.edit_list = vec![
+        mp4::ElstEntry {
+            segment_duration: 500,
+            media_time: -1,
+            media_rate: 1,
+            media_rate_fraction: 0,
+        },
+        mp4::ElstEntry {
+            segment_duration: 0,
+            media_time: 100,
+            media_rate: 1,
+            media_rate_fraction: 0,
+        },
+    ];
+    writer.add_track(&track_conf).unwrap();
+    for i in 0..10u64 {
+        let sample = mp4::Mp4Sample {
+            start_time: i * 200,
+            duration: 200,
+            rendering_offset: 0,
+            is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![0x0u8; 8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.duration(), Duration::from_millis(1450));
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.edit_list().len(), 2);
+    assert_eq!(track.edit_list()[1].segment_duration, 950);
+    assert_eq!(
+        track.presentation_duration().unwrap(),
+        Duration::from_millis(1450)
+    );
+    assert_eq!(
+        track.edit_segments().unwrap(),
+        vec![
+            mp4::EditSegment {
+                presentation_time: 0,
+                duration: 1000,
+                media_time: None,
+                media_rate: 1 << 16,
+            },
+            mp4::EditSegment {
+                presentation_time: 1000,
+                duration: 1900,
+                media_time: Some(100),
+                media_rate: 1 << 16,
+            },
+        ]
+    );
+
+    assert_eq!(track.presentation_time(0).unwrap(), None);
+    assert_eq!(track.presentation_time(100).unwrap(), Some(1000));
+    assert_eq!(track.presentation_time(1999).unwrap(), Some(2899));
+    assert_eq!(track.presentation_time(2000).unwrap(), None);
+    assert_eq!(track.media_time(0).unwrap(), None);
+    assert_eq!(track.media_time(1000).unwrap(), Some(100));
+    assert_eq!(track.media_time(2900).unwrap(), None);
+}
+
+#[test]
+fn test_read_overflowing_edit_list() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer = mp4::Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    let mut track_conf = mp4::TrackConfig::from(mp4::AacConfig::default());
+    track_conf.timescale = 48000;
+    track_conf.edit_list = vec![mp4::ElstEntry {
+        segment_duration: u64::MAX,
+        media_time: 0,
+        media_rate: 1,
+        media_rate_fraction: 0,
+    }];
+    writer.add_track(&track_conf).unwrap();
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert!(track.edit_segments().is_err());
+    assert!(track.presentation_time(0).is_err());
+    assert!(track.media_time(0).is_err());
+    assert!(track.presentation_duration().is_err());
+    assert!(mp4.sample_at_time(1, Duration::ZERO).is_err());
+}
+
+#[test]
+fn test_read_edit_list() {
+    let mp4 = get_reader("tests/samples/minimal.mp4");
+
+    // The AAC track skips 1024 samples of encoder priming.
+    let track = mp4.tracks().get(&2).unwrap();
+    assert_eq!(track.timescale(), 48000);
+    assert_eq!(
+        track.edit_list(),
+        &[mp4::ElstEntry {
+            segment_duration: 40,
+            media_time: 1024,
+            media_rate: 1,
+            media_rate_fraction: 0,
+        }]
+    );
+    assert_eq!(track.presentation_time(0).unwrap(), None);
+    assert_eq!(track.presentation_time(1024).unwrap(), Some(0));
+    assert_eq!(track.media_time(960).unwrap(), Some(1984));
+    assert_eq!(
+        track.presentation_duration().unwrap(),
+        Duration::from_millis(40)
+    );
+}
+
+#[test]
+fn test_seek_by_time() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    let mut track_conf = mp4::TrackConfig::from(media_conf);
+    track_conf.edit_list = vec![mp4::ElstEntry {
+        segment_duration: 0,
+        media_time: 100,
+        media_rate: 1,
+        media_rate_fraction: 0,
+    }];
+    writer.add_track(&track_conf).unwrap();
+
+    // GOPs of I P B B in decode order, presented as I B B P.
+    let offsets = [100, 300, 0, 0];
+    for i in 0..10u64 {
+        let sample = mp4::Mp4Sample {
+            start_time: i * 100,
+            duration: 100,
+            rendering_offset: offsets[i as usize % 4],
+            is_sync: i % 4 == 0,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![0x0u8; 8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+
+    let expected = [
+        (0, Some(1), Some(1)),
+        (100, Some(3), Some(1)),
+        (250, Some(4), Some(1)),
+        (300, Some(2), Some(1)),
+        (450, Some(5), Some(5)),
+        (700, Some(6), Some(5)),
+        (850, Some(9), Some(9)),
+        (950, None, None),
+    ];
+    for (millis, sample_id, sync_sample_id) in expected {
+        let time = Duration::from_millis(millis);
+        assert_eq!(mp4.sample_at_time(1, time).unwrap(), sample_id);
+        assert_eq!(mp4.sync_sample_before(1, time).unwrap(), sync_sample_id);
+    }
+    assert!(matches!(
+        mp4.sample_at_time(2, Duration::ZERO),
+        Err(mp4::Error::TrakNotFound(2))
+    ));
+}
+
+#[test]
+fn test_seek_fragments_by_time() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso5").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso5").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_init_segment().unwrap();
+    for i in 0..9u64 {
+        let sample = mp4::Mp4Sample {
+            start_time: i * 100,
+            duration: 100,
+            rendering_offset: 0,
+            is_sync: i % 3 == 0,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![0x0u8; 8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+        if i % 3 == 2 {
+            writer.flush_fragment().unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), 9);
+
+    let expected = [
+        (0, Some(1), Some(1)),
+        (250, Some(3), Some(1)),
+        (300, Some(4), Some(4)),
+        (899, Some(9), Some(7)),
+        (900, None, None),
+    ];
+    for (millis, sample_id, sync_sample_id) in expected {
+        let time = Duration::from_millis(millis);
+        assert_eq!(mp4.sample_at_time(1, time).unwrap(), sample_id);
+        assert_eq!(mp4.sync_sample_before(1, time).unwrap(), sync_sample_id);
+    }
+}
+
+#[test]
+fn test_sample_index() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let mut track_ids: Vec<u32> = mp4.tracks().keys().copied().collect();
+    track_ids.sort();
+    for track_id in track_ids {
+        let track = mp4.tracks().get(&track_id).unwrap();
+        let index = track.sample_index().to_vec();
+        assert_eq!(index.len() as u32, track.sample_count());
+        assert_eq!(track.sample_info(0), None);
+
+        let mut start_time = 0;
+        for (i, info) in index.iter().enumerate() {
+            let sample_id = i as u32 + 1;
+            assert_eq!(info.start_time, start_time);
+            start_time += info.duration as u64;
+            assert_eq!(mp4.sample_offset(track_id, sample_id).unwrap(), info.offset);
+
+            let sample = mp4.read_sample(track_id, sample_id).unwrap().unwrap();
+            assert_eq!(sample.start_time, info.start_time);
+            assert_eq!(sample.duration, info.duration);
+            assert_eq!(sample.rendering_offset, info.rendering_offset);
+            assert_eq!(sample.is_sync, info.is_sync);
+            assert_eq!(sample.bytes.len(), info.size as usize);
+        }
+    }
+}
+
+#[test]
+fn test_sample_index_truncated_fragment() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso5").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso5").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_init_segment().unwrap();
+    for i in 0..3u64 {
+        let sample = mp4::Mp4Sample {
+            start_time: i * 100,
+            duration: 100,
+            rendering_offset: 0,
+            is_sync: i == 0,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![i as u8; 8]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    // Cut off the data of the last sample.
+    let mut data = writer.into_writer().into_inner();
+    data.truncate(data.len() - 4);
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.sample_count(), 3);
+
+    let index = track.sample_index();
+    assert_eq!(index.len(), 2);
+    assert_eq!(index[1].offset, index[0].offset + 8);
+    assert_eq!(index[1].start_time, 100);
+    assert!(index[0].is_sync);
+    assert!(!index[1].is_sync);
+    assert_eq!(
+        index[1].sample_flags,
+        Some(mp4::SampleFlags {
+            depends_on: 1,
+            is_non_sync: true,
+            ..Default::default()
+        })
+    );
+}
+
+#[test]
+fn test_sample_iterators() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let sample_count = mp4.sample_count(1).unwrap();
+    let samples: Vec<mp4::Mp4Sample> = mp4.samples(1).unwrap().map(|s| s.unwrap()).collect();
+    assert_eq!(samples.len() as u32, sample_count);
+    for (i, sample) in samples.iter().enumerate() {
+        let expected = mp4.read_sample(1, i as u32 + 1).unwrap().unwrap();
+        assert_eq!(*sample, expected);
+    }
+    assert!(matches!(mp4.samples(3), Err(mp4::Error::TrakNotFound(3))));
+
+    let total: u32 = [1, 2].iter().map(|id| mp4.sample_count(*id).unwrap()).sum();
+    let indexes: Vec<Vec<mp4::SampleInfo>> = [1, 2]
+        .iter()
+        .map(|id| mp4.tracks()[id].sample_index().to_vec())
+        .collect();
+    let timescales: Vec<u64> = [1, 2]
+        .iter()
+        .map(|id| mp4.tracks()[id].timescale() as u64)
+        .collect();
+
+    let mut last_offset = 0;
+    let mut next_sample_ids = [1, 1];
+    let mut count = 0;
+    for item in mp4.interleaved_samples(mp4::SampleOrder::FileOffset) {
+        let (track_id, sample) = item.unwrap();
+        let sample_id = next_sample_ids[track_id as usize - 1];
+        next_sample_ids[track_id as usize - 1] += 1;
+        let info = &indexes[track_id as usize - 1][sample_id as usize - 1];
+        assert!(info.offset >= last_offset);
+        assert_eq!(sample.start_time, info.start_time);
+        last_offset = info.offset;
+        count += 1;
+    }
+    assert_eq!(count, total);
+
+    let mut last_time = Duration::ZERO;
+    let mut count = 0;
+    for item in mp4.interleaved_samples(mp4::SampleOrder::DecodeTime) {
+        let (track_id, sample) = item.unwrap();
+        let timescale = timescales[track_id as usize - 1];
+        let time = Duration::from_nanos(sample.start_time * 1_000_000_000 / timescale);
+        assert!(time >= last_time);
+        last_time = time;
+        count += 1;
+    }
+    assert_eq!(count, total);
+}
+
+#[test]
+fn test_read_sample_zero_copy() {
+    let data = mp4::Bytes::from(fs::read("tests/samples/minimal.mp4").unwrap());
+    let range = data.as_ptr_range();
+    let mp4 = mp4::Mp4Reader::from_bytes(data.clone()).unwrap();
+    let mut reader = get_reader("tests/samples/minimal.mp4");
+
+    let mut buf = Vec::new();
+    for track_id in [1, 2] {
+        for sample_id in 1..=mp4.sample_count(track_id).unwrap() {
+            let sample = mp4.get_sample(track_id, sample_id).unwrap().unwrap();
+            assert!(range.contains(&sample.bytes.as_ptr()));
+            let expected = reader.read_sample(track_id, sample_id).unwrap().unwrap();
+            assert_eq!(sample, expected);
+            assert_eq!(sample.bytes, expected.bytes);
+
+            let info = reader
+                .read_sample_into(track_id, sample_id, &mut buf)
+                .unwrap()
+                .unwrap();
+            assert_eq!(buf, expected.bytes);
+            assert_eq!(info.size as usize, buf.len());
+            assert_eq!(info.start_time, expected.start_time);
+            assert_eq!(info.is_sync, expected.is_sync);
+        }
+    }
+    assert!(mp4.get_sample(1, 1000).unwrap().is_none());
+    assert!(reader
+        .read_sample_into(1, 1000, &mut buf)
+        .unwrap()
+        .is_none());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_read_sample_mmap() {
+    let file = File::open("tests/samples/minimal.mp4").unwrap();
+    let mp4 = unsafe { mp4::Mp4Reader::from_mmap(&file) }.unwrap();
+    let mut reader = get_reader("tests/samples/minimal.mp4");
+    for sample_id in 1..=mp4.sample_count(1).unwrap() {
+        let sample = mp4.get_sample(1, sample_id).unwrap().unwrap();
+        let expected = reader.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(sample.bytes, expected.bytes);
+    }
+}
+
+#[test]
+fn test_write_interleaved_chunks() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: Some(Duration::from_millis(500)),
+    };
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    let mut track_conf = mp4::TrackConfig::from(media_conf);
+    writer.add_track(&track_conf).unwrap();
+    track_conf.timescale = 48000;
+    writer.add_track(&track_conf).unwrap();
+
+    // Write 2 seconds of each track in decode order.
+    let tracks = [(1, 100, 20), (2, 1200, 80)];
+    let sample = |track_id: u32, duration: u32, i: u64| mp4::Mp4Sample {
+        start_time: i * duration as u64,
+        duration,
+        rendering_offset: 0,
+        is_sync: true,
+        sample_description_index: 1,
+        sample_flags: None,
+        bytes: mp4::Bytes::from(vec![track_id as u8; 4]),
+    };
+    let mut samples: Vec<_> = tracks
+        .iter()
+        .flat_map(|&(track_id, duration, count)| (0..count).map(move |i| (track_id, duration, i)))
+        .collect();
+    samples.sort_by_key(|&(track_id, duration, i)| {
+        let timescale = if track_id == 1 { 1000 } else { 48000 };
+        i * duration as u64 * 1000 / timescale
+    });
+    for (track_id, duration, i) in samples {
+        writer
+            .write_sample(track_id, &sample(track_id, duration, i))
+            .unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    for (track_id, _, count) in tracks {
+        let track = mp4.tracks().get(&track_id).unwrap();
+        assert_eq!(track.sample_count(), count as u32);
+        assert_eq!(
+            track
+                .trak()
+                .mdia
+                .minf
+                .stbl
+                .stco
+                .as_ref()
+                .unwrap()
+                .entries
+                .len(),
+            4
+        );
+    }
+
+    // Chunks of 500ms alternate between the tracks.
+    let mut last_time = Duration::ZERO;
+    let mut last_track_id = 0;
+    let mut switches = 0;
+    for item in mp4.interleaved_samples(mp4::SampleOrder::FileOffset) {
+        let (track_id, sample) = item.unwrap();
+        assert!(sample.bytes.iter().all(|b| *b == track_id as u8));
+        let timescale = if track_id == 1 { 1000 } else { 48000 };
+        let time = Duration::from_nanos(sample.start_time * 1_000_000_000 / timescale);
+        assert!(time + Duration::from_millis(500) > last_time);
+        if track_id != last_track_id {
+            switches += 1;
+        }
+        last_time = time;
+        last_track_id = track_id;
+    }
+    assert_eq!(switches, 8);
+}
+
+/// Writer that shares the number of bytes written so far, to check what has
+/// been written before the end of the file.
+struct SharedLenCursor {
+    inner: Cursor<Vec<u8>>,
+    len: Rc<Cell<usize>>,
+}
+
+impl std::io::Write for SharedLenCursor {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.len.set(self.inner.get_ref().len());
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl std::io::Seek for SharedLenCursor {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn test_write_interleaved_chunks_track_ends_early() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: Some(Duration::from_millis(500)),
+    };
+    let len = Rc::new(Cell::new(0));
+    let data = SharedLenCursor {
+        inner: Cursor::new(Vec::new()),
+        len: len.clone(),
+    };
+    let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+    let header_len = len.get();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    let track_conf = mp4::TrackConfig::from(media_conf);
+    writer.add_track(&track_conf).unwrap();
+    writer.add_track(&track_conf).unwrap();
+
+    // Track 2 ends after 300ms, in the middle of its first chunk, while
+    // track 1 goes on for 2 seconds.
+    let sample = |track_id: u32, i: u64| mp4::Mp4Sample {
+        start_time: i * 100,
+        duration: 100,
+        rendering_offset: 0,
+        is_sync: true,
+        sample_description_index: 1,
+        sample_flags: None,
+        bytes: mp4::Bytes::from(vec![track_id as u8; 4]),
+    };
+    for i in 0..20 {
+        writer.write_sample(1, &sample(1, i)).unwrap();
+        if i < 3 {
+            writer.write_sample(2, &sample(2, i)).unwrap();
+        }
+    }
+
+    // Every chunk is written before the end, not only the ones up to the
+    // open chunk of track 2.
+    assert_eq!(len.get(), header_len + (20 + 3) * 4);
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().inner.into_inner();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), 20);
+    assert_eq!(mp4.sample_count(2).unwrap(), 3);
+    for track_id in 1..=2 {
+        for sample_id in 1..=mp4.sample_count(track_id).unwrap() {
+            let sample = mp4.read_sample(track_id, sample_id).unwrap().unwrap();
+            assert_eq!(sample.bytes, vec![track_id as u8; 4]);
+        }
+    }
+}
+
+#[test]
+fn test_write_chunk_strategies() {
+    let strategies = [
+        (mp4::ChunkStrategy::SampleCount(3), vec![(1, 3), (4, 1)], 4),
+        (
+            mp4::ChunkStrategy::Duration(Duration::from_millis(250)),
+            vec![(1, 3), (4, 1)],
+            4,
+        ),
+        (mp4::ChunkStrategy::ByteSize(10), vec![(1, 3), (4, 1)], 4),
+        (mp4::ChunkStrategy::SingleSample, vec![(1, 1)], 10),
+    ];
+    for (strategy, stsc, chunk_count) in strategies {
+        let config = mp4::Mp4Config {
+            major_brand: str::parse("isom").unwrap(),
+            minor_version: 512,
+            compatible_brands: vec![str::parse("isom").unwrap()],
+            timescale: 1000,
+            reserved_moov_size: None,
+            interleave_duration: None,
+        };
+        let data = Cursor::new(Vec::<u8>::new());
+        let mut writer = mp4::Mp4Writer::write_start(data, &config).unwrap();
+        let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+        let mut track_conf = mp4::TrackConfig::from(media_conf);
+        track_conf.chunk_strategy = Some(strategy);
+        writer.add_track(&track_conf).unwrap();
+        for i in 0..10u64 {
+            let sample = mp4::Mp4Sample {
+                start_time: i * 100,
+                duration: 100,
+                rendering_offset: 0,
+                is_sync: true,
+                sample_description_index: 1,
+                sample_flags: None,
+                bytes: mp4::Bytes::from(vec![i as u8; 4]),
+            };
+            writer.write_sample(1, &sample).unwrap();
+        }
+        writer.write_end().unwrap();
+
+        let data = writer.into_writer().into_inner();
+        let size = data.len() as u64;
+        let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        let stbl = &mp4.tracks().get(&1).unwrap().trak().mdia.minf.stbl;
+        let entries: Vec<(u32, u32)> = stbl
+            .stsc
+            .entries
+            .iter()
+            .map(|entry| (entry.first_chunk, entry.samples_per_chunk))
+            .collect();
+        assert_eq!(entries, stsc, "{:?}", strategy);
+        assert_eq!(stbl.stco.as_ref().unwrap().entries.len(), chunk_count);
+        for i in 0..10u32 {
+            let sample = mp4.read_sample(1, i + 1).unwrap().unwrap();
+            assert_eq!(sample.bytes, vec![i as u8; 4]);
+        }
+    }
+}
+
+fn protect_first_track(moov: &mut mp4::MoovBox, tenc: mp4::TencBox) {
+    let stsd = &mut moov.traks[0].mdia.minf.stbl.stsd;
+    let entry = stsd.entries.remove(0);
+    let sinf = mp4::SinfBox {
+        schm: Some(mp4::SchmBox {
+            scheme_type: str::parse("cenc").unwrap(),
+            scheme_version: 0x10000,
+            ..Default::default()
+        }),
+        schi: Some(mp4::SchiBox {
+            tenc: Some(tenc),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    stsd.entries.insert(
+        0,
+        mp4::SampleEntry::Encrypted(mp4::EncryptedSampleEntry::new(entry, sinf)),
+    );
+    moov.psshs.push(mp4::PsshBox {
+        system_id: [0x10; 16],
+        data: vec![1, 2, 3, 4],
+        ..Default::default()
+    });
+}
+
+fn sample_iv(i: u8) -> mp4::SampleEncryption {
+    mp4::SampleEncryption {
+        iv: vec![i; 8],
+        subsamples: vec![],
+    }
+}
+
+#[test]
+fn test_read_encrypted_fragments() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer =
+        mp4::Mp4FragmentedWriter::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    writer
+        .add_track(&mp4::TrackConfig::from(media_conf))
+        .unwrap();
+    writer.write_init_segment().unwrap();
+    let init = writer.into_writer().into_inner();
+    let size = init.len() as u64;
+    let init = Mp4Reader::read_header(Cursor::new(init), size).unwrap();
+
+    let mut moov = init.moov.clone();
+    protect_first_track(
+        &mut moov,
+        mp4::TencBox {
+            default_is_protected: 1,
+            default_per_sample_iv_size: 8,
+            default_kid: [0xab; 16],
+            ..Default::default()
+        },
+    );
+    let mut data = Vec::new();
+    init.ftyp.write_box(&mut data).unwrap();
+    moov.write_box(&mut data).unwrap();
+
+    let tfhd = mp4::TfhdBox {
+        flags: mp4::TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF
+            | mp4::TfhdBox::FLAG_DEFAULT_SAMPLE_DURATION
+            | mp4::TfhdBox::FLAG_DEFAULT_SAMPLE_SIZE,
+        track_id: 1,
+        default_sample_duration: Some(100),
+        default_sample_size: Some(4),
+        ..Default::default()
+    };
+
+    // The first fragment carries its IVs and subsample ranges in senc.
+    let mut first = vec![sample_iv(1), sample_iv(2), sample_iv(3)];
+    first[1].subsamples = vec![mp4::SubsampleEntry {
+        bytes_of_clear_data: 1,
+        bytes_of_protected_data: 3,
+    }];
+    let mut moof = mp4::MoofBox {
+        mfhd: mp4::MfhdBox {
+            sequence_number: 1,
+            ..Default::default()
+        },
+        psshs: vec![],
+        trafs: vec![mp4::TrafBox {
+            tfhd: tfhd.clone(),
+            tfdt: Some(mp4::TfdtBox::default()),
+            truns: vec![mp4::TrunBox {
+                flags: mp4::TrunBox::FLAG_DATA_OFFSET,
+                sample_count: 3,
+                data_offset: Some(0),
+                ..Default::default()
+            }],
+            senc: Some(mp4::SencBox::new(&first)),
+            ..Default::default()
+        }],
+    };
+    moof.trafs[0].truns[0].data_offset = Some(moof.box_size() as i32 + 8);
+    moof.write_box(&mut data).unwrap();
+    mp4::BoxHeader::new(mp4::BoxType::MdatBox, 8 + 3 * 4)
+        .write(&mut data)
+        .unwrap();
+    data.extend_from_slice(&[1; 12]);
+
+    // The second one points at auxiliary information at the start of mdat.
+    let second = [sample_iv(4), sample_iv(5)];
+    let mut moof = mp4::MoofBox {
+        mfhd: mp4::MfhdBox {
+            sequence_number: 2,
+            ..Default::default()
+        },
+        psshs: vec![],
+        trafs: vec![mp4::TrafBox {
+            tfhd,
+            tfdt: Some(mp4::TfdtBox {
+                base_media_decode_time: 300,
+                ..Default::default()
+            }),
+            truns: vec![mp4::TrunBox {
+                flags: mp4::TrunBox::FLAG_DATA_OFFSET,
+                sample_count: 2,
+                data_offset: Some(0),
+                ..Default::default()
+            }],
+            saizs: vec![mp4::SaizBox {
+                default_sample_info_size: 8,
+                sample_count: 2,
+                ..Default::default()
+            }],
+            saios: vec![mp4::SaioBox {
+                offsets: vec![0],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    };
+    let aux_start = moof.box_size() + 8;
+    moof.trafs[0].saios[0].offsets[0] = aux_start;
+    moof.trafs[0].truns[0].data_offset = Some(aux_start as i32 + 16);
+    moof.write_box(&mut data).unwrap();
+    mp4::BoxHeader::new(mp4::BoxType::MdatBox, 8 + 16 + 2 * 4)
+        .write(&mut data)
+        .unwrap();
+    for entry in second.iter() {
+        data.extend_from_slice(&entry.iv);
+    }
+    data.extend_from_slice(&[2; 8]);
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.moov.psshs.len(), 1);
+    assert_eq!(mp4.moov.psshs[0].data, vec![1, 2, 3, 4]);
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::AAC);
+    assert_eq!(track.box_type().unwrap(), mp4::BoxType::EncaBox.into());
+    let sinf = track.sinf().unwrap();
+    assert_eq!(sinf.frma.data_format, str::parse("mp4a").unwrap());
+    assert_eq!(sinf.scheme_type(), Some(str::parse("cenc").unwrap()));
+    assert_eq!(sinf.tenc().unwrap().default_kid, [0xab; 16]);
+
+    let expected: Vec<_> = first.iter().chain(second.iter()).cloned().collect();
+    for (i, entry) in expected.iter().enumerate() {
+        let sample_id = i as u32 + 1;
+        let info = mp4.sample_encryption(1, sample_id).unwrap();
+        assert_eq!(info.as_ref(), Some(entry));
+        assert!(mp4.read_sample(1, sample_id).unwrap().is_some());
+    }
+    let sample = mp4.read_sample(1, 5).unwrap().unwrap();
+    assert_eq!(sample.bytes, vec![2; 4]);
+}
+
+#[test]
+fn test_read_encrypted_samples() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let mut writer = mp4::Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    let media_conf = mp4::MediaConfig::AacConfig(mp4::AacConfig::default());
+    let mut track_conf = mp4::TrackConfig::from(media_conf);
+    track_conf.chunk_strategy = Some(mp4::ChunkStrategy::SampleCount(3));
+    writer.add_track(&track_conf).unwrap();
+    for i in 0..10u64 {
+        let sample = mp4::Mp4Sample {
+            start_time: i * 100,
+            duration: 100,
+            rendering_offset: 0,
+            is_sync: true,
+            sample_description_index: 1,
+            sample_flags: None,
+            bytes: mp4::Bytes::from(vec![i as u8; 4]),
+        };
+        writer.write_sample(1, &sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    let data = writer.into_writer().into_inner();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    let moov_start = size - mp4.moov.box_size();
+
+    // Replace moov with a free box holding the auxiliary information and a
+    // protected moov, once with a single saio offset and once with an offset
+    // per chunk. Auxiliary information of another type is not used, and is
+    // stored ahead of the scheme's to check that saiz and saio are paired.
+    for (per_chunk, aux_info_type) in [(false, None), (true, Some("cenc")), (true, Some("abcd"))] {
+        let mut data = data[..moov_start as usize].to_vec();
+        mp4::BoxHeader::new(mp4::BoxType::FreeBox, 8 + 5 * 8 + 5 * 16)
+            .write(&mut data)
+            .unwrap();
+        let mut sizes = Vec::new();
+        let mut chunk_offsets = Vec::new();
+        for i in 0..10u8 {
+            if i % 3 == 0 {
+                chunk_offsets.push(data.len() as u64);
+            }
+            let start = data.len();
+            data.extend_from_slice(&[i; 8]);
+            if i % 2 == 1 {
+                // One subsample: 1 clear byte and 3 protected bytes.
+                data.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 3]);
+            }
+            sizes.push((data.len() - start) as u8);
+        }
+
+        let mut moov = mp4.moov.clone();
+        protect_first_track(
+            &mut moov,
+            mp4::TencBox {
+                default_is_protected: 1,
+                default_per_sample_iv_size: 8,
+                ..Default::default()
+            },
+        );
+        let stbl = &mut moov.traks[0].mdia.minf.stbl;
+        let offsets = if per_chunk {
+            chunk_offsets
+        } else {
+            vec![moov_start + 8]
+        };
+        stbl.saizs = vec![
+            mp4::SaizBox {
+                flags: mp4::SaizBox::FLAG_AUX_INFO_TYPE,
+                aux_info_type: str::parse("abcd").unwrap(),
+                aux_info_type_parameter: 1,
+                default_sample_info_size: 1,
+                sample_count: 10,
+                ..Default::default()
+            },
+            mp4::SaizBox {
+                sample_count: 10,
+                sample_info_sizes: sizes.clone(),
+                ..Default::default()
+            },
+        ];
+        stbl.saios = vec![
+            mp4::SaioBox {
+                flags: mp4::SaioBox::FLAG_AUX_INFO_TYPE,
+                aux_info_type: str::parse("abcd").unwrap(),
+                aux_info_type_parameter: 1,
+                offsets: vec![0],
+                ..Default::default()
+            },
+            mp4::SaioBox {
+                offsets,
+                ..Default::default()
+            },
+        ];
+        if let Some(aux_info_type) = aux_info_type {
+            let saiz = &mut stbl.saizs[1];
+            saiz.flags = mp4::SaizBox::FLAG_AUX_INFO_TYPE;
+            saiz.aux_info_type = str::parse(aux_info_type).unwrap();
+        }
+        moov.write_box(&mut data).unwrap();
+
+        let size = data.len() as u64;
+        let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        if aux_info_type == Some("abcd") {
+            assert!(matches!(
+                mp4.sample_encryption(1, 1),
+                Err(mp4::Error::BoxInStblNotFound(1, mp4::BoxType::SaizBox))
+            ));
+            continue;
+        }
+        for i in 0..10u8 {
+            let info = mp4.sample_encryption(1, i as u32 + 1).unwrap().unwrap();
+            assert_eq!(info.iv, vec![i; 8], "per_chunk: {}", per_chunk);
+            let expected = if i % 2 == 1 {
+                vec![mp4::SubsampleEntry {
+                    bytes_of_clear_data: 1,
+                    bytes_of_protected_data: 3,
+                }]
+            } else {
+                vec![]
+            };
+            assert_eq!(info.subsamples, expected);
+        }
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+#[test]
+fn test_add_encrypted_track_without_feature() {
+    let data = Cursor::new(Vec::<u8>::new());
+    let mut writer = mp4::Mp4Writer::write_start(data, &mp4::Mp4Config::default()).unwrap();
+    let mut config = mp4::TrackConfig::from(mp4::AacConfig::default());
+    config.encryption = Some(mp4::EncryptionConfig {
+        scheme: mp4::EncryptionScheme::Cenc,
+        key_id: [0x01; 16],
+        key: [0xa1; 16],
+        iv: [0xb1; 16],
+    });
+    assert!(writer.add_track(&config).is_err());
+}
+
+#[cfg(feature = "encryption")]
+const VIDEO_KEY_ID: [u8; 16] = [0x01; 16];
+#[cfg(feature = "encryption")]
+const AUDIO_KEY_ID: [u8; 16] = [0x02; 16];
+
+#[cfg(feature = "encryption")]
+fn encrypted_track_configs(
+    video_scheme: mp4::EncryptionScheme,
+    audio_scheme: mp4::EncryptionScheme,
+) -> [mp4::TrackConfig; 2] {
+    let mut video = mp4::TrackConfig::from(mp4::AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0d, 0xac, 0xd9, 0x41, 0x41, 0xfa, 0x10],
+        pic_param_set: vec![0x68, 0xeb, 0xe3, 0xcb, 0x22, 0xc0],
+    });
+    video.encryption = Some(mp4::EncryptionConfig {
+        scheme: video_scheme,
+        key_id: VIDEO_KEY_ID,
+        key: [0xa1; 16],
+        iv: [0xb1; 16],
+    });
+    let mut audio = mp4::TrackConfig::from(mp4::AacConfig::default());
+    audio.encryption = Some(mp4::EncryptionConfig {
+        scheme: audio_scheme,
+        key_id: AUDIO_KEY_ID,
+        key: [0xa2; 16],
+        iv: [0xb2; 16],
+    });
+    [video, audio]
+}
+
+#[cfg(feature = "encryption")]
+fn encryption_keys() -> std::collections::HashMap<[u8; 16], [u8; 16]> {
+    vec![(VIDEO_KEY_ID, [0xa1; 16]), (AUDIO_KEY_ID, [0xa2; 16])]
+        .into_iter()
+        .collect()
+}
+
+/// Returns a clear test sample: length prefixed NAL units for video,
+/// raw bytes for audio.
+#[cfg(feature = "encryption")]
+fn clear_sample(track_id: u32, n: u32) -> mp4::Mp4Sample {
+    let is_sync = track_id == 2 || n % 4 == 0;
+    let bytes = if track_id == 1 {
+        let mut bytes = vec![0, 0, 0, 3, 0x06, 0x05, 0x01];
+        let slice_size = 100 + n as usize;
+        bytes.extend_from_slice(&(slice_size as u32).to_be_bytes());
+        bytes.push(if is_sync { 0x65 } else { 0x41 });
+        bytes.extend((1..slice_size).map(|i| (i + n as usize) as u8));
+        bytes
+    } else {
+        (0..40 + n).map(|i| (i * 3 + n) as u8).collect()
+    };
+    mp4::Mp4Sample {
+        start_time: n as u64 * 40,
+        duration: 40,
+        rendering_offset: 0,
+        is_sync,
+        sample_description_index: 1,
+        sample_flags: None,
+        bytes: mp4::Bytes::from(bytes),
+    }
+}
+
+#[cfg(feature = "encryption")]
+fn check_encrypted_samples(data: Vec<u8>, schemes: [mp4::EncryptionScheme; 2]) {
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+    for (track_id, scheme) in [1, 2].iter().zip(schemes.iter()) {
+        let track = mp4.tracks().get(track_id).unwrap();
+        let sinf = track.sinf().unwrap();
+        assert_eq!(sinf.scheme_type(), Some((*scheme).into()));
+        assert!(track.media_type().is_ok());
+    }
+    assert_eq!(
+        mp4.tracks().get(&1).unwrap().media_type().unwrap(),
+        MediaType::H264
+    );
+
+    let keys = encryption_keys();
+    for track_id in [1, 2] {
+        for n in 0..8 {
+            let expected = clear_sample(track_id, n);
+            let stored = mp4.read_sample(track_id, n + 1).unwrap().unwrap();
+            assert_ne!(stored.bytes, expected.bytes);
+            if track_id == 1 {
+                // NAL unit lengths and headers stay in the clear.
+                assert_eq!(stored.bytes[..12], expected.bytes[..12]);
+            }
+            let sample = mp4
+                .read_decrypted_sample(track_id, n + 1, &keys)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                sample.bytes, expected.bytes,
+                "track {} sample {}",
+                track_id, n
+            );
+        }
+    }
+
+    let err = mp4
+        .read_decrypted_sample(1, 1, &Default::default())
+        .unwrap_err();
+    assert!(matches!(err, mp4::Error::KeyNotFound(VIDEO_KEY_ID)));
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_write_encrypted_fragments() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: None,
+    };
+    let schemes = [mp4::EncryptionScheme::Cenc, mp4::EncryptionScheme::Cbcs];
+    let mut writer = mp4::Mp4FragmentedWriter::write_start(Vec::<u8>::new(), &config).unwrap();
+    for track_conf in encrypted_track_configs(schemes[0], schemes[1]).iter() {
+        writer.add_track(track_conf).unwrap();
+    }
+    for fragment in 0..2 {
+        for n in fragment * 4..fragment * 4 + 4 {
+            writer.write_sample(1, &clear_sample(1, n)).unwrap();
+            writer.write_sample(2, &clear_sample(2, n)).unwrap();
+        }
+        writer.flush_fragment().unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer();
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(Cursor::new(data.clone()), size).unwrap();
+    // Only the cenc track has per-sample IVs to store.
+    let trafs = &mp4.moofs[0].trafs;
+    assert!(trafs[0].senc.is_some() && trafs[0].saios.len() == 1);
+    assert!(trafs[1].senc.is_none());
+
+    check_encrypted_samples(data, schemes);
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_write_encrypted_samples() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+        reserved_moov_size: None,
+        interleave_duration: Some(Duration::from_millis(100)),
+    };
+    let schemes = [mp4::EncryptionScheme::Cbcs, mp4::EncryptionScheme::Cenc];
+    let mut writer = mp4::Mp4Writer::write_start(Cursor::new(Vec::<u8>::new()), &config).unwrap();
+    for track_conf in encrypted_track_configs(schemes[0], schemes[1]).iter() {
+        writer.add_track(track_conf).unwrap();
+    }
+    for n in 0..8 {
+        writer.write_sample(1, &clear_sample(1, n)).unwrap();
+        writer.write_sample(2, &clear_sample(2, n)).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    let data = writer.into_writer().into_inner();
+    check_encrypted_samples(data, schemes);
+}